@@ -168,9 +168,21 @@ fn build_env(source_template: tg::Template, manifest_subpath: &str) -> tg::Resul
 	Ok(env)
 }
 
+// Split a path in an artifact root into the artifact id and the subpath. The
+// roots are the ones `common`'s `ArtifactRoots` uses, from `artifact_roots`.
 fn parse_artifact_path(path: &str) -> Option<(tg::artifact::Id, String)> {
-	for root in ["/opt/tangram/artifacts/", "/.tangram/artifacts/"] {
-		let Some(rest) = path.strip_prefix(root) else {
+	let roots = artifact_roots::explicit().unwrap_or_else(|| {
+		std::env::current_exe()
+			.and_then(|exe| exe.canonicalize())
+			.map(|exe| artifact_roots::candidates(&exe))
+			.unwrap_or_else(|_| vec![artifact_roots::SANDBOX_ARTIFACTS_PATH.into()])
+	});
+	for root in &roots {
+		let Some(rest) = Path::new(path)
+			.strip_prefix(root)
+			.ok()
+			.and_then(Path::to_str)
+		else {
 			continue;
 		};
 		let (id_str, subpath) = match rest.find('/') {
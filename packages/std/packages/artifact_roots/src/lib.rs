@@ -0,0 +1,51 @@
+//! The directories that Tangram checks artifacts out to. `common`'s `ArtifactRoots` and `tgrustc` both get their roots from here.
+
+use std::path::{Path, PathBuf};
+
+/// The environment variable with an explicit, colon-separated list of artifact roots.
+pub const TANGRAM_ARTIFACTS_PATH: &str = "TANGRAM_ARTIFACTS_PATH";
+
+/// The artifact root of a sandbox.
+pub const SANDBOX_ARTIFACTS_PATH: &str = "/opt/tangram/artifacts";
+
+/// Get the roots in `TANGRAM_ARTIFACTS_PATH` if it is set, skipping empty entries.
+#[must_use]
+pub fn explicit() -> Option<Vec<PathBuf>> {
+	let path = std::env::var_os(TANGRAM_ARTIFACTS_PATH)?;
+	Some(
+		std::env::split_paths(&path)
+			.filter(|root| !root.as_os_str().is_empty())
+			.collect(),
+	)
+}
+
+/// Get the roots to search for an executable when they are not explicit: the `.tangram/artifacts` directories in the ancestors of the executable, closest-first, followed by the sandbox root. They may not exist.
+#[must_use]
+pub fn candidates(exe: &Path) -> Vec<PathBuf> {
+	let mut candidates: Vec<PathBuf> = exe
+		.ancestors()
+		.skip(1)
+		.map(|ancestor| ancestor.join(".tangram/artifacts"))
+		.collect();
+	candidates.push(PathBuf::from(SANDBOX_ARTIFACTS_PATH));
+	candidates
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::{Path, PathBuf};
+
+	#[test]
+	fn candidates() {
+		assert_eq!(
+			super::candidates(Path::new("/home/user/bin/tool")),
+			[
+				PathBuf::from("/home/user/bin/.tangram/artifacts"),
+				PathBuf::from("/home/user/.tangram/artifacts"),
+				PathBuf::from("/home/.tangram/artifacts"),
+				PathBuf::from("/.tangram/artifacts"),
+				PathBuf::from("/opt/tangram/artifacts"),
+			]
+		);
+	}
+}
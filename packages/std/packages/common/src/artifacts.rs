@@ -0,0 +1,197 @@
+use artifact_roots::SANDBOX_ARTIFACTS_PATH;
+pub use artifact_roots::TANGRAM_ARTIFACTS_PATH;
+use std::{
+	path::{Path, PathBuf},
+	sync::{LazyLock, Mutex},
+};
+use tangram_client::prelude::*;
+
+static ARTIFACT_ROOTS: LazyLock<ArtifactRoots> = LazyLock::new(ArtifactRoots::from_env);
+
+/// The directories that artifacts are checked out to, ordered closest-first.
+pub struct ArtifactRoots {
+	search: Mutex<Search>,
+
+	/// Whether the roots were given explicitly rather than discovered.
+	explicit: bool,
+}
+
+struct Search {
+	/// Roots discovered so far, ordered closest-first.
+	found: Vec<PathBuf>,
+
+	/// Candidates not yet probed.
+	pending: std::vec::IntoIter<PathBuf>,
+}
+
+impl ArtifactRoots {
+	/// Get the artifact roots of this process.
+	#[must_use]
+	pub fn global() -> &'static Self {
+		&ARTIFACT_ROOTS
+	}
+
+	/// Create artifact roots from the given list. Only these roots are used.
+	#[must_use]
+	pub fn new(roots: impl IntoIterator<Item = PathBuf>) -> Self {
+		Self {
+			search: Mutex::new(Search {
+				found: roots.into_iter().collect(),
+				pending: Vec::new().into_iter(),
+			}),
+			explicit: true,
+		}
+	}
+
+	/// Use the roots in `TANGRAM_ARTIFACTS_PATH` if it is set. Otherwise, search the `.tangram/artifacts` directories in the ancestors of the current executable followed by `/opt/tangram/artifacts`.
+	#[must_use]
+	pub fn from_env() -> Self {
+		if let Some(roots) = artifact_roots::explicit() {
+			return Self::new(roots);
+		}
+		let exe = std::env::current_exe()
+			.expect("failed to get the current executable")
+			.canonicalize()
+			.expect("failed to canonicalize the current executable");
+		Self {
+			search: Mutex::new(Search {
+				found: Vec::new(),
+				pending: artifact_roots::candidates(&exe).into_iter(),
+			}),
+			explicit: false,
+		}
+	}
+
+	/// Add a root that was created after startup, such as a new checkout. It is searched before the others.
+	pub fn add(&self, root: impl Into<PathBuf>) {
+		let root = root.into();
+		let mut search = self.search.lock().unwrap();
+		search.found.retain(|found| found != &root);
+		search.found.insert(0, root);
+	}
+
+	/// Return the i-th artifact root, probing discovered candidates only as far as needed.
+	#[must_use]
+	pub fn get(&self, index: usize) -> Option<PathBuf> {
+		let mut search = self.search.lock().unwrap();
+		while search.found.len() <= index {
+			let candidate = search.pending.next()?;
+			if candidate.is_dir() {
+				search.found.push(candidate);
+			}
+		}
+		search.found.get(index).cloned()
+	}
+
+	/// Iterate over the roots, closest-first.
+	pub fn roots(&self) -> impl Iterator<Item = PathBuf> + '_ {
+		(0..).map_while(|index| self.get(index))
+	}
+
+	/// Check whether a path is in an artifact root. Unless the roots are explicit, any `.tangram/artifacts` directory or the sandbox root counts.
+	#[must_use]
+	pub fn is_artifact_path(&self, path: &str) -> bool {
+		if !self.explicit
+			&& (path.contains("/.tangram/artifacts/")
+				|| path.contains(&format!("{SANDBOX_ARTIFACTS_PATH}/")))
+		{
+			return true;
+		}
+		self.roots().any(|root| Path::new(path).starts_with(&root))
+	}
+
+	/// Find the artifact that contains a path, which is the ancestor of the path directly under an artifact root.
+	#[must_use]
+	pub fn artifact_containing(&self, path: &Path) -> Option<PathBuf> {
+		let roots = self.unrender_roots();
+		path.ancestors()
+			.find(|ancestor| {
+				ancestor.parent().is_some_and(|parent| {
+					(!self.explicit && parent.ends_with(".tangram/artifacts"))
+						|| roots.iter().any(|root| parent == root)
+				})
+			})
+			.map(Path::to_path_buf)
+	}
+
+	/// Find the path of an artifact in the closest root that has it.
+	#[must_use]
+	pub fn path_for(&self, id: &tg::artifact::Id) -> Option<PathBuf> {
+		let suffix = id.to_string();
+		self.roots()
+			.map(|root| root.join(&suffix))
+			.find(|candidate| candidate.exists())
+	}
+
+	/// Render a [`tg::template::Data`] to a `String`, using the closest root that contains each artifact.
+	pub fn render_template_data(&self, data: &tg::template::Data) -> tg::Result<String> {
+		data.components
+			.iter()
+			.map(|component| match component {
+				tg::template::data::Component::String(string) => Ok(string.clone()),
+				tg::template::data::Component::Artifact(artifact_id) => {
+					let artifact_id = &artifact_id.item;
+					let path = self.path_for(artifact_id).ok_or_else(|| {
+						tg::error!("artifact {artifact_id} not present in any artifact root")
+					})?;
+					path.into_os_string().into_string().map_err(|os| {
+						tg::error!("artifact path is not valid UTF-8: {}", os.display())
+					})
+				},
+				tg::template::data::Component::Placeholder(data) => Ok(data.name.clone()),
+			})
+			.collect()
+	}
+
+	/// Get the roots that strings are unrendered with: the roots, followed by the sandbox root unless the roots are explicit.
+	#[must_use]
+	pub fn unrender_roots(&self) -> Vec<PathBuf> {
+		let mut roots: Vec<_> = self.roots().collect();
+		if !self.explicit {
+			roots.push(PathBuf::from(SANDBOX_ARTIFACTS_PATH));
+		}
+		roots
+	}
+
+	/// Unrender a template string into a [`tg::Template`], using the first root that it refers to.
+	pub fn unrender(&self, string: &str) -> tg::Result<tg::Template> {
+		for root in self.unrender_roots() {
+			let root = root.display().to_string();
+			if string.contains(&format!("{root}/")) {
+				return tg::Template::unrender(&root, string);
+			}
+		}
+		Ok(tg::Template::from(tg::template::Component::String(
+			string.to_owned(),
+		)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ArtifactRoots;
+	use std::path::PathBuf;
+	use tangram_client::prelude::*;
+
+	#[test]
+	fn explicit_roots() {
+		let roots = ArtifactRoots::new([PathBuf::from("/artifacts")]);
+		assert!(roots.is_artifact_path("/artifacts/dir_01abc/bin"));
+		assert!(!roots.is_artifact_path("/.tangram/artifacts/dir_01abc/bin"));
+
+		let template = roots.unrender("/opt/tangram/artifacts/bin").unwrap();
+		assert!(matches!(
+			template.components(),
+			[tg::template::Component::String(_)]
+		));
+
+		roots.add("/checkout/.tangram/artifacts");
+		assert_eq!(
+			roots.roots().collect::<Vec<_>>(),
+			[
+				PathBuf::from("/checkout/.tangram/artifacts"),
+				PathBuf::from("/artifacts")
+			]
+		);
+	}
+}
@@ -0,0 +1,511 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+use tangram_client::prelude::*;
+
+/// The type of the value of a setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+	/// A flag. Its variable is true if it is set to anything other than `0`, `false`, `no` or `off`.
+	Bool,
+
+	/// A non-negative integer.
+	Number,
+
+	/// Any string.
+	String,
+
+	/// One of the given strings, ignoring case.
+	Choice(&'static [&'static str]),
+}
+
+/// A setting of a proxy, read from a config file, an environment variable and a flag.
+#[derive(Clone, Copy, Debug)]
+pub struct Setting {
+	/// The environment variable, which is also the key in the config file.
+	pub env: &'static str,
+
+	/// The flag, such as `--tg-max-depth`.
+	pub flag: Option<&'static str>,
+
+	/// The type of the value.
+	pub kind: Kind,
+
+	/// The default value.
+	pub default: Option<&'static str>,
+
+	/// The documentation printed by `--tg-help`.
+	pub doc: &'static str,
+}
+
+/// Where the value of a setting came from, in increasing precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+	Default,
+	File,
+	Env,
+	Flag,
+}
+
+impl std::fmt::Display for Source {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Default => write!(f, "default"),
+			Self::File => write!(f, "file"),
+			Self::Env => write!(f, "env"),
+			Self::Flag => write!(f, "flag"),
+		}
+	}
+}
+
+/// A setting of the table or one that every proxy has, such as its tracing filter.
+#[derive(Clone, Debug)]
+struct Entry {
+	env: String,
+	flag: Option<String>,
+	kind: Kind,
+	default: Option<&'static str>,
+	doc: &'static str,
+}
+
+/// The effective configuration of a proxy. Defaults are overridden by the config file, which is overridden by environment variables, which are overridden by flags. Variables with the proxy's prefix and flags starting with `--tg-` or `--tangram-` that are not settings are rejected.
+#[derive(Clone, Debug)]
+pub struct Config {
+	/// The name of the proxy, such as `tgld`.
+	program: &'static str,
+
+	/// The settings, including the ones every proxy has.
+	entries: Vec<Entry>,
+
+	/// The values of the settings that are set, by environment variable.
+	values: BTreeMap<String, (String, Source)>,
+
+	/// Whether `--tg-help` was passed.
+	help: bool,
+
+	/// Whether `--tg-print-config` was passed.
+	print: bool,
+
+	/// The arguments that are not flags of the proxy, in order, with response files expanded.
+	pub args: Vec<String>,
+
+	/// The arguments to pass to the wrapped command. These are the original arguments without the flags of the proxy, so response files are passed unexpanded, unless a response file contains flags of the proxy.
+	pub passthrough_args: Vec<String>,
+}
+
+impl Config {
+	/// Read the configuration of `program` from the environment and the arguments of this process. If `--tg-help` or `--tg-print-config` was passed, print the help or the configuration and exit.
+	pub fn load(program: &'static str, settings: &[Setting]) -> tg::Result<Self> {
+		let config = Self::parse(
+			program,
+			settings,
+			std::env::vars(),
+			std::env::args().skip(1),
+		)?;
+		if config.help {
+			print!("{}", config.help());
+			std::process::exit(0);
+		}
+		if config.print {
+			print!("{}", config.render());
+			std::process::exit(0);
+		}
+		Ok(config)
+	}
+
+	/// Read the configuration of `program` from the given environment and arguments. The environment variables of `program` start with its name in upper case followed by `_`.
+	pub fn parse(
+		program: &'static str,
+		settings: &[Setting],
+		env: impl IntoIterator<Item = (String, String)>,
+		args: impl IntoIterator<Item = String>,
+	) -> tg::Result<Self> {
+		let prefix = format!("{}_", program.to_uppercase());
+		let mut entries = settings
+			.iter()
+			.map(|setting| Entry {
+				env: setting.env.to_owned(),
+				flag: setting.flag.map(ToOwned::to_owned),
+				kind: setting.kind,
+				default: setting.default,
+				doc: setting.doc,
+			})
+			.collect::<Vec<_>>();
+		entries.extend(common_entries(&prefix));
+		let mut config = Self {
+			program,
+			entries,
+			values: BTreeMap::new(),
+			help: false,
+			print: false,
+			args: Vec::new(),
+			passthrough_args: Vec::new(),
+		};
+
+		// Read the environment.
+		let mut env_values = BTreeMap::new();
+		for (key, value) in env {
+			if !key.starts_with(&prefix) {
+				continue;
+			}
+			let entry = config.entry_for_env(&key).ok_or_else(|| {
+				tg::error!(
+					"unknown environment variable {key}, run {program} --tg-help to list the settings"
+				)
+			})?;
+			if entry.kind != Kind::Bool && value.is_empty() {
+				continue;
+			}
+			let value = validate(entry, &value)?;
+			env_values.insert(key, value);
+		}
+
+		// Read the flags, then read the flags in any response files.
+		let mut flag_values = BTreeMap::new();
+		let args = config.take_flags(args, &mut flag_values)?;
+		let expanded = crate::response_file::expand(&args);
+		let count = expanded.len();
+		config.args = config.take_flags(expanded, &mut flag_values)?;
+		config.passthrough_args = if config.args.len() == count {
+			args
+		} else {
+			config.args.clone()
+		};
+
+		// Read the config file.
+		let config_env = format!("{prefix}CONFIG");
+		let file_values = match flag_values.get(&config_env).or(env_values.get(&config_env)) {
+			Some(path) => config.read_file(path)?,
+			None => BTreeMap::new(),
+		};
+
+		// Layer the values.
+		for entry in &config.entries {
+			if let Some(default) = entry.default {
+				config
+					.values
+					.insert(entry.env.clone(), (default.to_owned(), Source::Default));
+			}
+		}
+		let layers = [
+			(file_values, Source::File),
+			(env_values, Source::Env),
+			(flag_values, Source::Flag),
+		];
+		for (values, source) in layers {
+			for (key, value) in values {
+				config.values.insert(key, (value, source));
+			}
+		}
+
+		Ok(config)
+	}
+
+	/// Get the value of a setting.
+	#[must_use]
+	pub fn get(&self, env: &str) -> Option<&str> {
+		self.values.get(env).map(|(value, _)| value.as_str())
+	}
+
+	/// Get where the value of a setting came from.
+	#[must_use]
+	pub fn source(&self, env: &str) -> Option<Source> {
+		self.values.get(env).map(|(_, source)| *source)
+	}
+
+	/// Get the value of a boolean setting.
+	#[must_use]
+	pub fn bool(&self, env: &str) -> bool {
+		self.get(env)
+			.is_some_and(|value| parse_bool(value) == Some(true))
+	}
+
+	/// Get the value of a number setting.
+	#[must_use]
+	pub fn number(&self, env: &str) -> Option<usize> {
+		self.get(env).and_then(|value| value.parse().ok())
+	}
+
+	/// Set up tracing with the tracing settings of the proxy.
+	#[cfg(feature = "tracing")]
+	pub fn setup_tracing(&self) {
+		let prefix = format!("{}_", self.program.to_uppercase());
+		crate::tracing::setup_with(
+			self.get(&format!("{prefix}TRACING")),
+			self.get(&format!("{prefix}TRACING_FORMAT")),
+			self.get(&format!("{prefix}TRACING_FILE")),
+		);
+	}
+
+	/// Render the documentation of the settings.
+	#[must_use]
+	pub fn help(&self) -> String {
+		let mut help = format!(
+			"The settings of {}. Each is read from the config file, the environment and flags, which take precedence in that order.\n",
+			self.program
+		);
+		for entry in &self.entries {
+			let value = match entry.kind {
+				Kind::Bool => String::new(),
+				Kind::Number => "=<number>".to_owned(),
+				Kind::String => "=<value>".to_owned(),
+				Kind::Choice(choices) => format!("=<{}>", choices.join("|")),
+			};
+			write!(help, "\n  {}{value}", entry.env).unwrap();
+			if let Some(flag) = &entry.flag {
+				write!(help, ", {flag}{value}").unwrap();
+			}
+			if let Some(default) = entry.default {
+				write!(help, " (default: {default})").unwrap();
+			}
+			writeln!(help, "\n      {}", entry.doc).unwrap();
+		}
+		writeln!(help, "\n  --tg-help\n      Print this help and exit.").unwrap();
+		writeln!(
+			help,
+			"\n  --tg-print-config\n      Print the effective configuration and exit."
+		)
+		.unwrap();
+		help
+	}
+
+	/// Render the effective configuration, with the source of each value.
+	#[must_use]
+	pub fn render(&self) -> String {
+		let mut rendered = String::new();
+		for entry in &self.entries {
+			match self.values.get(&entry.env) {
+				Some((value, source)) => {
+					writeln!(rendered, "{}={value} ({source})", entry.env).unwrap();
+				},
+				None => writeln!(rendered, "{} (unset)", entry.env).unwrap(),
+			}
+		}
+		rendered
+	}
+
+	/// Read the flags of the proxy from the arguments into `flag_values` and return the other arguments.
+	fn take_flags(
+		&mut self,
+		args: impl IntoIterator<Item = String>,
+		flag_values: &mut BTreeMap<String, String>,
+	) -> tg::Result<Vec<String>> {
+		let program = self.program;
+		let mut rest = Vec::new();
+		let mut args = args.into_iter();
+		while let Some(arg) = args.next() {
+			if !(arg.starts_with("--tg-") || arg.starts_with("--tangram-")) {
+				rest.push(arg);
+				continue;
+			}
+			let (name, value) = match arg.split_once('=') {
+				Some((name, value)) => (name, Some(value.to_owned())),
+				None => (arg.as_str(), None),
+			};
+			match name {
+				"--tg-help" => self.help = true,
+				"--tg-print-config" => self.print = true,
+				_ => {
+					let entry = self.entry_for_flag(name).ok_or_else(|| {
+						tg::error!(
+							"unknown option {name}, run {program} --tg-help to list the settings"
+						)
+					})?;
+					let value = match (value, entry.kind) {
+						(Some(value), _) => value,
+						(None, Kind::Bool) => "true".to_owned(),
+						(None, _) => args
+							.next()
+							.ok_or_else(|| tg::error!("expected a value for {name}"))?,
+					};
+					let value = validate(entry, &value)?;
+					flag_values.insert(entry.env.clone(), value);
+				},
+			}
+		}
+		Ok(rest)
+	}
+
+	fn entry_for_env(&self, env: &str) -> Option<&Entry> {
+		self.entries.iter().find(|entry| entry.env == env)
+	}
+
+	fn entry_for_flag(&self, flag: &str) -> Option<&Entry> {
+		self.entries
+			.iter()
+			.find(|entry| entry.flag.as_deref() == Some(flag))
+	}
+
+	/// Read a config file, which is a JSON object from environment variables to values.
+	fn read_file(&self, path: &str) -> tg::Result<BTreeMap<String, String>> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|error| tg::error!(!error, path = %path, "failed to read the config file"))?;
+		let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&contents)
+			.map_err(|error| tg::error!(!error, path = %path, "failed to parse the config file"))?;
+		let mut values = BTreeMap::new();
+		for (key, value) in object {
+			let entry = self.entry_for_env(&key).ok_or_else(
+				|| tg::error!(path = %path, "unknown setting {key} in the config file"),
+			)?;
+			let value = match value {
+				serde_json::Value::String(value) => value,
+				serde_json::Value::Bool(value) => value.to_string(),
+				serde_json::Value::Number(value) => value.to_string(),
+				_ => {
+					return Err(
+						tg::error!(path = %path, "expected {key} to be a string, number or bool"),
+					);
+				},
+			};
+			values.insert(key, validate(entry, &value)?);
+		}
+		Ok(values)
+	}
+}
+
+/// The settings that every proxy has.
+fn common_entries(prefix: &str) -> [Entry; 4] {
+	[
+		Entry {
+			env: format!("{prefix}CONFIG"),
+			flag: Some("--tg-config".to_owned()),
+			kind: Kind::String,
+			default: None,
+			doc: "A JSON file that maps settings to their values.",
+		},
+		Entry {
+			env: format!("{prefix}TRACING"),
+			flag: None,
+			kind: Kind::String,
+			default: None,
+			doc: "The tracing filter, such as `info`. Tracing is disabled if it is unset.",
+		},
+		Entry {
+			env: format!("{prefix}TRACING_FORMAT"),
+			flag: None,
+			kind: Kind::Choice(&["text", "json", "chrome"]),
+			default: Some("text"),
+			doc: "The format of the trace.",
+		},
+		Entry {
+			env: format!("{prefix}TRACING_FILE"),
+			flag: None,
+			kind: Kind::String,
+			default: None,
+			doc: "A file to write the trace to instead of stderr. `{pid}` is replaced with the process ID.",
+		},
+	]
+}
+
+/// Check a value against the type of a setting. A choice is stored as it is spelled in the list of choices.
+fn validate(entry: &Entry, value: &str) -> tg::Result<String> {
+	let valid = match entry.kind {
+		Kind::Bool => parse_bool(value).map(|_| value),
+		Kind::Number => value.parse::<usize>().ok().map(|_| value),
+		Kind::String => Some(value),
+		Kind::Choice(choices) => choices
+			.iter()
+			.copied()
+			.find(|choice| choice.eq_ignore_ascii_case(value)),
+	};
+	let Some(value) = valid else {
+		return Err(tg::error!("invalid value {value:?} for {}", entry.env));
+	};
+	Ok(value.to_owned())
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+	match value.to_ascii_lowercase().as_str() {
+		"" | "1" | "true" | "yes" | "on" => Some(true),
+		"0" | "false" | "no" | "off" => Some(false),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Config, Kind, Setting, Source};
+	use tangram_client::prelude::*;
+
+	const SETTINGS: &[Setting] = &[
+		Setting {
+			env: "TGTEST_MAX_DEPTH",
+			flag: Some("--tg-max-depth"),
+			kind: Kind::Number,
+			default: Some("16"),
+			doc: "The maximum depth.",
+		},
+		Setting {
+			env: "TGTEST_PASSTHROUGH",
+			flag: Some("--tg-passthrough"),
+			kind: Kind::Bool,
+			default: None,
+			doc: "Run the command unchanged.",
+		},
+	];
+
+	fn parse(env: &[(&str, &str)], args: &[&str]) -> tg::Result<Config> {
+		Config::parse(
+			"tgtest",
+			SETTINGS,
+			env.iter()
+				.map(|(key, value)| ((*key).to_owned(), (*value).to_owned())),
+			args.iter().map(|arg| (*arg).to_owned()),
+		)
+	}
+
+	#[test]
+	fn layers() {
+		let config = parse(
+			&[("TGTEST_MAX_DEPTH", "4"), ("HOME", "/root")],
+			&["-o", "out"],
+		)
+		.unwrap();
+		assert_eq!(config.number("TGTEST_MAX_DEPTH"), Some(4));
+		assert_eq!(config.source("TGTEST_MAX_DEPTH"), Some(Source::Env));
+		assert!(!config.bool("TGTEST_PASSTHROUGH"));
+		assert_eq!(config.args, ["-o", "out"]);
+
+		let config = parse(
+			&[("TGTEST_MAX_DEPTH", "4"), ("TGTEST_PASSTHROUGH", "")],
+			&["--tg-max-depth", "2", "a.o"],
+		)
+		.unwrap();
+		assert_eq!(config.number("TGTEST_MAX_DEPTH"), Some(2));
+		assert!(config.bool("TGTEST_PASSTHROUGH"));
+		assert_eq!(config.args, ["a.o"]);
+
+		let config = parse(&[], &[]).unwrap();
+		assert_eq!(config.get("TGTEST_MAX_DEPTH"), Some("16"));
+		assert_eq!(config.source("TGTEST_MAX_DEPTH"), Some(Source::Default));
+
+		let config = parse(&[("TGTEST_TRACING_FORMAT", "JSON")], &[]).unwrap();
+		assert_eq!(config.get("TGTEST_TRACING_FORMAT"), Some("json"));
+	}
+
+	#[test]
+	fn response_files() {
+		let temp = tempfile::TempDir::new().unwrap();
+		let plain = temp.path().join("plain.rsp");
+		let flags = temp.path().join("flags.rsp");
+		std::fs::write(&plain, "-o out main.o").unwrap();
+		std::fs::write(&flags, "--tg-passthrough main.o").unwrap();
+
+		let plain = format!("@{}", plain.display());
+		let config = parse(&[], &["--tg-max-depth=2", &plain]).unwrap();
+		assert_eq!(config.args, ["-o", "out", "main.o"]);
+		assert_eq!(config.passthrough_args, [plain]);
+
+		let flags = format!("@{}", flags.display());
+		let config = parse(&[], &[&flags]).unwrap();
+		assert!(config.bool("TGTEST_PASSTHROUGH"));
+		assert_eq!(config.args, ["main.o"]);
+		assert_eq!(config.passthrough_args, ["main.o"]);
+	}
+
+	#[test]
+	fn unknown() {
+		assert!(parse(&[("TGTEST_MAX_DEPHT", "4")], &[]).is_err());
+		assert!(parse(&[], &["--tg-passthru"]).is_err());
+		assert!(parse(&[("TGTEST_MAX_DEPTH", "deep")], &[]).is_err());
+		assert!(parse(&[("TGTEST_TRACING", "info")], &["--tg-print-config"]).is_ok());
+	}
+}
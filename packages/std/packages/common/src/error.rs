@@ -4,14 +4,47 @@ use std::fmt::Write as _;
 use tangram_client::prelude::*;
 use tangram_either::Either;
 
+/// The environment variable that selects how errors are printed.
+pub const TANGRAM_ERROR_FORMAT: &str = "TANGRAM_ERROR_FORMAT";
+
+/// How errors are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+	/// Colored text for a terminal.
+	#[default]
+	Text,
+
+	/// One line of JSON per error.
+	Json,
+}
+
+impl Format {
+	/// Get the format from `TANGRAM_ERROR_FORMAT`, which is `text` or `json`.
+	#[must_use]
+	pub fn from_env() -> Self {
+		match std::env::var(TANGRAM_ERROR_FORMAT).as_deref() {
+			Ok("json") => Self::Json,
+			_ => Self::Text,
+		}
+	}
+}
+
 /// Print an error with all its rich information including source chain, locations, diagnostics, etc.
 pub fn print_error(error: tg::Error) {
 	let error = tg::Referent::with_item(error);
 	print_error_referent(error);
 }
 
-/// Print an error referent with all its rich information.
+/// Print an error referent with all its rich information, in the format from the environment.
 pub fn print_error_referent(error: tg::Referent<tg::Error>) {
+	match Format::from_env() {
+		Format::Text => print_error_referent_text(error),
+		Format::Json => eprintln!("{}", error_referent_to_json(error)),
+	}
+}
+
+/// Print an error referent as colored text.
+fn print_error_referent_text(error: tg::Referent<tg::Error>) {
 	let mut stack = vec![error];
 
 	while let Some(error_referent) = stack.pop() {
@@ -34,21 +67,13 @@ pub fn print_error_referent(error: tg::Referent<tg::Error>) {
 
 		// Print the location.
 		if let Some(location) = &error.location {
-			let mut location = location.clone();
-			if let tg::error::File::Module(module) = &mut location.file {
-				module.referent.inherit(&error_referent);
-			}
-			print_error_location(&location);
+			print_error_location(&inherit_location(location, &error_referent));
 		}
 
 		// Print the stack.
 		if let Some(error_stack) = &error.stack {
-			for loc in error_stack {
-				let mut location = loc.clone();
-				if let tg::error::File::Module(module) = &mut location.file {
-					module.referent.inherit(&error_referent);
-				}
-				print_error_location(&location);
+			for location in error_stack {
+				print_error_location(&inherit_location(location, &error_referent));
 			}
 		}
 
@@ -59,13 +84,7 @@ pub fn print_error_referent(error: tg::Referent<tg::Error>) {
 				if let Some(location) = &mut diagnostic.location {
 					location.module.referent.inherit(&error_referent);
 				}
-				let severity = match diagnostic.severity {
-					tg::diagnostic::Severity::Error => "error",
-					tg::diagnostic::Severity::Warning => "warning",
-					tg::diagnostic::Severity::Info => "info",
-					tg::diagnostic::Severity::Hint => "hint",
-				};
-				eprintln!("{} {}", severity, diagnostic.message);
+				eprintln!("{} {}", severity(diagnostic.severity), diagnostic.message);
 				if let Some(location) = &diagnostic.location {
 					print_location(&location.module, &location.range);
 				}
@@ -73,18 +92,149 @@ pub fn print_error_referent(error: tg::Referent<tg::Error>) {
 		}
 
 		// Add the source to the stack.
-		if let Some(source) = &error.source {
-			let source_handle = match &source.item {
-				Either::Left(object) => tg::Error::with_object(object.as_ref().clone()),
-				Either::Right(handle) => (**handle).clone(),
-			};
-			let mut source_referent = tg::Referent {
-				item: source_handle,
-				options: source.options.clone(),
-			};
-			source_referent.inherit(&error_referent);
-			stack.push(source_referent);
+		stack.extend(source_referent(&error, &error_referent));
+	}
+}
+
+/// Serialize an error referent and its source chain to JSON, resolving the referents of its modules. The source of each error is nested in it.
+#[must_use]
+pub fn error_referent_to_json(error: tg::Referent<tg::Error>) -> serde_json::Value {
+	let mut errors = Vec::new();
+	let mut next = Some(error);
+	while let Some(error_referent) = next.take() {
+		let error_handle = &error_referent.item;
+
+		// Get the object from the handle.
+		let Some(error) = error_handle.state().object().map(|o| o.unwrap_error()) else {
+			errors.push(serde_json::json!({ "id": error_handle.id().to_string() }));
+			continue;
+		};
+		let mut object = serde_json::Map::new();
+
+		// Add the message.
+		let message = error.message.as_deref().unwrap_or("an error occurred");
+		object.insert("message".to_owned(), message.into());
+
+		// Add the values.
+		if !error.values.is_empty() {
+			let values = error
+				.values
+				.iter()
+				.map(|(key, value)| (key.clone(), value.to_string().into()))
+				.collect();
+			object.insert("values".to_owned(), serde_json::Value::Object(values));
+		}
+
+		// Add the location.
+		if let Some(location) = &error.location {
+			let location = inherit_location(location, &error_referent);
+			object.insert("location".to_owned(), error_location_to_json(&location));
+		}
+
+		// Add the stack.
+		if let Some(error_stack) = &error.stack {
+			let locations = error_stack
+				.iter()
+				.map(|location| {
+					error_location_to_json(&inherit_location(location, &error_referent))
+				})
+				.collect();
+			object.insert("stack".to_owned(), serde_json::Value::Array(locations));
 		}
+
+		// Add the diagnostics.
+		if let Some(diagnostics) = &error.diagnostics {
+			let diagnostics = diagnostics
+				.iter()
+				.map(|diagnostic| {
+					let mut diagnostic = diagnostic.clone();
+					if let Some(location) = &mut diagnostic.location {
+						location.module.referent.inherit(&error_referent);
+					}
+					let mut object = serde_json::Map::new();
+					object.insert("severity".to_owned(), severity(diagnostic.severity).into());
+					object.insert("message".to_owned(), diagnostic.message.clone().into());
+					if let Some(location) = &diagnostic.location {
+						object.insert(
+							"location".to_owned(),
+							location_to_json(location_title(&location.module), &location.range),
+						);
+					}
+					serde_json::Value::Object(object)
+				})
+				.collect();
+			object.insert(
+				"diagnostics".to_owned(),
+				serde_json::Value::Array(diagnostics),
+			);
+		}
+
+		errors.push(serde_json::Value::Object(object));
+		next = source_referent(&error, &error_referent);
+	}
+
+	// Nest each error in the one it is the source of.
+	errors
+		.into_iter()
+		.rev()
+		.reduce(|source, mut error| {
+			error["source"] = source;
+			error
+		})
+		.unwrap_or_default()
+}
+
+/// Get the source of an error as a referent that inherits from the error's referent.
+fn source_referent(
+	error: &tg::error::Object,
+	error_referent: &tg::Referent<tg::Error>,
+) -> Option<tg::Referent<tg::Error>> {
+	let source = error.source.as_ref()?;
+	let source_handle = match &source.item {
+		Either::Left(object) => tg::Error::with_object(object.as_ref().clone()),
+		Either::Right(handle) => (**handle).clone(),
+	};
+	let mut source_referent = tg::Referent {
+		item: source_handle,
+		options: source.options.clone(),
+	};
+	source_referent.inherit(error_referent);
+	Some(source_referent)
+}
+
+fn inherit_location(
+	location: &tg::error::Location,
+	error_referent: &tg::Referent<tg::Error>,
+) -> tg::error::Location {
+	let mut location = location.clone();
+	if let tg::error::File::Module(module) = &mut location.file {
+		module.referent.inherit(error_referent);
+	}
+	location
+}
+
+fn error_location_to_json(location: &tg::error::Location) -> serde_json::Value {
+	let file = match &location.file {
+		tg::error::File::Internal(path) => format!("internal:{}", path.display()),
+		tg::error::File::Module(module) => location_title(module),
+	};
+	location_to_json(file, &location.range)
+}
+
+fn location_to_json(file: String, range: &tg::Range) -> serde_json::Value {
+	serde_json::json!({
+		"file": file,
+		"line": range.start.line + 1,
+		"character": range.start.character + 1,
+	})
+}
+
+fn severity(severity: tg::diagnostic::Severity) -> &'static str {
+	match severity {
+		tg::diagnostic::Severity::Error => "error",
+		tg::diagnostic::Severity::Warning => "warning",
+		tg::diagnostic::Severity::Info => "info",
+		tg::diagnostic::Severity::Hint => "hint",
 	}
 }
 
@@ -105,16 +255,18 @@ fn print_error_location(location: &tg::error::Location) {
 }
 
 fn print_location(module: &tg::Module, range: &tg::Range) {
+	eprintln!(
+		"   {}:{}:{}",
+		location_title(module),
+		range.start.line + 1,
+		range.start.character + 1,
+	);
+}
+
+/// Get the title of a module, which is its path, or its tag or path in its referent.
+fn location_title(module: &tg::Module) -> String {
 	match &module.referent.item {
-		tg::module::Item::Path(path) => {
-			eprint!(
-				"   {}:{}:{}",
-				path.display(),
-				range.start.line + 1,
-				range.start.character + 1,
-			);
-			eprintln!();
-		},
+		tg::module::Item::Path(path) => path.display().to_string(),
 		tg::module::Item::Edge(_edge) => {
 			let mut title = String::new();
 			if let Some(tag) = module.referent.tag() {
@@ -134,12 +286,44 @@ fn print_location(module: &tg::Module, range: &tg::Range) {
 			} else {
 				write!(title, "<unknown>").unwrap();
 			}
-			eprint!(
-				"   {title}:{}:{}",
-				range.start.line + 1,
-				range.start.character + 1,
-			);
-			eprintln!();
+			title
 		},
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::error_referent_to_json;
+	use tangram_client::prelude::*;
+
+	/// Get the JSON for the location where an error was created.
+	fn location(error: &tg::Error) -> serde_json::Value {
+		let object = error.state().object().unwrap().unwrap_error();
+		let range = &object.location.as_ref().unwrap().range;
+		serde_json::json!({
+			"file": format!("internal:{}", file!()),
+			"line": range.start.line + 1,
+			"character": range.start.character + 1,
+		})
+	}
+
+	#[test]
+	fn json() {
+		let source = tg::error!("failed to open the file");
+		let source_location = location(&source);
+		let error = tg::error!(!source, path = %"/bin/sh", "failed to run the command");
+		let error_location = location(&error);
+		assert_eq!(
+			error_referent_to_json(tg::Referent::with_item(error)),
+			serde_json::json!({
+				"message": "failed to run the command",
+				"values": { "path": "/bin/sh" },
+				"location": error_location,
+				"source": {
+					"message": "failed to open the file",
+					"location": source_location,
+				},
+			})
+		);
+	}
+}
@@ -0,0 +1,608 @@
+use crate::{
+	artifact_path_for,
+	manifest::{Executable, Interpreter, Manifest},
+	render_template_data,
+};
+use std::{collections::BTreeMap, path::Path};
+use tangram_client::prelude::*;
+
+/// What a wrapper would exec, computed from its manifest without running anything.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Explanation {
+	/// The path passed to `execve`. If there is none, the wrapper jumps to the entrypoint of the executable embedded in it.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+
+	/// The rendered script of a content executable, which the wrapper writes to a temporary file.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub content: Option<String>,
+
+	/// The arguments, starting with `argv[0]`.
+	pub argv: Vec<String>,
+
+	/// The environment.
+	pub env: BTreeMap<String, String>,
+}
+
+/// The names of the environment variables that hold the library paths and preloads for an interpreter.
+struct Loader {
+	library_path: &'static str,
+	preload: &'static str,
+}
+
+/// Compute the argv and env that the wrapper at `wrapper` would exec when run with `arguments` in `env`.
+#[allow(clippy::too_many_lines)]
+pub fn explain(
+	manifest: &Manifest,
+	wrapper: &str,
+	arguments: &[String],
+	mut env: BTreeMap<String, String>,
+) -> tg::Result<Explanation> {
+	// Resolve a relocatable manifest relative to the directory of the wrapper, like the runtime does.
+	let relocated;
+	let manifest = if manifest.artifacts_path.is_some() {
+		let path = std::fs::canonicalize(wrapper).unwrap_or_else(|_| wrapper.into());
+		relocated = manifest.relocate(path.parent().unwrap_or(Path::new("/")));
+		&relocated
+	} else {
+		manifest
+	};
+
+	// Render the executable.
+	let (executable, content) = match &manifest.executable {
+		Executable::Path(path) => (Some(render_template_data(path)?), None),
+		Executable::Content(template) => (
+			Some("/dev/fd/3".to_owned()),
+			Some(render_template_data(template)?),
+		),
+		Executable::Address(_) => (None, None),
+	};
+
+	// Apply the environment mutations.
+	if let Some(mutation) = &manifest.env {
+		apply_env(&mut env, mutation)?;
+	}
+
+	// Render the interpreter.
+	let (interpreter, interpreter_args, library_paths, preloads, loader) =
+		match &manifest.interpreter {
+			None => (None, Vec::new(), Vec::new(), Vec::new(), None),
+			Some(Interpreter::Normal(interpreter)) => (
+				Some(render_template_data(&interpreter.path)?),
+				render_all(Some(&interpreter.args))?,
+				Vec::new(),
+				Vec::new(),
+				None,
+			),
+			Some(Interpreter::LdLinux(interpreter)) => (
+				Some(render_template_data(&interpreter.path)?),
+				render_all(interpreter.args.as_ref())?,
+				render_all(interpreter.library_paths.as_ref())?,
+				render_all(interpreter.preloads.as_ref())?,
+				Some(Loader {
+					library_path: "LD_LIBRARY_PATH",
+					preload: "LD_PRELOAD",
+				}),
+			),
+			Some(Interpreter::LdMusl(interpreter)) => (
+				Some(render_template_data(&interpreter.path)?),
+				render_all(interpreter.args.as_ref())?,
+				render_all(interpreter.library_paths.as_ref())?,
+				render_all(interpreter.preloads.as_ref())?,
+				Some(Loader {
+					library_path: "LD_LIBRARY_PATH",
+					preload: "LD_PRELOAD",
+				}),
+			),
+			Some(Interpreter::DyLd(interpreter)) => (
+				None,
+				Vec::new(),
+				render_all(interpreter.library_paths.as_ref())?,
+				render_all(interpreter.preloads.as_ref())?,
+				Some(Loader {
+					library_path: "DYLD_LIBRARY_PATH",
+					preload: "DYLD_INSERT_LIBRARIES",
+				}),
+			),
+		};
+
+	// Combine the library paths and preloads with the ones in the environment.
+	let mut library_path = None;
+	let mut preload = None;
+	if let Some(loader) = &loader {
+		let is_dyld = matches!(manifest.interpreter, Some(Interpreter::DyLd(_)));
+		let inject = is_dyld || executable.is_none();
+		library_path = inject_paths(
+			&mut env,
+			&library_paths,
+			loader.library_path,
+			inject.then_some((
+				"TANGRAM_INJECTION_LIBRARY_PATH",
+				"TANGRAM_INJECTION_CLEAR_LIBRARY_PATH",
+			)),
+		);
+		preload = inject_paths(
+			&mut env,
+			&preloads,
+			loader.preload,
+			inject.then_some((
+				"TANGRAM_INJECTION_PRELOAD",
+				"TANGRAM_INJECTION_CLEAR_PRELOAD",
+			)),
+		);
+		env.insert(
+			"TANGRAM_INJECTION_IDENTITY_PATH".to_owned(),
+			wrapper.to_owned(),
+		);
+	}
+
+	// Append the arguments that are not for the wrapper to the arguments from the manifest.
+	let mut manifest_args = render_all(manifest.args.as_ref())?;
+	manifest_args.extend(
+		arguments
+			.iter()
+			.filter(|arg| !arg.starts_with("--tangram-"))
+			.cloned(),
+	);
+
+	// Create argv.
+	let mut argv = Vec::new();
+	let path = match (&executable, &interpreter, &manifest.interpreter) {
+		(Some(executable), Some(interpreter), Some(kind)) => {
+			argv.push(interpreter.clone());
+			argv.extend(interpreter_args);
+			let is_ld_linux = matches!(kind, Interpreter::LdLinux(_));
+			let is_ld_musl = matches!(kind, Interpreter::LdMusl(_));
+			if is_ld_linux {
+				argv.push("--inhibit-cache".to_owned());
+			}
+			if is_ld_linux || is_ld_musl {
+				if let Some(library_path) = library_path {
+					argv.push("--library-path".to_owned());
+					argv.push(library_path);
+				}
+				if let Some(preload) = preload {
+					argv.push("--preload".to_owned());
+					argv.push(preload);
+				}
+				argv.push("--argv0".to_owned());
+				argv.push(wrapper.to_owned());
+			}
+			if is_ld_musl {
+				argv.push("--".to_owned());
+			}
+			argv.push(executable.clone());
+			Some(interpreter.clone())
+		},
+		(Some(executable), _, _) => {
+			argv.push(wrapper.to_owned());
+			Some(executable.clone())
+		},
+		(None, _, _) => {
+			argv.push(wrapper.to_owned());
+			None
+		},
+	};
+	argv.extend(manifest_args);
+
+	Ok(Explanation {
+		path,
+		content,
+		argv,
+		env,
+	})
+}
+
+/// Join paths from the manifest with the existing value of the variable. If `restore` is set, the variable is set in the environment along with the variable that restores the original value.
+fn inject_paths(
+	env: &mut BTreeMap<String, String>,
+	paths: &[String],
+	key: &str,
+	restore: Option<(&str, &str)>,
+) -> Option<String> {
+	let original = env.get(key).cloned();
+	let joined = paths.join(":");
+	let value = match (&original, joined.is_empty()) {
+		(_, true) => original.clone(),
+		(Some(original), false) => Some(format!("{joined}:{original}")),
+		(None, false) => Some(joined),
+	};
+	if let (Some((restore, clear)), Some(value)) = (restore, &value)
+		&& !value.is_empty()
+	{
+		env.insert(key.to_owned(), value.clone());
+		match original {
+			Some(original) => env.insert(restore.to_owned(), original),
+			None => env.insert(clear.to_owned(), "true".to_owned()),
+		};
+	}
+	value
+}
+
+fn render_all(templates: Option<&Vec<tg::template::Data>>) -> tg::Result<Vec<String>> {
+	templates
+		.into_iter()
+		.flatten()
+		.map(render_template_data)
+		.collect()
+}
+
+/// Apply the mutation of the whole environment, which is either `Unset` or a map of the mutations of each variable.
+pub fn apply_env(
+	env: &mut BTreeMap<String, String>,
+	mutation: &tg::mutation::Data,
+) -> tg::Result<()> {
+	match mutation {
+		tg::mutation::Data::Unset => {
+			env.clear();
+		},
+		tg::mutation::Data::Set { value } => {
+			let Some(map) = map_of(value) else {
+				return Err(tg::error!("expected the environment to be set to a map"));
+			};
+			for (key, value) in map {
+				apply_value(env, key, value)?;
+			}
+		},
+		tg::mutation::Data::Merge { value } => {
+			for (key, value) in value {
+				apply_value(env, key, value)?;
+			}
+		},
+		_ => return Err(tg::error!("unsupported mutation of the environment")),
+	}
+	Ok(())
+}
+
+fn map_of(value: &tg::value::Data) -> Option<&BTreeMap<String, tg::value::Data>> {
+	match value {
+		tg::value::Data::Map(map) => Some(map),
+		_ => None,
+	}
+}
+
+/// Apply the value of a variable in the environment map. An array is a list of mutations, a mutation is applied, and anything else is rendered.
+fn apply_value(
+	env: &mut BTreeMap<String, String>,
+	key: &str,
+	value: &tg::value::Data,
+) -> tg::Result<()> {
+	match value {
+		tg::value::Data::Array(values) => {
+			for value in values {
+				let tg::value::Data::Mutation(mutation) = value else {
+					return Err(tg::error!(key = %key, "expected a mutation"));
+				};
+				apply_mutation(env, key, mutation)?;
+			}
+		},
+		tg::value::Data::Mutation(mutation) => {
+			apply_mutation(env, key, mutation)?;
+		},
+		value => {
+			env.insert(key.to_owned(), render_value(value)?);
+		},
+	}
+	Ok(())
+}
+
+/// Apply a mutation to a variable.
+pub fn apply_mutation(
+	env: &mut BTreeMap<String, String>,
+	key: &str,
+	mutation: &tg::mutation::Data,
+) -> tg::Result<()> {
+	match mutation {
+		tg::mutation::Data::Unset => {
+			env.remove(key);
+		},
+		tg::mutation::Data::Set { value } => {
+			apply_value(env, key, value)?;
+		},
+		tg::mutation::Data::SetIfUnset { value } => {
+			if !env.contains_key(key) {
+				apply_value(env, key, value)?;
+			}
+		},
+		tg::mutation::Data::Prepend { values } => {
+			let mut values = values
+				.iter()
+				.map(render_value)
+				.collect::<tg::Result<Vec<_>>>()?;
+			values.extend(env.get(key).cloned());
+			env.insert(key.to_owned(), values.join(":"));
+		},
+		tg::mutation::Data::Append { values } => {
+			let values = values
+				.iter()
+				.map(render_value)
+				.collect::<tg::Result<Vec<_>>>()?;
+			let values = env
+				.get(key)
+				.cloned()
+				.into_iter()
+				.chain(values)
+				.collect::<Vec<_>>();
+			env.insert(key.to_owned(), values.join(":"));
+		},
+		tg::mutation::Data::Prefix {
+			template,
+			separator,
+		} => {
+			let value = render_template_data(template)?;
+			let value = match env.get(key) {
+				Some(existing) => {
+					format!(
+						"{value}{}{existing}",
+						separator.as_deref().unwrap_or_default()
+					)
+				},
+				None => value,
+			};
+			env.insert(key.to_owned(), value);
+		},
+		tg::mutation::Data::Suffix {
+			template,
+			separator,
+		} => {
+			let value = render_template_data(template)?;
+			let value = match env.get(key) {
+				Some(existing) => {
+					format!(
+						"{existing}{}{value}",
+						separator.as_deref().unwrap_or_default()
+					)
+				},
+				None => value,
+			};
+			env.insert(key.to_owned(), value);
+		},
+		tg::mutation::Data::Merge { .. } => {
+			return Err(tg::error!(
+				key = %key,
+				"merge mutations are not supported for environment variables"
+			));
+		},
+	}
+	Ok(())
+}
+
+/// Render a value that is set in the environment.
+fn render_value(value: &tg::value::Data) -> tg::Result<String> {
+	match value {
+		tg::value::Data::Bool(value) => Ok(value.to_string()),
+		tg::value::Data::Number(value) => Ok(value.to_string()),
+		tg::value::Data::String(value) => Ok(value.clone()),
+		tg::value::Data::Template(template) => render_template_data(template),
+		tg::value::Data::Object(object) => {
+			let id: tg::artifact::Id = match &object.item {
+				tg::object::Id::Directory(id) => id.clone().into(),
+				tg::object::Id::File(id) => id.clone().into(),
+				tg::object::Id::Symlink(id) => id.clone().into(),
+				id => return Err(tg::error!(id = %id, "cannot render the object")),
+			};
+			let path = artifact_path_for(&id)
+				.ok_or_else(|| tg::error!(id = %id, "artifact not present in any artifact root"))?;
+			Ok(path.display().to_string())
+		},
+		_ => Err(tg::error!("cannot render the value in this context")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::explain;
+	use crate::manifest::{DyLdInterpreter, Executable, Interpreter, LdLinuxInterpreter, Manifest};
+	use std::collections::BTreeMap;
+	use tangram_client::prelude::*;
+
+	fn template(string: &str) -> tg::template::Data {
+		tg::template::Data {
+			components: vec![tg::template::data::Component::String(string.to_owned())],
+		}
+	}
+
+	fn string(string: &str) -> tg::value::Data {
+		tg::value::Data::String(string.to_owned())
+	}
+
+	fn mutation(mutation: tg::mutation::Data) -> tg::value::Data {
+		tg::value::Data::Mutation(mutation)
+	}
+
+	fn env(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+		entries
+			.iter()
+			.map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+			.collect()
+	}
+
+	fn manifest(
+		interpreter: Option<Interpreter>,
+		env: Option<BTreeMap<String, tg::value::Data>>,
+	) -> Manifest {
+		Manifest {
+			interpreter,
+			executable: Executable::Path(template("/bin/program")),
+			env: env.map(|map| tg::mutation::Data::Set {
+				value: Box::new(tg::value::Data::Map(map)),
+			}),
+			args: Some(vec![template("--default")]),
+			artifacts_path: None,
+		}
+	}
+
+	#[test]
+	fn mutations() {
+		let map = BTreeMap::from([
+			(
+				"PATH".to_owned(),
+				mutation(tg::mutation::Data::Prefix {
+					template: template("/tangram/bin"),
+					separator: Some(":".to_owned()),
+				}),
+			),
+			(
+				"CFLAGS".to_owned(),
+				mutation(tg::mutation::Data::Suffix {
+					template: template("-O2"),
+					separator: None,
+				}),
+			),
+			("HOME".to_owned(), mutation(tg::mutation::Data::Unset)),
+			("NAME".to_owned(), string("value")),
+			(
+				"KEEP".to_owned(),
+				mutation(tg::mutation::Data::SetIfUnset {
+					value: Box::new(string("new")),
+				}),
+			),
+			(
+				"FILL".to_owned(),
+				mutation(tg::mutation::Data::SetIfUnset {
+					value: Box::new(string("new")),
+				}),
+			),
+			(
+				"LIST".to_owned(),
+				tg::value::Data::Array(vec![
+					mutation(tg::mutation::Data::Append {
+						values: vec![string("c")],
+					}),
+					mutation(tg::mutation::Data::Prepend {
+						values: vec![string("a")],
+					}),
+				]),
+			),
+			(
+				"SET".to_owned(),
+				mutation(tg::mutation::Data::Set {
+					value: Box::new(tg::value::Data::Template(template("set"))),
+				}),
+			),
+		]);
+		let input = env(&[
+			("PATH", "/usr/bin"),
+			("CFLAGS", "-g "),
+			("HOME", "/root"),
+			("KEEP", "old"),
+			("LIST", "b"),
+		]);
+		let explanation = explain(&manifest(None, Some(map)), "/wrapper", &[], input).unwrap();
+		assert_eq!(
+			explanation.env,
+			env(&[
+				("CFLAGS", "-g -O2"),
+				("FILL", "new"),
+				("KEEP", "old"),
+				("LIST", "a:b:c"),
+				("NAME", "value"),
+				("PATH", "/tangram/bin:/usr/bin"),
+				("SET", "set"),
+			])
+		);
+		assert_eq!(explanation.path.as_deref(), Some("/bin/program"));
+		assert_eq!(explanation.argv, ["/wrapper", "--default"]);
+	}
+
+	#[test]
+	fn unset_env() {
+		let manifest = Manifest {
+			env: Some(tg::mutation::Data::Unset),
+			..manifest(None, None)
+		};
+		let explanation = explain(&manifest, "/wrapper", &[], env(&[("HOME", "/root")])).unwrap();
+		assert!(explanation.env.is_empty());
+	}
+
+	#[test]
+	fn relocatable() {
+		let manifest = Manifest {
+			executable: Executable::Path(template("$ORIGIN/.tangram/root/bin/program")),
+			env: Some(tg::mutation::Data::Set {
+				value: Box::new(tg::value::Data::Map(
+					[(
+						"HOME".to_owned(),
+						tg::value::Data::Template(template("$ORIGIN/home")),
+					)]
+					.into(),
+				)),
+			}),
+			artifacts_path: Some(".tangram/artifacts".to_owned()),
+			..manifest(None, None)
+		};
+		let explanation = explain(&manifest, "/bundle/wrapper", &[], BTreeMap::new()).unwrap();
+		assert_eq!(
+			explanation.path.as_deref(),
+			Some("/bundle/.tangram/root/bin/program")
+		);
+		assert_eq!(explanation.env["HOME"], "/bundle/home");
+	}
+
+	#[test]
+	fn ld_linux() {
+		let interpreter = Interpreter::LdLinux(LdLinuxInterpreter {
+			path: template("/lib/ld-linux.so"),
+			library_paths: Some(vec![template("/lib/a"), template("/lib/b")]),
+			preloads: Some(vec![template("/lib/preload.so")]),
+			args: None,
+		});
+		let args = ["input".to_owned(), "--tangram-suppress-env".to_owned()];
+		let explanation = explain(
+			&manifest(Some(interpreter), None),
+			"/wrapper",
+			&args,
+			env(&[("LD_LIBRARY_PATH", "/original")]),
+		)
+		.unwrap();
+		assert_eq!(explanation.path.as_deref(), Some("/lib/ld-linux.so"));
+		assert_eq!(
+			explanation.argv,
+			[
+				"/lib/ld-linux.so",
+				"--inhibit-cache",
+				"--library-path",
+				"/lib/a:/lib/b:/original",
+				"--preload",
+				"/lib/preload.so",
+				"--argv0",
+				"/wrapper",
+				"/bin/program",
+				"--default",
+				"input",
+			]
+		);
+		assert_eq!(
+			explanation.env,
+			env(&[
+				("LD_LIBRARY_PATH", "/original"),
+				("TANGRAM_INJECTION_IDENTITY_PATH", "/wrapper"),
+			])
+		);
+	}
+
+	#[test]
+	fn dyld() {
+		let interpreter = Interpreter::DyLd(DyLdInterpreter {
+			library_paths: Some(vec![template("/lib")]),
+			preloads: None,
+		});
+		let explanation = explain(
+			&manifest(Some(interpreter), None),
+			"/wrapper",
+			&[],
+			env(&[]),
+		)
+		.unwrap();
+		assert_eq!(explanation.path.as_deref(), Some("/bin/program"));
+		assert_eq!(explanation.argv, ["/wrapper", "--default"]);
+		assert_eq!(
+			explanation.env,
+			env(&[
+				("DYLD_LIBRARY_PATH", "/lib"),
+				("TANGRAM_INJECTION_CLEAR_LIBRARY_PATH", "true"),
+				("TANGRAM_INJECTION_IDENTITY_PATH", "/wrapper"),
+			])
+		);
+	}
+}
@@ -1,11 +1,15 @@
-use std::{
-	path::PathBuf,
-	sync::{LazyLock, Mutex},
-};
+use std::path::{Path, PathBuf};
 use tangram_client::prelude::*;
 
+pub mod artifacts;
+pub use artifacts::ArtifactRoots;
+
+pub mod config;
+pub mod explain;
 pub mod manifest;
-pub use manifest::Manifest;
+pub use manifest::{Manifest, ManifestBuilder};
+
+pub mod response_file;
 
 #[cfg(feature = "tracing")]
 pub mod tracing;
@@ -54,100 +58,30 @@ pub fn template_from_artifact_and_subpath(
 	])
 }
 
-struct ArtifactRootSearch {
-	/// Roots discovered so far, ordered closest-first.
-	found: Vec<String>,
-	/// Candidates not yet probed.
-	pending: std::vec::IntoIter<PathBuf>,
-}
-
-static ARTIFACT_ROOTS: LazyLock<Mutex<ArtifactRootSearch>> = LazyLock::new(|| {
-	let exe = std::env::current_exe()
-		.expect("failed to get the current executable")
-		.canonicalize()
-		.expect("failed to canonicalize the current executable");
-	let mut pending: Vec<PathBuf> = exe
-		.ancestors()
-		.skip(1)
-		.map(|a| a.join(".tangram/artifacts"))
-		.collect();
-	pending.push(PathBuf::from("/opt/tangram/artifacts"));
-	Mutex::new(ArtifactRootSearch {
-		found: Vec::new(),
-		pending: pending.into_iter(),
-	})
-});
-
-/// Return the i-th artifact root, walking ancestors only as far as needed.
-fn artifact_root_at(index: usize) -> Option<String> {
-	let mut search = ARTIFACT_ROOTS.lock().unwrap();
-	while search.found.len() <= index {
-		let candidate = search.pending.next()?;
-		if candidate.is_dir() {
-			let s = candidate
-				.to_str()
-				.expect("artifact root path must be valid UTF-8")
-				.to_string();
-			search.found.push(s);
-		}
-	}
-	search.found.get(index).cloned()
+/// Check whether a path is in an artifact root of this process.
+#[must_use]
+pub fn is_artifact_path(path: &str) -> bool {
+	ArtifactRoots::global().is_artifact_path(path)
 }
 
-/// Substring check: does this path live under any artifact root?
+/// Find the artifact that contains a path in the artifact roots of this process.
 #[must_use]
-pub fn is_artifact_path(path: &str) -> bool {
-	path.contains("/.tangram/artifacts/") || path.contains("/opt/tangram/artifacts/")
+pub fn artifact_containing(path: &Path) -> Option<PathBuf> {
+	ArtifactRoots::global().artifact_containing(path)
 }
 
-/// Find the on-disk path for an artifact ID by walking ancestor roots.
+/// Find the on-disk path for an artifact ID in the artifact roots of this process.
 #[must_use]
 pub fn artifact_path_for(id: &tg::artifact::Id) -> Option<PathBuf> {
-	let suffix = id.to_string();
-	let mut i = 0;
-	while let Some(root) = artifact_root_at(i) {
-		let candidate = PathBuf::from(&root).join(&suffix);
-		if candidate.exists() {
-			return Some(candidate);
-		}
-		i += 1;
-	}
-	None
+	ArtifactRoots::global().path_for(id)
 }
 
 /// Render a [`tg::template::Data`] to a `String`, using the closest artifact that contains it.
 pub fn render_template_data(data: &tg::template::Data) -> tg::Result<String> {
-	data.components
-		.iter()
-		.map(|component| match component {
-			tg::template::data::Component::String(string) => Ok(string.clone()),
-			tg::template::data::Component::Artifact(artifact_id) => {
-				let artifact_id = &artifact_id.item;
-				let path = artifact_path_for(artifact_id).ok_or_else(|| {
-					tg::error!("artifact {artifact_id} not present in any artifact root")
-				})?;
-				path.into_os_string()
-					.into_string()
-					.map_err(|os| tg::error!("artifact path is not valid UTF-8: {}", os.display()))
-			},
-			tg::template::data::Component::Placeholder(data) => Ok(data.name.clone()),
-		})
-		.collect()
+	ArtifactRoots::global().render_template_data(data)
 }
 
 /// Unrender a template string into a [`tg::Template`].
 pub fn unrender(string: &str) -> tg::Result<tg::Template> {
-	let mut i = 0;
-	while let Some(root) = artifact_root_at(i) {
-		if string.contains(&format!("{root}/")) {
-			return tg::Template::unrender(&root, string);
-		}
-		i += 1;
-	}
-	if string.contains("/opt/tangram/artifacts/") {
-		return tg::Template::unrender("/opt/tangram/artifacts", string);
-	}
-	Ok(tg::Template::from(tg::template::Component::String(
-		string.to_owned(),
-	)))
+	ArtifactRoots::global().unrender(string)
 }
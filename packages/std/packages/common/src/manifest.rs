@@ -5,10 +5,12 @@ use std::{
 	sync::LazyLock,
 };
 use tangram_client::prelude::*;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _, AsyncWriteExt};
 
 use crate::artifact_path_for;
 
+pub use wrap::ValidationError;
+
 /// The Tangram run entrypoint manifest.
 #[derive(
 	Clone,
@@ -37,6 +39,11 @@ pub struct Manifest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	#[tangram_serialize(id = 3, skip_serializing_if = "Option::is_none")]
 	pub args: Option<Vec<tg::template::Data>>,
+
+	/// The artifacts directory relative to the directory of the wrapper. A manifest with one is relocatable, and `$ORIGIN` at the start of its strings is the directory of the wrapper.
+	#[serde(rename = "artifactsPath", skip_serializing_if = "Option::is_none")]
+	#[tangram_serialize(id = 4, skip_serializing_if = "Option::is_none")]
+	pub artifacts_path: Option<String>,
 }
 
 /// An interpreter is another program that is used to launch the executable.
@@ -204,46 +211,314 @@ pub enum Executable {
 	Address(u64),
 }
 
+/// A builder for a [`Manifest`] from [`tg::Artifact`], [`tg::Template`] and [`tg::Mutation`] values. Library paths are normalized and deduplicated, and the manifest is checked with [`Manifest::validate`].
+#[derive(Clone, Debug)]
+pub struct ManifestBuilder {
+	interpreter: Option<InterpreterKind>,
+	executable: Executable,
+	library_paths: Vec<tg::template::Data>,
+	preloads: Vec<tg::template::Data>,
+	interpreter_args: Vec<tg::template::Data>,
+	env: Option<tg::mutation::Data>,
+	args: Vec<tg::template::Data>,
+}
+
+#[derive(Clone, Debug)]
+enum InterpreterKind {
+	Normal(tg::template::Data),
+	LdLinux(tg::template::Data),
+	LdMusl(tg::template::Data),
+	DyLd,
+}
+
+impl ManifestBuilder {
+	/// Create a builder for a manifest that runs the executable at a path.
+	#[must_use]
+	pub fn new(path: &tg::Template) -> Self {
+		Self::with_executable(Executable::Path(path.to_data()))
+	}
+
+	/// Create a builder for a manifest that runs an artifact.
+	#[must_use]
+	pub fn with_artifact(artifact: tg::Artifact) -> Self {
+		Self::new(&crate::template_from_artifact(artifact))
+	}
+
+	/// Create a builder for a manifest that renders a script and runs it with the interpreter.
+	#[must_use]
+	pub fn with_content(content: &tg::Template) -> Self {
+		Self::with_executable(Executable::Content(content.to_data()))
+	}
+
+	/// Create a builder for a manifest that jumps to an address in the executable it is embedded in.
+	#[must_use]
+	pub fn with_address(address: u64) -> Self {
+		Self::with_executable(Executable::Address(address))
+	}
+
+	fn with_executable(executable: Executable) -> Self {
+		Self {
+			interpreter: None,
+			executable,
+			library_paths: Vec::new(),
+			preloads: Vec::new(),
+			interpreter_args: Vec::new(),
+			env: None,
+			args: Vec::new(),
+		}
+	}
+
+	/// Run the executable with a normal interpreter.
+	#[must_use]
+	pub fn normal(mut self, path: &tg::Template) -> Self {
+		self.interpreter = Some(InterpreterKind::Normal(path.to_data()));
+		self
+	}
+
+	/// Run the executable with ld-linux.
+	#[must_use]
+	pub fn ld_linux(mut self, path: &tg::Template) -> Self {
+		self.interpreter = Some(InterpreterKind::LdLinux(path.to_data()));
+		self
+	}
+
+	/// Run the executable with ld-musl.
+	#[must_use]
+	pub fn ld_musl(mut self, path: &tg::Template) -> Self {
+		self.interpreter = Some(InterpreterKind::LdMusl(path.to_data()));
+		self
+	}
+
+	/// Run the executable with dyld.
+	#[must_use]
+	pub fn dyld(mut self) -> Self {
+		self.interpreter = Some(InterpreterKind::DyLd);
+		self
+	}
+
+	/// Add a library path for the interpreter.
+	#[must_use]
+	pub fn library_path(mut self, path: &tg::Template) -> Self {
+		self.library_paths.push(path.to_data());
+		self
+	}
+
+	/// Add library paths for the interpreter.
+	#[must_use]
+	pub fn library_paths<'a>(self, paths: impl IntoIterator<Item = &'a tg::Template>) -> Self {
+		paths.into_iter().fold(self, Self::library_path)
+	}
+
+	/// Add a library for the interpreter to preload.
+	#[must_use]
+	pub fn preload(mut self, path: &tg::Template) -> Self {
+		self.preloads.push(path.to_data());
+		self
+	}
+
+	/// Add an argument for the interpreter.
+	#[must_use]
+	pub fn interpreter_arg(mut self, arg: &tg::Template) -> Self {
+		self.interpreter_args.push(arg.to_data());
+		self
+	}
+
+	/// Set the mutation of the environment.
+	#[must_use]
+	pub fn env(mut self, env: &tg::Mutation) -> Self {
+		self.env = Some(env.to_data());
+		self
+	}
+
+	/// Add an argument for the executable.
+	#[must_use]
+	pub fn arg(mut self, arg: &tg::Template) -> Self {
+		self.args.push(arg.to_data());
+		self
+	}
+
+	/// Add arguments for the executable.
+	#[must_use]
+	pub fn args<'a>(self, args: impl IntoIterator<Item = &'a tg::Template>) -> Self {
+		args.into_iter().fold(self, Self::arg)
+	}
+
+	/// Build the manifest. An address executable is allowed, since it can only be built for an embedded wrapper.
+	pub fn build(self) -> Result<Manifest, ValidationError> {
+		// Normalize the library paths and keep the first of each.
+		let mut seen = std::collections::BTreeSet::new();
+		let library_paths: Vec<_> = self
+			.library_paths
+			.into_iter()
+			.map(normalize_path)
+			.filter(|path| seen.insert(display_template(path)))
+			.collect();
+		let non_empty = |values: Vec<tg::template::Data>| (!values.is_empty()).then_some(values);
+
+		let interpreter = match self.interpreter {
+			None if !library_paths.is_empty()
+				|| !self.preloads.is_empty()
+				|| !self.interpreter_args.is_empty() =>
+			{
+				return Err(ValidationError::Malformed(
+					"library paths, preloads and interpreter args require an interpreter"
+						.to_owned(),
+				));
+			},
+			Some(InterpreterKind::Normal(_)) if !library_paths.is_empty() => {
+				return Err(ValidationError::Ignored {
+					interpreter: "normal",
+					field: "libraryPaths",
+				});
+			},
+			Some(InterpreterKind::Normal(_)) if !self.preloads.is_empty() => {
+				return Err(ValidationError::Ignored {
+					interpreter: "normal",
+					field: "preloads",
+				});
+			},
+			Some(InterpreterKind::DyLd) if !self.interpreter_args.is_empty() => {
+				return Err(ValidationError::Ignored {
+					interpreter: "dyld",
+					field: "args",
+				});
+			},
+			None => None,
+			Some(InterpreterKind::Normal(path)) => Some(Interpreter::Normal(NormalInterpreter {
+				path,
+				args: self.interpreter_args,
+			})),
+			Some(InterpreterKind::LdLinux(path)) => {
+				Some(Interpreter::LdLinux(LdLinuxInterpreter {
+					path,
+					library_paths: non_empty(library_paths),
+					preloads: non_empty(self.preloads),
+					args: non_empty(self.interpreter_args),
+				}))
+			},
+			Some(InterpreterKind::LdMusl(path)) => Some(Interpreter::LdMusl(LdMuslInterpreter {
+				path,
+				library_paths: non_empty(library_paths),
+				preloads: non_empty(self.preloads),
+				args: non_empty(self.interpreter_args),
+			})),
+			Some(InterpreterKind::DyLd) => Some(Interpreter::DyLd(DyLdInterpreter {
+				library_paths: non_empty(library_paths),
+				preloads: non_empty(self.preloads),
+			})),
+		};
+
+		let manifest = Manifest {
+			interpreter,
+			executable: self.executable,
+			env: self.env,
+			args: non_empty(self.args),
+			artifacts_path: None,
+		};
+		let embedded = matches!(manifest.executable, Executable::Address(_));
+		manifest.validate(embedded)?;
+		Ok(manifest)
+	}
+}
+
 impl Manifest {
-	/// Read a manifest from the end of the given `[tg::File]`.
+	/// Read a manifest from the end of the given `[tg::File]`. Only the headers of the file and the manifest are read from its blob.
 	pub async fn read_from_file(file: tg::File) -> tg::Result<Option<Self>> {
 		tracing::debug!(?file, "Reading manifest from file");
-		let path = tg::checkout(tg::checkout::Arg {
-			artifact: tg::Referent::with_item(file.id().into()),
-			dependencies: false,
-			extension: None,
-			force: false,
-			lock: Some(tg::checkout::Lock::Attr),
-			path: None,
-		})
-		.await
-		.map_err(|error| tg::error!(!error, "failed to checkout the file"))?;
-		tokio::task::spawn_blocking(move || Self::read_from_path(path))
+		let blob = file
+			.contents()
 			.await
-			.map_err(|error| tg::error!(!error, "failed to read the manifest"))?
+			.map_err(|error| tg::error!(!error, "failed to get the file contents"))?;
+		let mut reader = blob
+			.read(tg::blob::read::Arg::default())
+			.await
+			.map_err(|error| tg::error!(!error, "failed to read the blob"))?;
+		let size = reader
+			.seek(std::io::SeekFrom::End(0))
+			.await
+			.map_err(|error| tg::error!(!error, "failed to get the size of the blob"))?;
+
+		// Read the ranges of the blob that the manifest reader needs.
+		let mut manifest_reader = wrap::ManifestReader::new(size, None);
+		loop {
+			let ranges = match manifest_reader.missing() {
+				Ok(ranges) => ranges,
+				Err(error) => {
+					return Self::from_output(Err(error))
+						.map_err(|error| tg::error!(!error, "failed to read the manifest"));
+				},
+			};
+			if ranges.is_empty() {
+				break;
+			}
+			for range in ranges {
+				let mut bytes = vec![0; (range.end - range.start).try_into().unwrap()];
+				reader
+					.seek(std::io::SeekFrom::Start(range.start))
+					.await
+					.map_err(|error| tg::error!(!error, "failed to seek the blob"))?;
+				reader
+					.read_exact(&mut bytes)
+					.await
+					.map_err(|error| tg::error!(!error, "failed to read the blob"))?;
+				manifest_reader
+					.supply(range.start, &bytes)
+					.map_err(|error| tg::error!(!error, "failed to read the manifest"))?;
+			}
+		}
+		Self::from_output(manifest_reader.finish())
 			.map_err(|error| tg::error!(!error, "failed to read the manifest"))
 	}
 
+	/// Check whether the given `[tg::File]` is a wrapper, without reading more of it than the headers and the manifest.
+	pub async fn is_wrapper(file: &tg::File) -> tg::Result<bool> {
+		Ok(Self::read_from_file(file.clone()).await?.is_some())
+	}
+
 	/// Read a manifest from the end of the file at the given path.
 	pub fn read_from_path(path: impl AsRef<Path>) -> std::io::Result<Option<Self>> {
 		let path = path.as_ref();
 		tracing::debug!(path = %path.display(), "Reading manifest from path");
-		Ok(wrap::read_manifest(path, None).manifest)
+		Self::from_output(wrap::read_manifest(path, None))
+	}
+
+	/// Read a manifest from the end of the bytes of a file.
+	pub fn read_from_bytes(bytes: &[u8]) -> std::io::Result<Option<Self>> {
+		Self::from_output(wrap::read_manifest_from_bytes(bytes, None))
+	}
+
+	fn from_output(output: wrap::Result<wrap::Output<Self>>) -> std::io::Result<Option<Self>> {
+		match output {
+			Ok(output) => Ok(output.manifest),
+
+			// A file that is not a wrapper, or is a corrupt one, has no manifest.
+			Err(error) if error.is_malformed() => {
+				tracing::debug!(%error, "not a valid wrapper");
+				Ok(None)
+			},
+
+			Err(error) => Err(error.into()),
+		}
 	}
 
 	#[allow(clippy::too_many_lines)]
+	#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 	pub async fn embed(&self, file: &tg::File) -> tg::Result<tg::File> {
 		#[cfg(feature = "tracing")]
 		tracing::debug!(?self, "Embedding manifest");
+		self.validate(true)
+			.map_err(|error| tg::error!(source = error, "invalid manifest"))?;
 
-		// Get the paths of the required files.
-		let wrapper_bin = TANGRAM_WRAPPER_BIN_PATH
-			.as_ref()
-			.ok_or_else(|| tg::error!("missing wrapper bin"))?;
-		let wrapper_exe = TANGRAM_WRAPPER_EXE_PATH
-			.as_ref()
-			.ok_or_else(|| tg::error!("missing wrapper exe"))?;
-		let objcopy = TANGRAM_OBJCOPY_PATH.as_ref();
+		// Get the paths of the required files. A directory of wrappers is used if there is no single wrapper.
+		let wrapper = match (
+			TANGRAM_WRAPPER_BIN_PATH.as_ref(),
+			TANGRAM_WRAPPER_EXE_PATH.as_ref(),
+		) {
+			(Some(bin), Some(exe)) => Some((bin, exe)),
+			_ if TANGRAM_WRAPPERS_PATH.is_some() => None,
+			(None, _) => return Err(tg::error!("missing wrapper bin")),
+			(_, None) => return Err(tg::error!("missing wrapper exe")),
+		};
 
 		// Cache the input file, which is not a dependency of this executable.
 		tg::cache::cache(tg::cache::Arg {
@@ -258,10 +533,12 @@ impl Manifest {
 			.ok_or_else(|| tg::error!("failed to locate input file in any artifact root"))?;
 
 		// Provide the context to wrap.
-		wrap::set_wrapper_bin_path(wrapper_bin.clone());
-		wrap::set_wrapper_exe_path(wrapper_exe.clone());
-		if let Some(objcopy) = objcopy {
-			wrap::set_objcopy_path(objcopy.clone());
+		if let Some((wrapper_bin, wrapper_exe)) = wrapper {
+			wrap::set_wrapper_bin_path(wrapper_bin);
+			wrap::set_wrapper_exe_path(wrapper_exe);
+		}
+		if let Some(wrappers) = TANGRAM_WRAPPERS_PATH.as_ref() {
+			wrap::set_wrappers_path(wrappers);
 		}
 
 		// Copy the input file to a a temp.
@@ -278,7 +555,7 @@ impl Manifest {
 		tokio::task::spawn_blocking({
 			let manifest = self.clone();
 			let output = tempfile.path().to_owned();
-			move || wrap::embed(output, &manifest, None)
+			move || wrap::embed(output, &manifest, None, wrap::Options::default())
 		})
 		.await
 		.map_err(|error| tg::error!(!error, "failed to wrap the binary"))?
@@ -345,18 +622,42 @@ impl Manifest {
 	}
 
 	pub fn write_to_path(&self, path: &Path) -> tg::Result<()> {
-		wrap::write_manifest(path, self, None);
+		wrap::write_manifest(path, self, None, wrap::Options::default()).map_err(
+			|error| tg::error!(!error, path = %path.display(), "failed to write the manifest"),
+		)
+	}
+
+	/// Apply edits to the manifest of the wrapper at the given path in place, keeping its encoding and compression.
+	pub fn edit_path(path: &Path, edits: &[wrap::Edit]) -> tg::Result<()> {
+		wrap::edit_manifest::<Self>(path, None, |manifest| {
+			manifest
+				.edit(edits)
+				.map_err(|error| wrap::Error::InvalidEdit(error.to_string()))
+		})
+		.map_err(|error| tg::error!(!error, path = %path.display(), "failed to edit the manifest"))
+	}
+
+	/// Apply edits to the environment and arguments with `wrap`'s implementation. Paths in an artifact root are unrendered into templates with artifact components.
+	pub fn edit(&mut self, edits: &[wrap::Edit]) -> tg::Result<()> {
+		let mut value = serde_json::to_value(&*self)
+			.map_err(|error| tg::error!(!error, "failed to serialize the manifest"))?;
+		let roots = crate::ArtifactRoots::global().unrender_roots();
+		wrap::apply_edits(&mut value, edits, &roots)
+			.map_err(|error| tg::error!(!error, "failed to edit the manifest"))?;
+		*self = serde_json::from_value(value)
+			.map_err(|error| tg::error!(!error, "failed to deserialize the manifest"))?;
 		Ok(())
 	}
 
-	/// Create a new wrapper from a manifest. Will locate the wrapper file from the `TANGRAM_WRAPPER_EXE_PATH` environment variable.
+	/// Create a new wrapper from a manifest. Will locate the wrapper file from the `TANGRAM_WRAPPER_EXE_PATH` environment variable, or in the `TANGRAM_WRAPPERS_PATH` directory for the system of the executable.
+	#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 	pub async fn write(&self) -> tg::Result<tg::File> {
 		tracing::debug!(?self, "Writing manifest");
+		self.validate(false)
+			.map_err(|error| tg::error!(source = error, "invalid manifest"))?;
 
 		// Get the path of the wrapper file.
-		let path = TANGRAM_WRAPPER_EXE_PATH
-			.as_ref()
-			.ok_or_else(|| tg::error!("missing wrapper exe"))?;
+		let path = self.wrapper_exe_path()?;
 
 		// Create a temp.
 		let temp = tempfile::NamedTempFile::new()
@@ -439,6 +740,137 @@ impl Manifest {
 		Ok(output_file)
 	}
 
+	/// Find the wrapper executable for the system of the executable. A wrapper set with `TANGRAM_WRAPPER_EXE_PATH` must be for the same system as the executable if the executable is a binary.
+	fn wrapper_exe_path(&self) -> tg::Result<PathBuf> {
+		// Get the system of the executable, if it is a binary.
+		let system = match &self.executable {
+			Executable::Path(executable) => {
+				let executable = crate::render_template_data(executable)?;
+				match wrap::system(&executable, None) {
+					Ok(system) => Some(system),
+					Err(error) if error.is_malformed() && TANGRAM_WRAPPER_EXE_PATH.is_some() => {
+						None
+					},
+					Err(error) => {
+						return Err(
+							tg::error!(!error, executable = %executable, "failed to get the system of the executable"),
+						);
+					},
+				}
+			},
+			_ => None,
+		};
+
+		// Use the wrapper that was set explicitly if there is one.
+		if let Some(path) = TANGRAM_WRAPPER_EXE_PATH.as_ref() {
+			if let Some(system) = system {
+				let wrapper = wrap::system(path, None).map_err(
+					|error| tg::error!(!error, path = %path.display(), "failed to get the system of the wrapper"),
+				)?;
+				if wrapper != system {
+					let error = wrap::Error::WrapperMismatch {
+						wrapper,
+						binary: system,
+					};
+					return Err(
+						tg::error!(!error, path = %path.display(), "the wrapper does not match the executable"),
+					);
+				}
+			}
+			return Ok(path.clone());
+		}
+
+		// Otherwise, select the wrapper for the system of the executable.
+		let wrappers = TANGRAM_WRAPPERS_PATH
+			.as_ref()
+			.ok_or_else(|| tg::error!("missing wrapper exe"))?;
+		let system = system.ok_or_else(|| {
+			tg::error!("cannot select a wrapper for an executable that is not a path")
+		})?;
+		wrap::set_wrappers_path(wrappers);
+		let (path, _) = wrap::wrapper_paths(&system)
+			.map_err(|error| tg::error!(!error, "failed to select a wrapper"))?;
+		Ok(path)
+	}
+
+	/// Check that the manifest is one the wrapper runtime accepts, with the same rules as `wrap validate`. An address executable is only allowed if `embedded` is set.
+	pub fn validate(&self, embedded: bool) -> Result<(), ValidationError> {
+		let value = serde_json::to_value(self)
+			.map_err(|error| ValidationError::Malformed(error.to_string()))?;
+		match wrap::validate(&value, embedded) {
+			Ok(()) => Ok(()),
+			Err(wrap::Error::Invalid(error)) => Err(error),
+			Err(error) => Err(ValidationError::Malformed(error.to_string())),
+		}
+	}
+
+	/// Resolve a relocatable manifest for the wrapper in the directory `origin`. Artifacts become paths in its artifacts directory and a leading `$ORIGIN` in strings becomes `origin`. Other manifests are returned unchanged.
+	#[must_use]
+	pub fn relocate(&self, origin: &Path) -> Self {
+		let mut manifest = self.clone();
+		let Some(artifacts_path) = manifest.artifacts_path.take() else {
+			return manifest;
+		};
+		let relocation = Relocation {
+			origin: origin.display().to_string(),
+			artifacts_dir: origin.join(artifacts_path),
+		};
+
+		match &mut manifest.interpreter {
+			Some(Interpreter::Normal(interpreter)) => {
+				relocation.template(&mut interpreter.path);
+				interpreter
+					.args
+					.iter_mut()
+					.for_each(|arg| relocation.template(arg));
+			},
+			Some(
+				Interpreter::LdLinux(LdLinuxInterpreter {
+					path,
+					library_paths,
+					preloads,
+					args,
+				})
+				| Interpreter::LdMusl(LdMuslInterpreter {
+					path,
+					library_paths,
+					preloads,
+					args,
+				}),
+			) => {
+				relocation.template(path);
+				for template in [library_paths, preloads, args]
+					.into_iter()
+					.flatten()
+					.flatten()
+				{
+					relocation.template(template);
+				}
+			},
+			Some(Interpreter::DyLd(interpreter)) => {
+				let templates = [&mut interpreter.library_paths, &mut interpreter.preloads];
+				for template in templates.into_iter().flatten().flatten() {
+					relocation.template(template);
+				}
+			},
+			None => (),
+		}
+		match &mut manifest.executable {
+			Executable::Path(template) | Executable::Content(template) => {
+				relocation.template(template);
+			},
+			Executable::Address(_) => (),
+		}
+		if let Some(env) = &mut manifest.env {
+			relocation.mutation(env);
+		}
+		for arg in manifest.args.iter_mut().flatten() {
+			relocation.template(arg);
+		}
+
+		manifest
+	}
+
 	/// Collect the dependencies from a manifest.
 	#[must_use]
 	pub fn dependencies(&self) -> BTreeMap<tg::Reference, Option<tg::file::Dependency>> {
@@ -520,6 +952,25 @@ impl Manifest {
 	}
 }
 
+impl wrap::Codec for Manifest {
+	fn encode(&self, encoding: wrap::Encoding) -> wrap::Result<Vec<u8>> {
+		match encoding {
+			wrap::Encoding::Json => Ok(serde_json::to_vec(self)?),
+			wrap::Encoding::Serialize => {
+				tangram_serialize::to_vec(self).map_err(|error| wrap::Error::Codec(error.into()))
+			},
+		}
+	}
+
+	fn decode(data: &[u8], encoding: wrap::Encoding) -> wrap::Result<Self> {
+		match encoding {
+			wrap::Encoding::Json => Ok(serde_json::from_slice(data)?),
+			wrap::Encoding::Serialize => tangram_serialize::from_slice(data)
+				.map_err(|error| wrap::Error::Codec(error.into())),
+		}
+	}
+}
+
 pub fn collect_dependencies_from_value_data(
 	value: &tg::value::Data,
 	dependencies: &mut BTreeMap<tg::Reference, Option<tg::file::Dependency>>,
@@ -611,6 +1062,108 @@ pub fn collect_dependencies_from_mutation_data(
 	}
 }
 
+/// Where the artifacts and `$ORIGIN` of a relocatable manifest are.
+struct Relocation {
+	origin: String,
+	artifacts_dir: PathBuf,
+}
+
+impl Relocation {
+	fn template(&self, template: &mut tg::template::Data) {
+		for component in &mut template.components {
+			match component {
+				tg::template::data::Component::String(string) => {
+					if let Some(rest) = string.strip_prefix("$ORIGIN") {
+						*string = format!("{}{rest}", self.origin);
+					}
+				},
+				tg::template::data::Component::Artifact(id) => {
+					let path = self.artifacts_dir.join(id.item.to_string());
+					*component = tg::template::data::Component::String(path.display().to_string());
+				},
+				tg::template::data::Component::Placeholder(_) => (),
+			}
+		}
+	}
+
+	fn value(&self, value: &mut tg::value::Data) {
+		match value {
+			tg::value::Data::Object(id) => {
+				if matches!(
+					id.item,
+					tg::object::Id::Directory(_)
+						| tg::object::Id::File(_)
+						| tg::object::Id::Symlink(_)
+				) {
+					let path = self.artifacts_dir.join(id.item.to_string());
+					*value = tg::value::Data::String(path.display().to_string());
+				}
+			},
+			tg::value::Data::Mutation(mutation) => self.mutation(mutation),
+			tg::value::Data::Template(template) => self.template(template),
+			tg::value::Data::Array(values) => values.iter_mut().for_each(|value| self.value(value)),
+			tg::value::Data::Map(map) => map.values_mut().for_each(|value| self.value(value)),
+			_ => (),
+		}
+	}
+
+	fn mutation(&self, mutation: &mut tg::mutation::Data) {
+		match mutation {
+			tg::mutation::Data::Unset => (),
+			tg::mutation::Data::Set { value } | tg::mutation::Data::SetIfUnset { value } => {
+				self.value(value);
+			},
+			tg::mutation::Data::Prepend { values } | tg::mutation::Data::Append { values } => {
+				values.iter_mut().for_each(|value| self.value(value));
+			},
+			tg::mutation::Data::Prefix { template, .. }
+			| tg::mutation::Data::Suffix { template, .. } => self.template(template),
+			tg::mutation::Data::Merge { value } => {
+				value.values_mut().for_each(|value| self.value(value));
+			},
+		}
+	}
+}
+
+/// Display a template the way `wrap inspect` does, with artifacts as `${id}`.
+fn display_template(template: &tg::template::Data) -> String {
+	template
+		.components
+		.iter()
+		.map(|component| match component {
+			tg::template::data::Component::String(string) => string.clone(),
+			tg::template::data::Component::Artifact(artifact) => format!("${{{}}}", artifact.item),
+			tg::template::data::Component::Placeholder(placeholder) => {
+				format!("<{}>", placeholder.name)
+			},
+		})
+		.collect()
+}
+
+/// Merge adjacent strings of a path, drop empty ones, and remove a trailing slash.
+fn normalize_path(path: tg::template::Data) -> tg::template::Data {
+	let mut components: Vec<tg::template::data::Component> = Vec::new();
+	for component in path.components {
+		match (components.last_mut(), component) {
+			(_, tg::template::data::Component::String(string)) if string.is_empty() => (),
+			(
+				Some(tg::template::data::Component::String(last)),
+				tg::template::data::Component::String(string),
+			) => last.push_str(&string),
+			(_, component) => components.push(component),
+		}
+	}
+	if let Some(tg::template::data::Component::String(last)) = components.last_mut() {
+		let trimmed = last.trim_end_matches('/');
+		if trimmed.is_empty() && components.len() > 1 {
+			components.pop();
+		} else if !trimmed.is_empty() {
+			last.truncate(trimmed.len());
+		}
+	}
+	tg::template::Data { components }
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn dependency_from_object_id(id: &tg::object::Id) -> Option<tg::file::Dependency> {
 	Some(tg::file::Dependency(tg::Referent::with_item(Some(
@@ -632,8 +1185,9 @@ static TANGRAM_WRAPPER_EXE_PATH: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
 		.map(PathBuf::from)
 });
 
-static TANGRAM_OBJCOPY_PATH: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
-	std::env::var("TANGRAM_OBJCOPY_PATH")
+// A directory with a wrapper for each system, used when there is no single wrapper.
+static TANGRAM_WRAPPERS_PATH: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+	std::env::var("TANGRAM_WRAPPERS_PATH")
 		.ok()
 		.map(PathBuf::from)
 });
@@ -644,3 +1198,64 @@ static TANGRAM_CODESIGN_PATH: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
 		.ok()
 		.map(PathBuf::from)
 });
+
+#[cfg(test)]
+mod tests {
+	use super::{Executable, Interpreter, ManifestBuilder, ValidationError, display_template};
+	use tangram_client::prelude::*;
+
+	fn template(string: &str) -> tg::Template {
+		tg::Template::from(tg::template::Component::String(string.to_owned()))
+	}
+
+	fn display(templates: Option<&Vec<tg::template::Data>>) -> Vec<String> {
+		templates
+			.into_iter()
+			.flatten()
+			.map(display_template)
+			.collect()
+	}
+
+	#[test]
+	fn builder() {
+		let manifest = ManifestBuilder::new(&template("/bin/program"))
+			.ld_linux(&template("/lib/ld-linux-x86-64.so.2"))
+			.library_paths(&[template("/lib/"), template("/usr/lib"), template("/lib")])
+			.preload(&template("/lib/libinject.so"))
+			.arg(&template("--default"))
+			.build()
+			.unwrap();
+		let Some(Interpreter::LdLinux(interpreter)) = &manifest.interpreter else {
+			panic!("expected an ld-linux interpreter");
+		};
+		assert_eq!(
+			display(interpreter.library_paths.as_ref()),
+			["/lib", "/usr/lib"]
+		);
+		assert!(interpreter.args.is_none());
+		assert_eq!(display(manifest.args.as_ref()), ["--default"]);
+
+		let error = ManifestBuilder::new(&template("")).build().unwrap_err();
+		assert_eq!(error, ValidationError::EmptyPath("executable"));
+
+		let error = ManifestBuilder::new(&template("/bin/program"))
+			.normal(&template("/bin/sh"))
+			.preload(&template("/lib/libinject.so"))
+			.build()
+			.unwrap_err();
+		assert_eq!(
+			error,
+			ValidationError::Ignored {
+				interpreter: "normal",
+				field: "preloads",
+			}
+		);
+
+		let manifest = ManifestBuilder::with_address(0x1000).build().unwrap();
+		assert!(matches!(manifest.executable, Executable::Address(0x1000)));
+		assert_eq!(
+			manifest.validate(false),
+			Err(ValidationError::AddressNotEmbedded)
+		);
+	}
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeSet;
+
+/// Expand the `@file` arguments with the arguments in the files, as GCC does. Response files may include other response files. An argument whose file cannot be read, or that would include itself, is kept as is.
+#[must_use]
+pub fn expand(args: &[String]) -> Vec<String> {
+	let mut expanded = Vec::new();
+	expand_inner(args, &mut BTreeSet::new(), &mut expanded);
+	expanded
+}
+
+fn expand_inner(args: &[String], including: &mut BTreeSet<String>, expanded: &mut Vec<String>) {
+	for arg in args {
+		let Some(path) = arg.strip_prefix('@') else {
+			expanded.push(arg.clone());
+			continue;
+		};
+		if including.contains(path) {
+			expanded.push(arg.clone());
+			continue;
+		}
+		let Ok(contents) = std::fs::read_to_string(path) else {
+			expanded.push(arg.clone());
+			continue;
+		};
+		including.insert(path.to_owned());
+		expand_inner(&parse(&contents), including, expanded);
+		including.remove(path);
+	}
+}
+
+/// Split the contents of a response file into arguments. Arguments are separated by whitespace, may be quoted with single or double quotes, and a backslash escapes the next character anywhere.
+#[must_use]
+pub fn parse(contents: &str) -> Vec<String> {
+	let mut args = Vec::new();
+	let mut arg = String::new();
+	let mut in_arg = false;
+	let mut quote = None;
+	let mut chars = contents.chars();
+	while let Some(c) = chars.next() {
+		match (c, quote) {
+			('\\', _) => {
+				if let Some(c) = chars.next() {
+					arg.push(c);
+				}
+				in_arg = true;
+			},
+			(c, Some(q)) if c == q => quote = None,
+			(c, Some(_)) => arg.push(c),
+			('\'' | '"', None) => {
+				quote = Some(c);
+				in_arg = true;
+			},
+			(c, None) if c.is_whitespace() => {
+				if in_arg {
+					args.push(std::mem::take(&mut arg));
+					in_arg = false;
+				}
+			},
+			(c, None) => {
+				arg.push(c);
+				in_arg = true;
+			},
+		}
+	}
+	if in_arg {
+		args.push(arg);
+	}
+	args
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{expand, parse};
+
+	#[test]
+	fn response_files() {
+		assert_eq!(
+			parse("-o 'a b' \"c\\\"d\" e\\ f ''\n-L/lib"),
+			["-o", "a b", "c\"d", "e f", "", "-L/lib"]
+		);
+
+		let temp = tempfile::TempDir::new().unwrap();
+		let inner = temp.path().join("inner.rsp");
+		let outer = temp.path().join("outer.rsp");
+		std::fs::write(&inner, "-lfoo main.o").unwrap();
+		std::fs::write(
+			&outer,
+			format!("-o out @{} @{}", inner.display(), outer.display()),
+		)
+		.unwrap();
+		let args = [format!("@{}", outer.display()), "@missing.rsp".to_owned()];
+		assert_eq!(
+			expand(&args),
+			[
+				"-o".to_owned(),
+				"out".to_owned(),
+				"-lfoo".to_owned(),
+				"main.o".to_owned(),
+				format!("@{}", outer.display()),
+				"@missing.rsp".to_owned(),
+			]
+		);
+	}
+}
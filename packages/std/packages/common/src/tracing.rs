@@ -1,25 +1,232 @@
-use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt};
+use std::{
+	io::Write as _,
+	sync::{
+		Mutex,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::{SystemTime, UNIX_EPOCH},
+};
+use tracing_subscriber::{
+	Layer, fmt::writer::BoxMakeWriter, prelude::__tracing_subscriber_SubscriberExt,
+	registry::LookupSpan, util::SubscriberInitExt,
+};
 
-/// Initialize tracing.
+/// Initialize tracing. The variable `var_name`, such as `TGLD_TRACING`, holds the filter. `{var_name}_FORMAT` selects the `text`, `json` or `chrome` format, and `{var_name}_FILE` is a file to write to instead of stderr, in which `{pid}` is replaced with the process ID.
 pub fn setup(var_name: &str) {
+	let filter = std::env::var(var_name).ok();
+	let format = std::env::var(format!("{var_name}_FORMAT")).ok();
+	let file = std::env::var(format!("{var_name}_FILE")).ok();
+	setup_with(filter.as_deref(), format.as_deref(), file.as_deref());
+}
+
+/// Initialize tracing with the given filter, format and file. Tracing is disabled if there is no valid filter.
+pub fn setup_with(filter: Option<&str>, format: Option<&str>, file: Option<&str>) {
 	// Create the env layer.
-	let targets_layer = std::env::var(var_name)
-		.ok()
-		.and_then(|filter| filter.parse::<tracing_subscriber::filter::Targets>().ok());
+	let targets_layer =
+		filter.and_then(|filter| filter.parse::<tracing_subscriber::filter::Targets>().ok());
 
 	// If tracing is enabled, create and initialize the subscriber.
 	if let Some(targets_layer) = targets_layer {
-		let format_layer = tracing_subscriber::fmt::layer()
-			.compact()
-			.with_ansi(false)
-			.with_span_events(
-				tracing_subscriber::fmt::format::FmtSpan::NEW
-					| tracing_subscriber::fmt::format::FmtSpan::CLOSE,
-			)
-			.with_writer(std::io::stderr);
+		let file = file.and_then(|path| {
+			let path = path.replace("{pid}", &std::process::id().to_string());
+			std::fs::File::create(&path)
+				.inspect_err(|error| {
+					eprintln!("failed to create the trace file {path}: {error}");
+				})
+				.ok()
+		});
+		let span_events = tracing_subscriber::fmt::format::FmtSpan::NEW
+			| tracing_subscriber::fmt::format::FmtSpan::CLOSE;
+		let (text_layer, json_layer, chrome_layer) = match format {
+			Some("json") => {
+				let layer = tracing_subscriber::fmt::layer()
+					.json()
+					.with_span_events(span_events)
+					.with_writer(make_writer(file));
+				(None, Some(layer), None)
+			},
+			Some("chrome") => {
+				let writer: Box<dyn std::io::Write + Send> = match file {
+					Some(file) => Box::new(file),
+					None => Box::new(std::io::stderr()),
+				};
+				(None, None, Some(ChromeLayer::new(writer)))
+			},
+			_ => {
+				let layer = tracing_subscriber::fmt::layer()
+					.compact()
+					.with_ansi(false)
+					.with_span_events(span_events)
+					.with_writer(make_writer(file));
+				(Some(layer), None, None)
+			},
+		};
 		let subscriber = tracing_subscriber::registry()
 			.with(targets_layer)
-			.with(format_layer);
+			.with(text_layer)
+			.with(json_layer)
+			.with(chrome_layer);
 		subscriber.init();
 	}
 }
+
+fn make_writer(file: Option<std::fs::File>) -> BoxMakeWriter {
+	match file {
+		Some(file) => BoxMakeWriter::new(Mutex::new(file)),
+		None => BoxMakeWriter::new(std::io::stderr),
+	}
+}
+
+/// A layer that writes spans and events in the Chrome trace event format. The output is a JSON array with one event per line that is left open, which trace viewers accept. Timestamps are microseconds since the Unix epoch, so the traces of several processes can be merged.
+struct ChromeLayer {
+	writer: Mutex<Box<dyn std::io::Write + Send>>,
+	pid: u32,
+}
+
+/// The start time and fields of a span.
+struct Timing {
+	start: u64,
+	args: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ChromeLayer {
+	fn new(mut writer: Box<dyn std::io::Write + Send>) -> Self {
+		writeln!(writer, "[").ok();
+		Self {
+			writer: Mutex::new(writer),
+			pid: std::process::id(),
+		}
+	}
+
+	fn write(&self, event: &serde_json::Value) {
+		let mut writer = self.writer.lock().unwrap();
+		writeln!(writer, "{event},").ok();
+		writer.flush().ok();
+	}
+}
+
+impl<S> Layer<S> for ChromeLayer
+where
+	S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+	fn on_new_span(
+		&self,
+		attrs: &tracing::span::Attributes<'_>,
+		id: &tracing::span::Id,
+		ctx: tracing_subscriber::layer::Context<'_, S>,
+	) {
+		let Some(span) = ctx.span(id) else {
+			return;
+		};
+		let mut visitor = Visitor::default();
+		attrs.record(&mut visitor);
+		span.extensions_mut().insert(Timing {
+			start: now(),
+			args: visitor.0,
+		});
+	}
+
+	fn on_record(
+		&self,
+		id: &tracing::span::Id,
+		values: &tracing::span::Record<'_>,
+		ctx: tracing_subscriber::layer::Context<'_, S>,
+	) {
+		let Some(span) = ctx.span(id) else {
+			return;
+		};
+		if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+			let mut visitor = Visitor(std::mem::take(&mut timing.args));
+			values.record(&mut visitor);
+			timing.args = visitor.0;
+		}
+	}
+
+	fn on_event(
+		&self,
+		event: &tracing::Event<'_>,
+		_ctx: tracing_subscriber::layer::Context<'_, S>,
+	) {
+		let mut visitor = Visitor::default();
+		event.record(&mut visitor);
+		let mut args = visitor.0;
+		let name = match args.remove("message") {
+			Some(serde_json::Value::String(message)) => message,
+			_ => event.metadata().name().to_owned(),
+		};
+		self.write(&serde_json::json!({
+			"name": name,
+			"cat": event.metadata().target(),
+			"ph": "i",
+			"s": "t",
+			"ts": now(),
+			"pid": self.pid,
+			"tid": thread_id(),
+			"args": args,
+		}));
+	}
+
+	fn on_close(&self, id: tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+		let Some(span) = ctx.span(&id) else {
+			return;
+		};
+		let Some(timing) = span.extensions_mut().remove::<Timing>() else {
+			return;
+		};
+		self.write(&serde_json::json!({
+			"name": span.name(),
+			"cat": span.metadata().target(),
+			"ph": "X",
+			"ts": timing.start,
+			"dur": now().saturating_sub(timing.start),
+			"pid": self.pid,
+			"tid": thread_id(),
+			"args": timing.args,
+		}));
+	}
+}
+
+/// Collect the fields of a span or event as JSON.
+#[derive(Default)]
+struct Visitor(serde_json::Map<String, serde_json::Value>);
+
+impl tracing::field::Visit for Visitor {
+	fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+		self.0
+			.insert(field.name().to_owned(), format!("{value:?}").into());
+	}
+}
+
+/// Get the current time in microseconds since the Unix epoch.
+fn now() -> u64 {
+	let micros = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_micros();
+	u64::try_from(micros).unwrap_or(u64::MAX)
+}
+
+/// Get a small number that identifies the current thread.
+fn thread_id() -> u64 {
+	static NEXT: AtomicU64 = AtomicU64::new(1);
+	thread_local! {
+		static ID: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+	}
+	ID.with(|id| *id)
+}
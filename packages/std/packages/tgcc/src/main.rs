@@ -1,3 +1,4 @@
+use common::config::{Config, Kind, Setting};
 use std::{
 	collections::BTreeMap,
 	io::Write,
@@ -65,24 +66,17 @@ enum RemapKind {
 
 impl Environment {
 	// Parse the runtime environment.
-	fn parse() -> tg::Result<Self> {
+	fn parse(config: &Config) -> tg::Result<Self> {
 		let mut env = BTreeMap::new();
-		let mut enable = false;
+		let enable = config.bool("TGCC_ENABLE");
 		for (key, value) in std::env::vars() {
-			match key.as_str() {
-				"TGCC_ENABLE" => {
-					enable = value.parse().map_err(|error| {
-						tg::error!(source = error, "Failed to parse TGCC_ENABLE")
-					})?;
-				},
-				key if BLACKLISTED_ENV_VARS.contains(&key) => {},
-				_ => {
-					let value = common::unrender(&value)?;
-					env.insert(key, value.into());
-				},
+			if key.starts_with("TGCC_") || BLACKLISTED_ENV_VARS.contains(&key.as_str()) {
+				continue;
 			}
+			let value = common::unrender(&value)?;
+			env.insert(key, value.into());
 		}
-		let cc = which_cc()?;
+		let cc = which_cc(config)?;
 		Ok(Self { enable, cc, env })
 	}
 }
@@ -90,14 +84,14 @@ impl Environment {
 impl Args {
 	// Parse the cli arguments as if this program was gcc to extract the sources, search paths, and rest of the arguments.
 	#[allow(clippy::too_many_lines)]
-	fn parse() -> Self {
+	fn parse(args: &[String]) -> Self {
 		let mut remap_targets = vec![];
 		let mut output = None;
 		let mut cli_args = vec![];
 		let mut stdin = false;
 		let mut iprefix = String::new();
 
-		let mut args = std::env::args().skip(1).peekable();
+		let mut args = args.iter().cloned().peekable();
 		while let Some(arg) = args.next() {
 			match arg.as_str() {
 				// By convention, '-' refers to using stdin as the source file.
@@ -282,16 +276,22 @@ fn main() {
 }
 
 fn main_inner() -> tg::Result<()> {
+	// Read the config from the config file, the environment and arguments.
+	let config = Config::load("tgcc", SETTINGS)?;
+
+	// Setup tracing.
+	config.setup_tracing();
+
 	// Get the environment information (env vars, runtime, cc path).
-	let environment = Environment::parse()?;
+	let environment = Environment::parse(&config)?;
 
 	// Get the command line arguments.
-	let args = Args::parse();
+	let args = Args::parse(&config.args);
 
 	// If this invocation isn't being used to generate output or needs to read from stdin, fallback on the detected C compiler.
 	if !environment.enable || args.output.is_none() || args.stdin {
 		let error = std::process::Command::new(&environment.cc)
-			.args(std::env::args_os().skip(1))
+			.args(&config.passthrough_args)
 			.exec();
 
 		return Err(tg::error!(
@@ -312,6 +312,7 @@ fn main_inner() -> tg::Result<()> {
 }
 
 #[allow(clippy::too_many_lines)]
+#[tracing::instrument(skip_all)]
 async fn run_proxy(environment: Environment, args: Args) -> tg::Result<()> {
 	let Args {
 		output,
@@ -403,23 +404,20 @@ async fn run_proxy(environment: Environment, args: Args) -> tg::Result<()> {
 		.await
 		.map_err(|error| tg::error!(source = error, "cc failed: no output"))?;
 
-	// Verify we did everything correctly.
-	let mut tangram_path = std::env::current_dir()
+	// Find the output in the artifact roots, including the checkout of the current directory.
+	let current_dir = std::env::current_dir()
 		.map_err(|error| tg::error!(source = error, "failed to get current working directory"))?;
-	while !tangram_path.join(".tangram").exists() {
-		let Some(parent) = tangram_path.parent() else {
-			break;
-		};
-		tangram_path = parent.into();
+	let roots = common::ArtifactRoots::global();
+	if let Some(checkout) = current_dir
+		.ancestors()
+		.map(|ancestor| ancestor.join(".tangram/artifacts"))
+		.find(|path| path.is_dir())
+	{
+		roots.add(checkout);
 	}
-	let artifacts_path = if tangram_path.join(".tangram").exists() {
-		tangram_path.join(".tangram/artifacts")
-	} else if std::path::Path::new("/opt/tangram/artifacts").exists() {
-		"/opt/tangram/artifacts".into()
-	} else {
-		return Err(tg::error!("failed to find the artifacts directory"));
-	};
-	let artifact_path = artifacts_path.join(output_file.id().to_string());
+	let artifact_path = roots
+		.path_for(&output_file.id())
+		.ok_or_else(|| tg::error!("failed to find the output in the artifact roots"))?;
 	eprintln!("Copying {} to {output:#?}", artifact_path.display());
 	std::fs::copy(artifact_path, output)
 		.map_err(|error| tg::error!(source = error, "failed to copy file"))?;
@@ -428,9 +426,9 @@ async fn run_proxy(environment: Environment, args: Args) -> tg::Result<()> {
 }
 
 // Find the C compiler by checking the TGCC_COMPILER compiler or searching PATH for cc.
-fn which_cc() -> tg::Result<PathBuf> {
+fn which_cc(config: &Config) -> tg::Result<PathBuf> {
 	let compiler_name = std::env::args().next().unwrap();
-	if let Ok(cc) = std::env::var("TGCC_COMPILER") {
+	if let Some(cc) = config.get("TGCC_COMPILER") {
 		return Ok(cc.into());
 	}
 	let path =
@@ -455,6 +453,7 @@ struct SourceTree {
 }
 
 // Convert a list of sources into a corresponding list of tg::Template.
+#[tracing::instrument(skip_all)]
 async fn create_remapping_table(
 	remap_targets: Vec<RemapTarget>,
 ) -> tg::Result<BTreeMap<RemapTarget, tg::Template>> {
@@ -474,7 +473,7 @@ async fn create_remapping_table(
 		}
 
 		// Check if this is a path that should be a template. Needs to happen after canonicalization in case a local symlink was created pointing to an artifact.
-		if path.starts_with("/.tangram/artifacts") || path.starts_with("/opt/tangram/artifacts") {
+		if common::is_artifact_path(path.to_str().unwrap()) {
 			let template = common::unrender(path.to_str().unwrap())?;
 			table.insert(remap_target, template);
 			continue;
@@ -528,6 +527,7 @@ fn insert_into_source_tree(
 }
 
 // Check in the source tree and return a list of templates that correspond to the files within it.
+#[tracing::instrument(skip_all)]
 async fn check_in_source_tree(subtree: SourceTree) -> tg::Result<Vec<(RemapTarget, tg::Template)>> {
 	// Directory builder to check in the directory at the end.
 	let mut builder = tg::directory::Builder::with_entries(BTreeMap::new());
@@ -612,13 +612,25 @@ async fn check_in_source_tree(subtree: SourceTree) -> tg::Result<Vec<(RemapTarge
 
 const DRIVER_SH: &str = include_str!("driver.sh");
 
-// Environment variables that must be filtered out before invoking the driver target.
-const BLACKLISTED_ENV_VARS: [&str; 5] = [
-	"TANGRAM_ADDRESS",
-	"TGCC_TRACING",
-	"TGCC_COMPILER",
-	"HOME",
-	"OUTPUT",
+// Environment variables that must be filtered out before invoking the driver target, in addition to tgcc's own settings.
+const BLACKLISTED_ENV_VARS: [&str; 3] = ["TANGRAM_ADDRESS", "HOME", "OUTPUT"];
+
+// The settings read from the config file, the environment and flags.
+const SETTINGS: &[Setting] = &[
+	Setting {
+		env: "TGCC_ENABLE",
+		flag: None,
+		kind: Kind::Bool,
+		default: Some("false"),
+		doc: "Run the compiler as a Tangram process. Otherwise, run the compiler directly.",
+	},
+	Setting {
+		env: "TGCC_COMPILER",
+		flag: None,
+		kind: Kind::String,
+		default: None,
+		doc: "The C compiler. Defaults to the second compiler with the same name in PATH.",
+	},
 ];
 
 // List of gcc options that take a value. This list **must** be comprehensive.
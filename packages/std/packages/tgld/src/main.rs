@@ -1,13 +1,18 @@
+use common::config::{Config, Kind, Setting};
 use futures::{StreamExt as _, TryStreamExt as _};
 use itertools::Itertools;
 use std::{
 	collections::{BTreeMap, HashMap, HashSet},
 	hash::BuildHasher,
+	io::Read as _,
 	path::PathBuf,
 	str::FromStr,
 };
 use tangram_client::prelude::*;
 use tokio::io::AsyncReadExt as _;
+use tracing::Instrument as _;
+
+mod script;
 
 type Hasher = fnv::FnvBuildHasher;
 
@@ -21,11 +26,14 @@ fn main() {
 }
 
 fn main_inner() -> tg::Result<()> {
+	// Read the config from the config file, the environment and arguments.
+	let config = Config::load("tgld", SETTINGS)?;
+
 	// Setup tracing.
-	common::tracing::setup("TGLD_TRACING");
+	config.setup_tracing();
 
-	// Read the options from the environment and arguments.
-	let options = read_options()?;
+	// Read the options from the config.
+	let options = read_options(&config)?;
 	tracing::debug!(?options);
 
 	tg::init()?;
@@ -73,7 +81,7 @@ struct Options {
 	/// The path to the command that will be invoked.
 	command_path: PathBuf,
 
-	/// The original arguments to the command.
+	/// The original arguments to the command, with any response files unexpanded.
 	command_args: Vec<String>,
 
 	/// If any NEEDED libraries are missing at the end, should we still produce a wrapper?. Will warn if false, error if true. Default: false.
@@ -106,251 +114,189 @@ struct Options {
 	/// Whether the linker should run in passthrough mode.
 	passthrough: bool,
 
+	/// The runtime library paths passed with `-rpath`.
+	rpaths: Vec<String>,
+
 	/// Additional argument values to set in the wrapper.
-	wrapper_arg_value: Option<Vec<tg::template::Data>>,
+	wrapper_arg_value: Option<Vec<tg::Template>>,
 
 	/// Additional environment variable values to set in the wrapper.
-	wrapper_env_value: Option<tg::mutation::Data>,
+	wrapper_env_value: Option<tg::Mutation>,
 }
 
-// Read the options from the environment and arguments.
-#[allow(clippy::too_many_lines)]
-fn read_options() -> tg::Result<Options> {
+// The settings read from the config file, the environment and flags.
+const SETTINGS: &[Setting] = &[
+	Setting {
+		env: "TGLD_COMMAND_PATH",
+		flag: None,
+		kind: Kind::String,
+		default: None,
+		doc: "The path to the linker that will be invoked. Required.",
+	},
+	Setting {
+		env: "TGLD_PASSTHROUGH",
+		flag: Some("--tg-passthrough"),
+		kind: Kind::Bool,
+		default: None,
+		doc: "Run the linker without creating a wrapper.",
+	},
+	Setting {
+		env: "TGLD_DISALLOW_MISSING",
+		flag: Some("--tg-disallow-missing"),
+		kind: Kind::Bool,
+		default: None,
+		doc: "Fail if any needed libraries are missing instead of warning.",
+	},
+	Setting {
+		env: "TGLD_ALLOW_MISSING_LIBRARIES",
+		flag: Some("--tg-allow-missing-libraries"),
+		kind: Kind::Bool,
+		default: None,
+		doc: "Warn if any needed libraries are missing, even if TGLD_DISALLOW_MISSING is set.",
+	},
+	Setting {
+		env: "TGLD_EMBED_WRAPPER",
+		flag: Some("--tg-embed-wrapper"),
+		kind: Kind::Bool,
+		default: None,
+		doc: "Embed the wrapper into the output executable.",
+	},
+	Setting {
+		env: "TGLD_INTERPRETER_PATH",
+		flag: None,
+		kind: Kind::String,
+		default: None,
+		doc: "The interpreter used by the output executable, or `none`.",
+	},
+	Setting {
+		env: "TGLD_INTERPRETER_ARGS",
+		flag: None,
+		kind: Kind::String,
+		default: None,
+		doc: "Whitespace-separated arguments to pass to the interpreter.",
+	},
+	Setting {
+		env: "TGLD_INJECTION_PATH",
+		flag: None,
+		kind: Kind::String,
+		default: None,
+		doc: "The path to the injection library.",
+	},
+	Setting {
+		env: "TGLD_LIBRARY_PATH_OPT_LEVEL",
+		flag: Some("--tg-library-path-opt-level"),
+		kind: Kind::Choice(&["none", "filter", "resolve", "isolate", "combine"]),
+		default: Some("isolate"),
+		doc: "The library path optimization strategy.",
+	},
+	Setting {
+		env: "TGLD_MAX_DEPTH",
+		flag: Some("--tg-max-depth"),
+		kind: Kind::Number,
+		default: Some("16"),
+		doc: "The maximum number of transitive library path searches to perform during optimization.",
+	},
+	Setting {
+		env: "TGLD_WRAPPER_ARG_VALUE",
+		flag: Some("--tangram-wrapper-arg-value"),
+		kind: Kind::String,
+		default: None,
+		doc: "An array of templates to set as the args of the wrapper.",
+	},
+	Setting {
+		env: "TGLD_WRAPPER_ARG_VALUE_PATH",
+		flag: Some("--tangram-wrapper-arg-value-path"),
+		kind: Kind::String,
+		default: None,
+		doc: "A file containing an array of templates to set as the args of the wrapper.",
+	},
+	Setting {
+		env: "TGLD_WRAPPER_ENV_VALUE",
+		flag: Some("--tangram-wrapper-env-value"),
+		kind: Kind::String,
+		default: None,
+		doc: "A mutation to set as the env of the wrapper.",
+	},
+	Setting {
+		env: "TGLD_WRAPPER_ENV_VALUE_PATH",
+		flag: Some("--tangram-wrapper-env-value-path"),
+		kind: Kind::String,
+		default: None,
+		doc: "A file containing a mutation to set as the env of the wrapper.",
+	},
+];
+
+// Read the options from the config.
+fn read_options(config: &Config) -> tg::Result<Options> {
 	// Create the output.
-	let mut command_args = Vec::new();
 	let mut output_path = None;
 	let mut library_paths = Vec::new();
+	let mut rpaths = Vec::new();
 
 	// Get the command.
-	let command_path = std::env::var("TGLD_COMMAND_PATH")
-		.map_err(|error| tg::error!(source = error, "TGLD_COMMAND_PATH must be set."))?
+	let command_path = config
+		.get("TGLD_COMMAND_PATH")
+		.ok_or_else(|| tg::error!("TGLD_COMMAND_PATH must be set."))?
 		.into();
 
-	// Get the passthrough flag.
-	let mut passthrough = std::env::var("TGLD_PASSTHROUGH").is_ok();
-
-	// Get the allow_missing flag.
-	let mut disallow_missing = std::env::var("TGLD_DISALLOW_MISSING").is_ok();
+	// Get the flags.
+	let passthrough = config.bool("TGLD_PASSTHROUGH");
+	let disallow_missing =
+		config.bool("TGLD_DISALLOW_MISSING") && !config.bool("TGLD_ALLOW_MISSING_LIBRARIES");
+	let embed = config.bool("TGLD_EMBED_WRAPPER");
 
-	// Get the interpreter path.
-	let interpreter_path = std::env::var("TGLD_INTERPRETER_PATH").ok();
-
-	// Get the wrap binary.
-	let mut embed = std::env::var("TGLD_EMBED_WRAPPER").is_ok();
-
-	// Get additional interpreter args, if any.
-	let interpreter_args = std::env::var("TGLD_INTERPRETER_ARGS").ok().map(|combined| {
+	// Get the interpreter.
+	let interpreter_path = config.get("TGLD_INTERPRETER_PATH").map(ToOwned::to_owned);
+	let interpreter_args = config.get("TGLD_INTERPRETER_ARGS").map(|combined| {
 		combined
 			.split_whitespace()
 			.map(std::string::ToString::to_string)
 			.collect_vec()
 	});
 
-	// Get the max depth.
-	let mut max_depth = std::env::var("TGLD_MAX_DEPTH")
-		.ok()
-		.map_or(MAX_DEPTH, |s| s.parse().unwrap_or(MAX_DEPTH));
-
 	// Get the injection path.
-	let injection_path = std::env::var("TGLD_INJECTION_PATH").ok();
-
-	// Get the option to disable combining library paths. Enabled by default.
-	let mut library_path_optimization = std::env::var("TGLD_LIBRARY_PATH_OPT_LEVEL")
-		.ok()
-		.map_or(LibraryPathStrategy::default(), |s| {
-			s.parse().unwrap_or_default()
-		});
-
-	// Get an iterator over the arguments.
-	let mut args = std::env::args();
-
-	// Skip arg0.
-	args.next();
+	let injection_path = config.get("TGLD_INJECTION_PATH").map(ToOwned::to_owned);
+
+	// Get the library path optimization options.
+	let library_path_strategy = config.get("TGLD_LIBRARY_PATH_OPT_LEVEL").map_or(
+		Ok(LibraryPathStrategy::default()),
+		LibraryPathStrategy::from_str,
+	)?;
+	let max_depth = config.number("TGLD_MAX_DEPTH").unwrap_or(MAX_DEPTH);
+
+	// Get the wrapper arg values.
+	let wrapper_arg_value = wrapper_value(config, "TGLD_WRAPPER_ARG_VALUE")?
+		.map(|value| {
+			value
+				.try_unwrap_array()
+				.map_err(|_| tg::error!("expected an array"))?
+				.into_iter()
+				.map(|v| {
+					v.try_unwrap_template()
+						.map_err(|_| tg::error!("expected a template"))
+				})
+				.collect::<tg::Result<Vec<_>>>()
+		})
+		.transpose()?;
+
+	// Get the wrapper env values.
+	let wrapper_env_value = wrapper_value(config, "TGLD_WRAPPER_ENV_VALUE")?
+		.map(|value| {
+			value
+				.try_unwrap_mutation()
+				.map_err(|_| tg::error!("expected a mutation"))
+		})
+		.transpose()?;
 
 	// Prepare to store dynamic libraries passed directly to the linker.
-	let mut additional_library_candidate_paths = Vec::new();
-
-	// Prepare to store wrapper arg values.
-	let mut wrapper_arg_value = if let Ok(path) = std::env::var("TGLD_WRAPPER_ARG_VALUE_PATH") {
-		let value = std::fs::read_to_string(&path)
-			.map_err(|error| tg::error!(!error, "failed to read TGLD_WRAPPER_ARG_VALUE_PATH"))?
-			.parse::<tg::Value>()
-			.map_err(|error| tg::error!(!error, "failed to parse wrapper arg value"))?;
-		let data = value
-			.to_data()
-			.try_unwrap_array()
-			.map_err(|_| tg::error!("expected an array"))?
-			.into_iter()
-			.map(|v| {
-				v.try_unwrap_template()
-					.map_err(|_| tg::error!("expected a template"))
-			})
-			.collect::<tg::Result<Vec<_>>>()?;
-		Some(data)
-	} else {
-		None
-	};
-
-	// Prepare to store wrapper env values.
-	let mut wrapper_env_value = if let Ok(path) = std::env::var("TGLD_WRAPPER_ENV_VALUE_PATH") {
-		let value = std::fs::read_to_string(&path)
-			.map_err(|error| tg::error!(!error, "failed to read TGLD_WRAPPER_ENV_VALUE_PATH"))?
-			.parse::<tg::Value>()
-			.map_err(|error| tg::error!(!error, "failed to parse wrapper env value"))?;
-		let data = value
-			.try_unwrap_mutation()
-			.map_err(|_| tg::error!("expected a mutation"))?
-			.to_data();
-		Some(data)
-	} else {
-		None
-	};
+	let mut library_candidates = Vec::new();
 
-	// Handle the arguments.
+	// Handle the arguments that are not tgld's, with response files expanded.
+	let mut args = config.args.iter().cloned();
 	while let Some(arg) = args.next() {
-		// Pass through any arg that isn't a tangram arg.
-		if arg.starts_with("--tg-") || arg.starts_with("--tangram-") {
-			// Handle setting combined library paths. Will override the env var if set.
-			if arg.starts_with("--tg-library-path-opt-level=") {
-				let option = arg.strip_prefix("--tg-library-path-opt-level=").unwrap();
-				library_path_optimization =
-					LibraryPathStrategy::from_str(option).unwrap_or_default();
-			} else if arg.starts_with("--tg-max-depth=") {
-				let option = arg.strip_prefix("--tg-max-depth=").unwrap();
-				if let Ok(max_depth_arg) = option.parse() {
-					max_depth = max_depth_arg;
-				} else {
-					tracing::warn!("Invalid max depth argument {option}. Using default.");
-				}
-			} else if arg.starts_with("--tg-passthrough") {
-				passthrough = true;
-			} else if arg.starts_with("--tg-disallow-missing") {
-				disallow_missing = true;
-			} else if arg.starts_with("--tg-embed-wrapper") {
-				embed = true;
-			} else if let Some(value) = arg.strip_prefix("--tangram-wrapper-arg-value=") {
-				let value = value
-					.parse::<tg::Value>()
-					.map_err(|error| tg::error!(!error, "failed to parse wrapper arg value"))?;
-				let data = value
-					.to_data()
-					.try_unwrap_array()
-					.map_err(|_| tg::error!("expected an array"))?
-					.into_iter()
-					.map(|v| {
-						v.try_unwrap_template()
-							.map_err(|_| tg::error!("expected a template"))
-					})
-					.collect::<tg::Result<Vec<_>>>()?;
-				wrapper_arg_value.replace(data);
-			} else if arg == "--tangram-wrapper-arg-value" {
-				if let Some(value) = args.next() {
-					let value = value
-						.parse::<tg::Value>()
-						.map_err(|error| tg::error!(!error, "failed to parse wrapper arg value"))?;
-					let data = value
-						.to_data()
-						.try_unwrap_array()
-						.map_err(|_| tg::error!("expected an array"))?
-						.into_iter()
-						.map(|v| {
-							v.try_unwrap_template()
-								.map_err(|_| tg::error!("expected a template"))
-						})
-						.collect::<tg::Result<Vec<_>>>()?;
-					wrapper_arg_value.replace(data);
-				}
-			} else if let Some(path) = arg.strip_prefix("--tangram-wrapper-arg-value-path=") {
-				let value = std::fs::read_to_string(path)
-					.map_err(|error| tg::error!(!error, "failed to read wrapper arg value path"))?
-					.parse::<tg::Value>()
-					.map_err(|error| tg::error!(!error, "failed to parse wrapper arg value"))?;
-				let data = value
-					.to_data()
-					.try_unwrap_array()
-					.map_err(|_| tg::error!("expected an array"))?
-					.into_iter()
-					.map(|v| {
-						v.try_unwrap_template()
-							.map_err(|_| tg::error!("expected a template"))
-					})
-					.collect::<tg::Result<Vec<_>>>()?;
-				wrapper_arg_value.replace(data);
-			} else if arg == "--tangram-wrapper-arg-value-path" {
-				if let Some(path) = args.next() {
-					let value = std::fs::read_to_string(&path)
-						.map_err(|error| {
-							tg::error!(!error, "failed to read wrapper arg value path")
-						})?
-						.parse::<tg::Value>()
-						.map_err(|error| tg::error!(!error, "failed to parse wrapper arg value"))?;
-					let data = value
-						.to_data()
-						.try_unwrap_array()
-						.map_err(|_| tg::error!("expected an array"))?
-						.into_iter()
-						.map(|v| {
-							v.try_unwrap_template()
-								.map_err(|_| tg::error!("expected a template"))
-						})
-						.collect::<tg::Result<Vec<_>>>()?;
-					wrapper_arg_value.replace(data);
-				}
-			} else if let Some(value) = arg.strip_prefix("--tangram-wrapper-env-value=") {
-				let value = value
-					.parse::<tg::Value>()
-					.map_err(|error| tg::error!(!error, "failed to parse wrapper env value"))?;
-				let data = value
-					.try_unwrap_mutation()
-					.map_err(|_| tg::error!("expected a mutation"))?
-					.to_data();
-				wrapper_env_value.replace(data);
-			} else if arg == "--tangram-wrapper-env-value" {
-				if let Some(value) = args.next() {
-					let value = value
-						.parse::<tg::Value>()
-						.map_err(|error| tg::error!(!error, "failed to parse wrapper env value"))?;
-					let data = value
-						.try_unwrap_mutation()
-						.map_err(|_| tg::error!("expected a mutation"))?
-						.to_data();
-					wrapper_env_value.replace(data);
-				}
-			} else if let Some(path) = arg.strip_prefix("--tangram-wrapper-env-value-path=") {
-				let value = std::fs::read_to_string(path)
-					.map_err(|error| tg::error!(!error, "failed to read wrapper env value path"))?
-					.parse::<tg::Value>()
-					.map_err(|error| tg::error!(!error, "failed to parse wrapper env value"))?;
-				let data = value
-					.try_unwrap_mutation()
-					.map_err(|_| tg::error!("expected a mutation"))?
-					.to_data();
-				wrapper_env_value.replace(data);
-			} else if arg == "--tangram-wrapper-env-value-path" {
-				if let Some(path) = args.next() {
-					let value = std::fs::read_to_string(&path)
-						.map_err(|error| {
-							tg::error!(!error, "failed to read wrapper env value path")
-						})?
-						.parse::<tg::Value>()
-						.map_err(|error| tg::error!(!error, "failed to parse wrapper env value"))?;
-					let data = value
-						.try_unwrap_mutation()
-						.map_err(|_| tg::error!("expected a mutation"))?
-						.to_data();
-					wrapper_env_value.replace(data);
-				}
-			} else {
-				command_args.push(arg.clone());
-			}
-		} else {
-			command_args.push(arg.clone());
-		}
-
 		// Handle the output path argument.
 		if arg == "-o" || arg == "--output" {
 			if let Some(path) = args.next() {
-				command_args.push(path.clone());
 				output_path = path.into();
 			}
 		} else if let Some(output_arg) = arg.strip_prefix("-o") {
@@ -368,6 +314,12 @@ fn read_options() -> tg::Result<Options> {
 			library_paths.push(library_arg.to_owned());
 		} else if let Some(library_path) = arg.strip_prefix("-L") {
 			library_paths.push(library_path.to_owned());
+		} else if arg == "-rpath" {
+			if let Some(rpath) = args.next() {
+				rpaths.extend(rpath.split(':').filter(|p| !p.is_empty()).map(String::from));
+			}
+		} else if let Some(rpath) = arg.strip_prefix("-rpath=") {
+			rpaths.extend(rpath.split(':').filter(|p| !p.is_empty()).map(String::from));
 		} else if let Some(wl_args) = arg.strip_prefix("-Wl,") {
 			// Handle -Wl,-L,/path and -Wl,-rpath-link,/path.
 			let parts: Vec<&str> = wl_args.split(',').collect();
@@ -379,6 +331,13 @@ fn read_options() -> tg::Result<Options> {
 							.filter(|p| !p.is_empty())
 							.map(String::from),
 					);
+				} else if window[0] == "-rpath" {
+					rpaths.extend(
+						window[1]
+							.split(':')
+							.filter(|p| !p.is_empty())
+							.map(String::from),
+					);
 				}
 			}
 			// Handle -Wl,-L=/path and -Wl,-rpath-link=/path forms.
@@ -389,36 +348,43 @@ fn read_options() -> tg::Result<Options> {
 				{
 					library_paths
 						.extend(path.split(':').filter(|p| !p.is_empty()).map(String::from));
+				} else if let Some(path) = part.strip_prefix("-rpath=") {
+					rpaths.extend(path.split(':').filter(|p| !p.is_empty()).map(String::from));
 				}
 			}
 		}
 
 		// Add any dynamic libraries passed directly to the linker.
 		if is_library_candidate(&arg) {
-			// If the path can't be canonicalized, do nothing - it's not a valid library candidate.
-			if let Ok(canonical_path) = std::fs::canonicalize(&arg) {
-				additional_library_candidate_paths.push(canonical_path);
-			}
+			library_candidates.push(PathBuf::from(&arg));
 		}
 	}
 
+	// Follow any linker scripts and libtool archives to the shared objects they name.
+	let search_paths = library_paths.iter().map(PathBuf::from).collect_vec();
+	let additional_library_candidate_paths = library_candidates
+		.iter()
+		.flat_map(|candidate| follow_library_candidate(candidate, &search_paths))
+		.collect_vec();
+
 	// If no explicit output path was provided, instead look for `a.out`.
 	let output_path = output_path.as_deref().unwrap_or("a.out").into();
 
 	let options = Options {
 		additional_library_candidate_paths,
 		command_path,
-		command_args,
+		command_args: config.passthrough_args.clone(),
 		disallow_missing,
 		embed,
 		interpreter_path,
 		interpreter_args,
 		injection_path,
-		library_path_strategy: library_path_optimization,
+		library_path_strategy,
 		library_paths,
 		max_depth,
 		output_path,
 		passthrough,
+		rpaths,
 		wrapper_arg_value,
 		wrapper_env_value,
 	};
@@ -426,6 +392,24 @@ fn read_options() -> tg::Result<Options> {
 	Ok(options)
 }
 
+/// Read a wrapper value given inline by the setting `env` or in the file given by `{env}_PATH`, whichever has the higher precedence.
+fn wrapper_value(config: &Config, env: &str) -> tg::Result<Option<tg::Value>> {
+	let path_env = format!("{env}_PATH");
+	let string = if config.source(&path_env) > config.source(env) {
+		let path = config.get(&path_env).unwrap();
+		std::fs::read_to_string(path)
+			.map_err(|error| tg::error!(!error, path = %path, "failed to read {path_env}"))?
+	} else if let Some(value) = config.get(env) {
+		value.to_owned()
+	} else {
+		return Ok(None);
+	};
+	let value = string
+		.parse::<tg::Value>()
+		.map_err(|error| tg::error!(!error, "failed to parse {env}"))?;
+	Ok(Some(value))
+}
+
 #[allow(clippy::too_many_lines)]
 async fn create_wrapper(options: &Options) -> tg::Result<()> {
 	// Analyze the output file.
@@ -435,6 +419,8 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 		name,
 		needed_libraries: initial_needed_libraries,
 		entrypoint,
+		rpath,
+		runpath,
 	} = analyze_output_file(&options.output_path).await?;
 	tracing::debug!(?is_executable, ?interpreter, ?initial_needed_libraries);
 
@@ -472,58 +458,20 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 	.await?;
 
 	// Unrender all library paths to symlinks. If any library path points into the working directory, check in its contents.
+	let search_paths = options
+		.library_paths
+		.iter()
+		.map(PathBuf::from)
+		.collect_vec();
 	let library_paths = command_line_library_path
 		.into_iter()
 		.chain(
-			futures::future::try_join_all(options.library_paths.iter().map(|library_path| async {
-				let symlink = common::template_to_symlink(&common::unrender(library_path)?)?;
-				let artifact = symlink.artifact().await?;
-				let path = symlink.path().await?;
-				let artifact_path = match (artifact, path) {
-					(Some(artifact), path) => {
-						tracing::debug!(?artifact, ?path, "checking for entries");
-						if let Ok(directory) = artifact.try_unwrap_directory() {
-							let entries = if let Some(ref subpath) = path {
-								if let Ok(subdirectory) =
-									directory.get(&subpath).await?.try_unwrap_directory()
-								{
-									subdirectory.entries().await?
-								} else {
-									BTreeMap::default()
-								}
-							} else {
-								directory.entries().await?
-							};
-							if entries.is_empty() {
-								None
-							} else {
-								tracing::debug!(?path, "found a directory with entries");
-								let dir_with_subpath =
-									dir_with_subpath_from_directory(&directory, path).await?;
-								Some(dir_with_subpath)
-							}
-						} else {
-							None
-						}
-					},
-					(None, Some(path)) => {
-						tracing::debug!(
-							"Library path points into working directory: {:?}. Creating directory.",
-							path
-						);
-						if let Ok(ref canonicalized_path) = std::fs::canonicalize(&path) {
-							checkin_local_library_path(canonicalized_path).await?
-						} else {
-							tracing::warn!(
-								"Could not canonicalize library path {path:?}. Skipping."
-							);
-							None
-						}
-					},
-					(None, None) => None,
-				};
-				Ok::<_, tg::Error>(artifact_path)
-			}))
+			futures::future::try_join_all(
+				options
+					.library_paths
+					.iter()
+					.map(|library_path| resolve_library_path(library_path, &search_paths)),
+			)
 			.await?
 			.into_iter()
 			.flatten(),
@@ -531,6 +479,25 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 		.collect_vec();
 	tracing::debug!(?library_paths, "Library paths");
 
+	// Resolve the runpaths of the output file and the -rpath arguments relative to the output's directory.
+	let origin = std::fs::canonicalize(&options.output_path)
+		.ok()
+		.and_then(|path| path.parent().map(ToOwned::to_owned))
+		.unwrap_or_default();
+	let runpaths = Runpaths {
+		rpath: resolve_output_runpaths(&rpath, &origin, &search_paths).await,
+		runpath: resolve_output_runpaths(
+			&runpath
+				.into_iter()
+				.chain(options.rpaths.iter().cloned())
+				.collect_vec(),
+			&origin,
+			&search_paths,
+		)
+		.await,
+	};
+	tracing::debug!(?runpaths, "Runpaths");
+
 	// Obtain the file artifact from the output path.
 	let (output_file, original_permissions) = {
 		// Store the original file permissions before check in.
@@ -541,6 +508,7 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 		let original_permissions = original_metadata.permissions();
 
 		tracing::debug!(?output_path, "about to check in output file");
+		let span = tracing::info_span!("checkin", ?output_path);
 		let output_file = tg::checkin(tg::checkin::Arg {
 			options: tg::checkin::Options {
 				destructive: false,
@@ -555,6 +523,7 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 			path: output_path,
 			updates: vec![],
 		})
+		.instrument(span)
 		.await?
 		.try_unwrap_file()
 		.map_err(|error| tg::error!(source = error, "expected a file"))?;
@@ -564,7 +533,7 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 	let output_file_id = output_file.id();
 	tracing::debug!(?output_file_id, "checked in output file");
 
-	let library_paths = if library_paths.is_empty() {
+	let library_paths = if library_paths.is_empty() && runpaths.is_empty() {
 		None
 	} else {
 		let library_paths: HashSet<DirectoryWithSubpath, Hasher> =
@@ -579,7 +548,7 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 		);
 
 		let library_paths = optimize_library_paths(
-			&output_file,
+			&runpaths,
 			library_paths,
 			&mut needed_libraries,
 			options.library_path_strategy,
@@ -685,9 +654,133 @@ async fn create_wrapper(options: &Options) -> tg::Result<()> {
 	Ok(())
 }
 
-/// Check in any files needed libraries and produce a directory with correct names.
+/// Resolve a library path to a directory. Paths into artifacts are used directly, and paths into the working directory are checked in, searching `library_paths` for the `-l` inputs of any linker scripts.
+async fn resolve_library_path(
+	library_path: &str,
+	library_paths: &[PathBuf],
+) -> tg::Result<Option<DirectoryWithSubpath>> {
+	let symlink = common::template_to_symlink(&common::unrender(library_path)?)?;
+	let artifact = symlink.artifact().await?;
+	let path = symlink.path().await?;
+	let artifact_path = match (artifact, path) {
+		(Some(artifact), path) => {
+			tracing::debug!(?artifact, ?path, "checking for entries");
+			if let Ok(directory) = artifact.try_unwrap_directory() {
+				let entries = if let Some(ref subpath) = path {
+					if let Ok(subdirectory) = directory.get(&subpath).await?.try_unwrap_directory()
+					{
+						subdirectory.entries().await?
+					} else {
+						BTreeMap::default()
+					}
+				} else {
+					directory.entries().await?
+				};
+				if entries.is_empty() {
+					None
+				} else {
+					tracing::debug!(?path, "found a directory with entries");
+					let dir_with_subpath =
+						dir_with_subpath_from_directory(&directory, path).await?;
+					Some(dir_with_subpath)
+				}
+			} else {
+				None
+			}
+		},
+		(None, Some(path)) => {
+			tracing::debug!(
+				"Library path points into working directory: {:?}. Creating directory.",
+				path
+			);
+			if let Ok(ref canonicalized_path) = std::fs::canonicalize(&path) {
+				checkin_local_library_path(canonicalized_path, library_paths).await?
+			} else {
+				tracing::warn!("Could not canonicalize library path {path:?}. Skipping.");
+				None
+			}
+		},
+		(None, None) => None,
+	};
+	Ok(artifact_path)
+}
+
+/// Get the file for a library. A path into an artifact is read from the artifact, and any other path is checked in.
+async fn library_file(path: &std::path::Path) -> tg::Result<tg::File> {
+	let path_str = path
+		.to_str()
+		.ok_or_else(|| tg::error!(path = %path.display(), "unable to convert path to str"))?;
+	let file = if common::is_artifact_path(path_str) {
+		tracing::trace!("found an artifact, extracting file object");
+		let template = common::unrender(path_str)?;
+		tracing::trace!(?template, "unrendered library candidate path");
+		// Obtain the file object from the artifact.
+		// We expect to underender one of two forms:
+		// - A single file artifact.
+		// - Two components, a directory artifact and string subpath.
+		match template.components() {
+			[tg::template::Component::Artifact(artifact)] => artifact
+				.clone()
+				.try_unwrap_file()
+				.map_err(|error| tg::error!(!error, "expected a file"))?,
+			[
+				tg::template::Component::Artifact(artifact),
+				tg::template::Component::String(subpath),
+			] => {
+				let d = artifact
+					.clone()
+					.try_unwrap_directory()
+					.map_err(|error| tg::error!(!error, "expected a directory"))?;
+				if let Some(inner) = d
+					.try_get(subpath.strip_prefix('/').unwrap_or(subpath))
+					.await?
+				{
+					inner
+						.try_unwrap_file()
+						.map_err(|error| tg::error!(!error, "expected a file"))?
+				} else {
+					let artifact = artifact.id();
+					return Err(
+						tg::error!(%artifact, %subpath, "failed to get inner artifact at subpath"),
+					);
+				}
+			},
+			_ => {
+				return Err(tg::error!(
+					?template,
+					"expected a template with one artifact and zero or one string components"
+				));
+			},
+		}
+	} else {
+		tracing::trace!("found a path in the current build temp, checking in");
+		// The file is located in our own build directory. Check it in.
+		tg::checkin(tg::checkin::Arg {
+			options: tg::checkin::Options {
+				destructive: false,
+				deterministic: true,
+				ignore: false,
+				source_dependencies: true,
+				locked: true,
+				lock: None,
+				root: true,
+				..tg::checkin::Options::default()
+			},
+			path: path.to_owned(),
+			updates: vec![],
+		})
+		.await?
+		.try_unwrap_file()
+		.map_err(|error| tg::error!(source = error, "expected a file"))?
+	};
+	Ok(file)
+}
+
+/// Check in any files needed libraries and produce a directory with correct names. Linker scripts are followed, searching all of `library_paths` for their `-l` inputs.
+#[tracing::instrument(skip_all)]
 async fn checkin_local_library_path(
 	library_path: &impl AsRef<std::path::Path>,
+	library_paths: &[PathBuf],
 ) -> tg::Result<Option<DirectoryWithSubpath>> {
 	let library_path = library_path.as_ref();
 	tracing::debug!(?library_path, "Checking in local library path");
@@ -714,41 +807,42 @@ async fn checkin_local_library_path(
 				return Ok(None);
 			}
 
-			if let Ok(AnalyzeOutputFileOutput {
-				name: Some(name), ..
-			}) = analyze_output_file(&library_candidate_path).await
-			{
+			// Follow linker scripts and libtool archives to the shared objects they name.
+			let library_candidates = match analyze_output_file(&library_candidate_path).await {
+				Ok(output) => vec![(library_candidate_path, output)],
+				Err(_) => {
+					let mut library_candidates = Vec::new();
+					for path in follow_library_candidate(&library_candidate_path, library_paths) {
+						if let Ok(output) = analyze_output_file(&path).await {
+							library_candidates.push((path, output));
+						}
+					}
+					library_candidates
+				},
+			};
+
+			let mut entries = Vec::new();
+			for (library_candidate_path, output) in library_candidates {
+				let Some(name) = output.name else {
+					continue;
+				};
 				tracing::debug!(?name, "Found library candidate.");
 				// Check in the file.
-				let library_candidate_file = tg::checkin(tg::checkin::Arg {
-					options: tg::checkin::Options {
-						destructive: false,
-						deterministic: true,
-						ignore: false,
-						source_dependencies: true,
-						locked: true,
-						lock: None,
-						root: true,
-						..tg::checkin::Options::default()
-					},
-					path: library_candidate_path,
-					updates: vec![],
-				})
-				.await?
-				.try_unwrap_file()
-				.map_err(|error| tg::error!(source = error, "expected a file"))?;
+				let library_candidate_file = library_file(&library_candidate_path).await?;
 
 				// Add an entry to the directory.
 				let id = library_candidate_file.id();
 				tracing::info!(?name, ?id, "Checked in library candidate.");
-				Ok::<_, tg::Error>(Some((name, tg::Artifact::File(library_candidate_file))))
-			} else {
-				Ok(None)
+				entries.push((name, tg::Artifact::File(library_candidate_file)));
 			}
+			Ok::<_, tg::Error>(Some(entries))
 		})
 		.filter_map(|result| async { result.await.transpose() })
-		.try_collect::<BTreeMap<_, _>>()
-		.await?;
+		.try_collect::<Vec<_>>()
+		.await?
+		.into_iter()
+		.flatten()
+		.collect::<BTreeMap<_, _>>();
 
 	let result = if entries.is_empty() {
 		None
@@ -769,120 +863,78 @@ fn extract_filename(path: &(impl AsRef<str> + ToString + ?Sized)) -> String {
 }
 
 /// Create a manifest.
-#[allow(clippy::too_many_lines)]
 async fn create_manifest<H: BuildHasher>(
 	ld_output_id: tg::artifact::Id,
 	options: &Options,
 	interpreter: InterpreterRequirement,
 	library_paths: Option<HashSet<DirectoryWithSubpath, H>>,
 ) -> tg::Result<common::Manifest> {
+	// Create the executable.
+	let mut builder = common::ManifestBuilder::with_artifact(tg::Artifact::with_id(ld_output_id));
+
 	// Create the interpreter.
-	let interpreter = {
-		let config = match interpreter {
-			InterpreterRequirement::Default(flavor) => {
-				let path = options
-					.interpreter_path
-					.as_ref()
-					.expect("TGLD_INTERPRETER_PATH must be set.");
-
-				Some((path.clone(), flavor))
-			},
-			InterpreterRequirement::Path(path) => {
-				let interpreter_flavor = determine_interpreter_flavor(&path).await?;
+	let config = match interpreter {
+		InterpreterRequirement::Default(flavor) => {
+			let path = options
+				.interpreter_path
+				.as_ref()
+				.expect("TGLD_INTERPRETER_PATH must be set.");
+
+			Some((path.clone(), flavor))
+		},
+		InterpreterRequirement::Path(path) => {
+			let interpreter_flavor = determine_interpreter_flavor(&path).await?;
 
-				Some((path, interpreter_flavor))
-			},
-			InterpreterRequirement::None => None,
+			Some((path, interpreter_flavor))
+		},
+		InterpreterRequirement::None => None,
+	};
+	tracing::trace!(?config, "Interpreter configuration");
+
+	// There is no interpreter for a statically linked executable.
+	if let Some((path, interpreter_flavor)) = config {
+		// Unrender the interpreter path.
+		let path = common::unrender(&path)?;
+		builder = match interpreter_flavor {
+			InterpreterFlavor::Dyld => builder.dyld(),
+			InterpreterFlavor::Gnu => builder.ld_linux(&path),
+			InterpreterFlavor::Musl => builder.ld_musl(&path),
 		};
-		tracing::trace!(?config, "Interpreter configuration");
 
 		// Render the library paths.
-		let library_paths = if let Some(library_paths) = library_paths {
-			let result = futures::future::try_join_all(library_paths.into_iter().map(
-				|dir_with_subpath| async move {
-					let directory = tg::Directory::with_id(dir_with_subpath.id);
-					let template = if let Some(subpath) = dir_with_subpath.subpath {
-						common::template_from_artifact_and_subpath(directory.into(), subpath)
-					} else {
-						common::template_from_artifact(directory.into())
-					};
-					let data = template.to_data();
-					Ok::<_, tg::Error>(data)
-				},
-			))
-			.await?;
-			Some(result)
-		} else {
-			None
-		};
-		tracing::trace!(?library_paths, "Library paths");
-
-		if let Some((path, interpreter_flavor)) = config {
-			// Unrender the interpreter path.
-			let path = common::unrender(&path)?;
-			let path = path.to_data();
-
-			// Unrender the preloads.
-			let mut preloads = None;
-			if let Some(injection_path) = options.injection_path.as_deref() {
-				preloads = Some(vec![common::unrender(injection_path)?.to_data()]);
-			}
-
-			// Unrender the additional args.
-			let mut args = None;
-			if let Some(interpreter_args) = options.interpreter_args.as_deref() {
-				let interpreter_args = interpreter_args
-					.iter()
-					.map(|arg| async move { Ok::<_, tg::Error>(common::unrender(arg)?.to_data()) });
-				args = Some(futures::future::try_join_all(interpreter_args).await?);
-			}
+		for dir_with_subpath in library_paths.into_iter().flatten() {
+			let directory = tg::Directory::with_id(dir_with_subpath.id);
+			let template = if let Some(subpath) = dir_with_subpath.subpath {
+				common::template_from_artifact_and_subpath(directory.into(), subpath)
+			} else {
+				common::template_from_artifact(directory.into())
+			};
+			builder = builder.library_path(&template);
+		}
 
-			match interpreter_flavor {
-				InterpreterFlavor::Dyld => Some(common::manifest::Interpreter::DyLd(
-					common::manifest::DyLdInterpreter {
-						library_paths,
-						preloads,
-					},
-				)),
-				InterpreterFlavor::Gnu => Some(common::manifest::Interpreter::LdLinux(
-					common::manifest::LdLinuxInterpreter {
-						path,
-						library_paths,
-						preloads,
-						args,
-					},
-				)),
-				InterpreterFlavor::Musl => Some(common::manifest::Interpreter::LdMusl(
-					common::manifest::LdMuslInterpreter {
-						path,
-						library_paths,
-						preloads,
-						args,
-					},
-				)),
-			}
-		} else {
-			// There was no interpreter specified. We are likely a statically linked executable.
-			None
+		// Unrender the preloads.
+		if let Some(injection_path) = options.injection_path.as_deref() {
+			builder = builder.preload(&common::unrender(injection_path)?);
 		}
-	};
 
-	// Create the executable.
-	let executable = common::manifest::Executable::Path(
-		common::template_from_artifact(tg::Artifact::with_id(ld_output_id)).to_data(),
-	);
+		// Unrender the additional args.
+		for arg in options.interpreter_args.iter().flatten() {
+			builder = builder.interpreter_arg(&common::unrender(arg)?);
+		}
+	}
 
-	// Create empty values for env and args.
-	let env = options.wrapper_env_value.clone();
-	let args = options.wrapper_arg_value.clone();
+	// Set the env and args.
+	if let Some(env) = &options.wrapper_env_value {
+		builder = builder.env(env);
+	}
+	if let Some(args) = &options.wrapper_arg_value {
+		builder = builder.args(args);
+	}
 
 	// Create the manifest.
-	let manifest = common::Manifest {
-		interpreter,
-		executable,
-		env,
-		args,
-	};
+	let manifest = builder
+		.build()
+		.map_err(|error| tg::error!(source = error, "invalid manifest"))?;
 
 	Ok(manifest)
 }
@@ -898,6 +950,10 @@ struct AnalyzeOutputFileOutput {
 	needed_libraries: Vec<String>,
 	/// The entrypoint of the executable.
 	entrypoint: Option<u64>,
+	/// The `DT_RPATH` entries, which the dynamic loader ignores if there are `DT_RUNPATH` entries.
+	rpath: Vec<String>,
+	/// The `DT_RUNPATH` entries.
+	runpath: Vec<String>,
 }
 
 /// The possible interpreter requirements of an output file.
@@ -960,73 +1016,7 @@ async fn create_library_directory_for_command_line_libraries<H: BuildHasher>(
 				);
 
 				// Obtain the file object.
-				let library_candidate_path_str = library_candidate_path.to_str().ok_or_else(
-					|| tg::error!(path = %library_candidate_path.display(), "unable to convert path to str"),
-				)?;
-				let library_candidate_file = if common::is_artifact_path(library_candidate_path_str)
-				{
-					tracing::trace!("found an artifact, extracting file object");
-					let template = common::unrender(library_candidate_path_str)?;
-					tracing::trace!(?template, "unrendered library candidate path");
-					// Obtain the file object from the artifact.
-					// We expect to underender one of two forms:
-					// - A single file artifact.
-					// - Two components, a directory artifact and string subpath.
-					match template.components() {
-						[tg::template::Component::Artifact(artifact)] => artifact
-							.clone()
-							.try_unwrap_file()
-							.map_err(|error| tg::error!(!error, "expected a file"))?,
-						[
-							tg::template::Component::Artifact(artifact),
-							tg::template::Component::String(subpath),
-						] => {
-							let d = artifact
-								.clone()
-								.try_unwrap_directory()
-								.map_err(|error| tg::error!(!error, "expected a directory"))?;
-							if let Some(inner) = d
-								.try_get(subpath.strip_prefix('/').unwrap_or(subpath))
-								.await?
-							{
-								inner
-									.try_unwrap_file()
-									.map_err(|error| tg::error!(!error, "expected a file"))?
-							} else {
-								let artifact = artifact.id();
-								return Err(
-									tg::error!(%artifact, %subpath, "failed to get inner artifact at subpath"),
-								);
-							}
-						},
-						_ => {
-							return Err(tg::error!(
-								?template,
-								"expected a template with one artifact and zero or one string components"
-							));
-						},
-					}
-				} else {
-					tracing::trace!("found a path in the current build temp, checking in");
-					// The file is located in our own build directory. Check it in.
-					tg::checkin(tg::checkin::Arg {
-						options: tg::checkin::Options {
-							destructive: false,
-							deterministic: true,
-							ignore: false,
-							source_dependencies: true,
-							locked: true,
-							lock: None,
-							root: true,
-							..tg::checkin::Options::default()
-						},
-						path: library_candidate_path.clone(),
-						updates: vec![],
-					})
-					.await?
-					.try_unwrap_file()
-					.map_err(|error| tg::error!(source = error, "expected a file"))?
-				};
+				let library_candidate_file = library_file(library_candidate_path).await?;
 
 				// Add an entry to the directory.
 				entries.insert(name, tg::Artifact::File(library_candidate_file));
@@ -1058,27 +1048,185 @@ fn is_library_candidate(arg: &str) -> bool {
 	} else {
 		unreachable!();
 	};
-	arg.contains(dylib_ext)
+	arg.contains(dylib_ext) || arg.ends_with(".la")
+}
+
+/// Follow linker scripts and libtool archives to the shared objects they name, searching `library_paths` for `-l` inputs. Any other file is returned as is. Paths that do not exist are dropped.
+fn follow_library_candidate(path: &std::path::Path, library_paths: &[PathBuf]) -> Vec<PathBuf> {
+	let mut visited = HashSet::new();
+	let mut found = Vec::new();
+	follow_library_candidate_inner(path, library_paths, &mut visited, &mut found);
+	found
+}
+
+fn follow_library_candidate_inner(
+	path: &std::path::Path,
+	library_paths: &[PathBuf],
+	visited: &mut HashSet<PathBuf>,
+	found: &mut Vec<PathBuf>,
+) {
+	let Ok(path) = std::fs::canonicalize(path) else {
+		return;
+	};
+	if !visited.insert(path.clone()) {
+		return;
+	}
+	let directory = path.parent().unwrap_or(std::path::Path::new("/"));
+	let contents = read_text(&path);
+
+	// Follow a libtool archive to its shared object and dependencies.
+	if let Some(archive) = contents.as_deref().and_then(script::parse_libtool_archive) {
+		tracing::debug!(?path, ?archive, "Following libtool archive.");
+		let mut library_paths = library_paths.to_vec();
+		for input in &archive.dependency_libs {
+			if let script::Input::LibraryPath(library_path) = input {
+				library_paths.push(library_path.into());
+			}
+		}
+		if let Some(dlname) = &archive.dlname {
+			// An uninstalled library is in `.libs`, and an installed one is in the libdir or the library paths.
+			let shared_object = [directory.join(dlname), directory.join(".libs").join(dlname)]
+				.into_iter()
+				.chain(archive.libdir.iter().filter_map(|libdir| {
+					resolve_absolute_input(&std::path::Path::new(libdir).join(dlname), directory)
+				}))
+				.chain(
+					library_paths
+						.iter()
+						.map(|library_path| library_path.join(dlname)),
+				)
+				.find(|candidate| candidate.is_file());
+			if let Some(shared_object) = shared_object {
+				follow_library_candidate_inner(&shared_object, &library_paths, visited, found);
+			}
+		}
+		for input in &archive.dependency_libs {
+			if let Some(input) = find_script_input(input, directory, &library_paths) {
+				follow_library_candidate_inner(&input, &library_paths, visited, found);
+			}
+		}
+		return;
+	}
+
+	// Follow the inputs of a linker script.
+	if let Some(inputs) = contents.as_deref().and_then(script::parse_linker_script) {
+		tracing::debug!(?path, ?inputs, "Following linker script.");
+		for input in &inputs {
+			if let Some(input) = find_script_input(input, directory, library_paths) {
+				follow_library_candidate_inner(&input, library_paths, visited, found);
+			}
+		}
+		return;
+	}
+
+	found.push(path);
+}
+
+/// The length of the prefix of a library candidate that is read to check whether it is text.
+const TEXT_PREFIX_LEN: u64 = 512;
+
+/// Read a library candidate if it is text, such as a linker script or libtool archive. A prefix is read first, so that shared objects and other binary files are rejected without reading them fully.
+fn read_text(path: &std::path::Path) -> Option<String> {
+	let mut file = std::fs::File::open(path).ok()?;
+	let mut bytes = Vec::new();
+	file.by_ref()
+		.take(TEXT_PREFIX_LEN)
+		.read_to_end(&mut bytes)
+		.ok()?;
+	if bytes.starts_with(b"\x7fELF") || bytes.contains(&0) {
+		return None;
+	}
+
+	// A character may be cut off at the end of the prefix.
+	if let Err(error) = std::str::from_utf8(&bytes)
+		&& error.error_len().is_some()
+	{
+		return None;
+	}
+	file.read_to_end(&mut bytes).ok()?;
+	String::from_utf8(bytes).ok()
+}
+
+/// Find the file for an input of a linker script or libtool archive in `directory`. An absolute path is resolved with [`resolve_absolute_input`] and otherwise searched for by its file name. A relative path is searched for in the directory of the script and then the library paths, and a library named with `-l` in the library paths.
+fn find_script_input(
+	input: &script::Input,
+	directory: &std::path::Path,
+	library_paths: &[PathBuf],
+) -> Option<PathBuf> {
+	let names = match input {
+		script::Input::Path(path) if std::path::Path::new(path).is_absolute() => {
+			let path = std::path::Path::new(path);
+			if let Some(resolved) = resolve_absolute_input(path, directory) {
+				return Some(resolved);
+			}
+			vec![path.file_name()?.to_str()?.to_owned()]
+		},
+		script::Input::Path(path) => vec![path.clone()],
+		script::Input::Library(name) => match name.strip_prefix(':') {
+			Some(file_name) => vec![file_name.to_owned()],
+			None => vec![format!("lib{name}.so"), format!("lib{name}.a")],
+		},
+		script::Input::LibraryPath(_) => return None,
+	};
+	let directories = match input {
+		script::Input::Path(_) => std::iter::once(directory)
+			.chain(library_paths.iter().map(PathBuf::as_path))
+			.collect_vec(),
+		_ => library_paths.iter().map(PathBuf::as_path).collect_vec(),
+	};
+	directories
+		.into_iter()
+		.flat_map(|directory| names.iter().map(move |name| directory.join(name)))
+		.find(|candidate| candidate.is_file())
+}
+
+/// Resolve an absolute path named by a linker script or libtool archive in `directory`. A path into an artifact is used as is. Any other path is resolved in the artifact that contains the script, as GNU ld resolves it in the sysroot, so that no file outside an artifact is checked in.
+fn resolve_absolute_input(path: &std::path::Path, directory: &std::path::Path) -> Option<PathBuf> {
+	let in_artifact = path
+		.to_str()
+		.is_some_and(common::is_artifact_path)
+		.then(|| path.to_owned());
+	let in_script_artifact = common::artifact_containing(directory)
+		.map(|artifact| artifact.join(path.strip_prefix("/").unwrap_or(path)));
+	in_artifact
+		.into_iter()
+		.chain(in_script_artifact)
+		.find(|candidate| candidate.is_file())
 }
 
 /// Produce the library paths for the output wrapper according to the given configuration.
+#[tracing::instrument(skip_all, fields(?strategy))]
 async fn optimize_library_paths<H: BuildHasher + Default + Send + Sync>(
-	file: &tg::File,
-	library_paths: HashSet<DirectoryWithSubpath, H>,
+	runpaths: &Runpaths,
+	mut library_paths: HashSet<DirectoryWithSubpath, H>,
 	needed_libraries: &mut HashMap<String, Option<DirectoryWithSubpath>, H>,
 	strategy: LibraryPathStrategy,
 	max_depth: usize,
 	disallow_missing: bool,
 ) -> tg::Result<HashSet<DirectoryWithSubpath, H>> {
-	if matches!(strategy, LibraryPathStrategy::None) || library_paths.is_empty() {
+	if matches!(strategy, LibraryPathStrategy::None) {
+		library_paths.extend(runpaths.directories().cloned());
 		return Ok(library_paths);
 	}
 
-	// Cache the library paths before searching them, so reads do not reassemble blobs.
-	cache_library_paths(&library_paths).await?;
-
-	// Find all the transitive needed libraries of the output file we can locate in the library path.
-	find_transitive_needed_libraries(file, &library_paths, needed_libraries, max_depth, 0).await?;
+	// Cache the library paths and runpaths before searching them, so reads do not reassemble blobs.
+	let search_paths = library_paths
+		.iter()
+		.chain(runpaths.directories())
+		.cloned()
+		.collect();
+	cache_library_paths(&search_paths).await?;
+
+	// Find all the transitive needed libraries of the output file we can locate in its runpaths and the library path.
+	find_transitive_needed_libraries(
+		runpaths,
+		&[],
+		&library_paths,
+		needed_libraries,
+		max_depth,
+		0,
+	)
+	.await?;
 	tracing::debug!(?needed_libraries, "post-find");
 
 	let filtered_library_paths = needed_libraries.values().flatten().cloned().collect();
@@ -1162,6 +1310,7 @@ async fn optimize_library_paths<H: BuildHasher + Default + Send + Sync>(
 
 /// Cache a set of library paths. Each referent carries its stored token, without which the server
 /// falls back to an index lookup to authorize it.
+#[tracing::instrument(skip_all, fields(count = library_paths.len()))]
 async fn cache_library_paths<H: BuildHasher + Default>(
 	library_paths: &HashSet<DirectoryWithSubpath, H>,
 ) -> tg::Result<()> {
@@ -1285,42 +1434,28 @@ async fn resolve_directories<H: BuildHasher + Default>(
 	Ok(resolved_paths)
 }
 
-/// Recursively find all needed libraries for an executable.
+/// Recursively find all needed libraries for an object, searching the directories in the order of [`loader_search_paths`]. `loader_rpaths` holds the `DT_RPATH` directories of the objects that loaded it, closest first, ending with the output file's.
 async fn find_transitive_needed_libraries<H: BuildHasher + Default + Send + Sync>(
-	file: &tg::File,
+	runpaths: &Runpaths,
+	loader_rpaths: &[DirectoryWithSubpath],
 	library_paths: &HashSet<DirectoryWithSubpath, H>,
 	all_needed_libraries: &mut HashMap<String, Option<DirectoryWithSubpath>, H>,
 	max_depth: usize,
 	depth: usize,
 ) -> tg::Result<()> {
-	// Check for transitive dependencies if we've recurred beyond the initial file.
-	if depth > 0 {
-		let id = file.id();
-		tracing::debug!(?id, "analyzing transitive dependency");
-		match analyze_executable(&file.bytes().await?) {
-			Ok(AnalyzeOutputFileOutput {
-				needed_libraries, ..
-			}) => {
-				tracing::debug!(?id, ?needed_libraries, "found additional needed libraries");
-				for library in &needed_libraries {
-					if cfg!(target_os = "macos") && library == "libSystem.B.dylib" {
-						continue;
-					}
-					all_needed_libraries.entry(library.clone()).or_insert(None);
-				}
-			},
-			Err(e) => {
-				tracing::debug!(?e, ?id, "failed to analyze file as an object!");
-			},
-		}
-	}
-
-	// Check if we're done after analyzing the current file.
+	// Check if we're done.
 	if found_all_libraries(all_needed_libraries) || depth == max_depth {
 		return Ok(());
 	}
 
-	for dir_with_subpath in library_paths {
+	// The libraries this object loads inherit its `DT_RPATH` and the ones it inherited.
+	let inherited_rpaths = runpaths
+		.rpath
+		.iter()
+		.chain(loader_rpaths)
+		.cloned()
+		.collect_vec();
+	for dir_with_subpath in loader_search_paths(runpaths, loader_rpaths, library_paths) {
 		let directory = directory_from_dir_with_subpath(dir_with_subpath).await?;
 		tracing::trace!(?dir_with_subpath, "Checking directory for libraries.");
 		let copy = all_needed_libraries.keys().cloned().collect_vec();
@@ -1341,8 +1476,12 @@ async fn find_transitive_needed_libraries<H: BuildHasher + Default + Send + Sync
 				*all_needed_libraries
 					.entry(library_name.clone())
 					.or_insert(None) = Some(dir_with_subpath.clone());
+				let runpaths =
+					analyze_found_library(&found_library, dir_with_subpath, all_needed_libraries)
+						.await?;
 				Box::pin(find_transitive_needed_libraries(
-					&found_library,
+					&runpaths,
+					&inherited_rpaths,
 					library_paths,
 					all_needed_libraries,
 					max_depth,
@@ -1359,6 +1498,200 @@ async fn find_transitive_needed_libraries<H: BuildHasher + Default + Send + Sync
 	Ok(())
 }
 
+/// Get the directories that the dynamic loader searches for the needed libraries of an object: its `DT_RPATH` directories and those of the objects that loaded it, unless it has a `DT_RUNPATH`, then the library paths, then its `DT_RUNPATH` directories.
+fn loader_search_paths<'a, H: BuildHasher>(
+	runpaths: &'a Runpaths,
+	loader_rpaths: &'a [DirectoryWithSubpath],
+	library_paths: &'a HashSet<DirectoryWithSubpath, H>,
+) -> impl Iterator<Item = &'a DirectoryWithSubpath> {
+	let loader_rpaths: &[DirectoryWithSubpath] = if runpaths.runpath.is_empty() {
+		loader_rpaths
+	} else {
+		&[]
+	};
+	runpaths
+		.rpath
+		.iter()
+		.chain(loader_rpaths)
+		.chain(library_paths)
+		.chain(&runpaths.runpath)
+}
+
+/// Add the needed libraries of a library found in `origin` and resolve its runpaths.
+async fn analyze_found_library<H: BuildHasher + Default>(
+	file: &tg::File,
+	origin: &DirectoryWithSubpath,
+	all_needed_libraries: &mut HashMap<String, Option<DirectoryWithSubpath>, H>,
+) -> tg::Result<Runpaths> {
+	let id = file.id();
+	tracing::debug!(?id, "analyzing transitive dependency");
+	match analyze_executable(&file.bytes().await?) {
+		Ok(AnalyzeOutputFileOutput {
+			needed_libraries,
+			rpath,
+			runpath,
+			..
+		}) => {
+			tracing::debug!(?id, ?needed_libraries, "found additional needed libraries");
+			for library in &needed_libraries {
+				if cfg!(target_os = "macos") && library == "libSystem.B.dylib" {
+					continue;
+				}
+				all_needed_libraries.entry(library.clone()).or_insert(None);
+			}
+			let runpaths = Runpaths {
+				rpath: resolve_library_runpaths(&rpath, origin).await,
+				runpath: resolve_library_runpaths(&runpath, origin).await,
+			};
+			Ok(runpaths)
+		},
+		Err(e) => {
+			tracing::debug!(?e, ?id, "failed to analyze file as an object!");
+			Ok(Runpaths::default())
+		},
+	}
+}
+
+/// The directories that an object asks the dynamic loader to search, resolved to artifacts.
+#[derive(Clone, Debug, Default)]
+struct Runpaths {
+	/// The directories from `DT_RPATH`, which are searched before the library paths.
+	rpath: Vec<DirectoryWithSubpath>,
+
+	/// The directories from `DT_RUNPATH`, which are searched after the library paths.
+	runpath: Vec<DirectoryWithSubpath>,
+}
+
+impl Runpaths {
+	fn is_empty(&self) -> bool {
+		self.rpath.is_empty() && self.runpath.is_empty()
+	}
+
+	fn directories(&self) -> impl Iterator<Item = &DirectoryWithSubpath> {
+		self.rpath.iter().chain(&self.runpath)
+	}
+}
+
+/// Resolve the runpaths of the output file, expanding `$ORIGIN` to the output's directory. Only directories relative to `$ORIGIN` and artifact paths are kept, so the host's library directories are not checked in. Linker scripts in checked in directories are followed through `library_paths`.
+async fn resolve_output_runpaths(
+	runpaths: &[String],
+	origin: &std::path::Path,
+	library_paths: &[PathBuf],
+) -> Vec<DirectoryWithSubpath> {
+	let mut resolved = Vec::new();
+	for runpath in runpaths.iter().flat_map(|runpath| expand_runpath(runpath)) {
+		let path = if let Some(relative) = strip_origin(&runpath) {
+			let path = origin.join(relative.trim_start_matches('/'));
+			if !path.is_dir() {
+				continue;
+			}
+			path.display().to_string()
+		} else if common::is_artifact_path(&runpath) {
+			runpath
+		} else {
+			tracing::debug!(?runpath, "Skipping runpath outside the output's directory.");
+			continue;
+		};
+		let dir_with_subpath = resolve_library_path(&path, library_paths)
+			.await
+			.inspect_err(|error| tracing::debug!(?error, ?path, "Failed to resolve runpath."))
+			.ok()
+			.flatten();
+		if let Some(dir_with_subpath) = dir_with_subpath
+			&& !resolved.contains(&dir_with_subpath)
+		{
+			resolved.push(dir_with_subpath);
+		}
+	}
+	resolved
+}
+
+/// Resolve the runpaths of a library found in `origin`, expanding `$ORIGIN` to `origin`. Only directories relative to `$ORIGIN` within the same artifact and artifact paths are kept.
+async fn resolve_library_runpaths(
+	runpaths: &[String],
+	origin: &DirectoryWithSubpath,
+) -> Vec<DirectoryWithSubpath> {
+	let mut resolved = Vec::new();
+	for runpath in runpaths.iter().flat_map(|runpath| expand_runpath(runpath)) {
+		let dir_with_subpath = if let Some(relative) = strip_origin(&runpath) {
+			let Some(subpath) = join_subpath(origin.subpath.as_deref(), relative) else {
+				tracing::debug!(?runpath, "Skipping runpath outside the artifact.");
+				continue;
+			};
+			let dir_with_subpath = DirectoryWithSubpath {
+				id: origin.id.clone(),
+				subpath: (!subpath.as_os_str().is_empty()).then_some(subpath),
+				token: origin.token.clone(),
+			};
+			if directory_from_dir_with_subpath(&dir_with_subpath)
+				.await
+				.is_err()
+			{
+				continue;
+			}
+			Some(dir_with_subpath)
+		} else if common::is_artifact_path(&runpath) {
+			resolve_library_path(&runpath, &[])
+				.await
+				.inspect_err(|error| {
+					tracing::debug!(?error, ?runpath, "Failed to resolve runpath.")
+				})
+				.ok()
+				.flatten()
+		} else {
+			None
+		};
+		if let Some(dir_with_subpath) = dir_with_subpath
+			&& !resolved.contains(&dir_with_subpath)
+		{
+			resolved.push(dir_with_subpath);
+		}
+	}
+	resolved
+}
+
+/// Expand `$PLATFORM` and `$LIB` in a runpath. `$LIB` depends on how the dynamic loader was built, so both `lib` and `lib64` are tried.
+fn expand_runpath(runpath: &str) -> Vec<String> {
+	let platform = std::env::consts::ARCH;
+	let runpath = runpath
+		.replace("${PLATFORM}", platform)
+		.replace("$PLATFORM", platform);
+	if runpath.contains("$LIB") || runpath.contains("${LIB}") {
+		["lib", "lib64"]
+			.into_iter()
+			.map(|lib| runpath.replace("${LIB}", lib).replace("$LIB", lib))
+			.collect()
+	} else {
+		vec![runpath]
+	}
+}
+
+/// Get the part of a runpath after a leading `$ORIGIN`.
+fn strip_origin(runpath: &str) -> Option<&str> {
+	let relative = runpath
+		.strip_prefix("${ORIGIN}")
+		.or_else(|| runpath.strip_prefix("$ORIGIN"))?;
+	(relative.is_empty() || relative.starts_with('/')).then_some(relative)
+}
+
+/// Join a path relative to `$ORIGIN` onto the subpath of the directory containing an object. Returns `None` if the result leaves the artifact.
+fn join_subpath(subpath: Option<&std::path::Path>, relative: &str) -> Option<PathBuf> {
+	let mut joined = subpath.map(ToOwned::to_owned).unwrap_or_default();
+	for component in std::path::Path::new(relative).components() {
+		match component {
+			std::path::Component::Normal(name) => joined.push(name),
+			std::path::Component::ParentDir => {
+				if !joined.pop() {
+					return None;
+				}
+			},
+			std::path::Component::CurDir | std::path::Component::RootDir => (),
+			std::path::Component::Prefix(_) => return None,
+		}
+	}
+	Some(joined)
+}
+
 /// Determine if all needed libraries have been found.
 #[tracing::instrument]
 fn found_all_libraries<H: BuildHasher + Default>(
@@ -1427,6 +1760,8 @@ fn analyze_executable(bytes: &[u8]) -> tg::Result<AnalyzeOutputFileOutput> {
 			name: None,
 			needed_libraries: vec![],
 			entrypoint: None,
+			rpath: vec![],
+			runpath: vec![],
 		},
 
 		// Handle an ELF file.
@@ -1449,6 +1784,22 @@ fn analyze_executable(bytes: &[u8]) -> tg::Result<AnalyzeOutputFileOutput> {
 
 			let entrypoint = (elf.entry != 0).then_some(elf.entry);
 
+			// Read the runpaths. Each entry is a colon-separated list.
+			let split = |paths: &[&str]| {
+				paths
+					.iter()
+					.flat_map(|path| path.split(':'))
+					.filter(|path| !path.is_empty())
+					.map(ToOwned::to_owned)
+					.collect_vec()
+			};
+			let runpath = split(&elf.runpaths);
+			let rpath = if runpath.is_empty() {
+				split(&elf.rpaths)
+			} else {
+				vec![]
+			};
+
 			// Check whether or not the object requires an interpreter:
 			// - If the object has an interpreter field.
 			// - If the object is a PIE and has 1 or more NEEDS.
@@ -1477,6 +1828,8 @@ fn analyze_executable(bytes: &[u8]) -> tg::Result<AnalyzeOutputFileOutput> {
 				name,
 				needed_libraries,
 				entrypoint,
+				rpath,
+				runpath,
 			}
 		},
 
@@ -1498,6 +1851,8 @@ fn analyze_executable(bytes: &[u8]) -> tg::Result<AnalyzeOutputFileOutput> {
 					name,
 					needed_libraries,
 					entrypoint: Some(entrypoint),
+					rpath: vec![],
+					runpath: vec![],
 				}
 			},
 			goblin::mach::Mach::Fat(mach) => {
@@ -1525,6 +1880,8 @@ fn analyze_executable(bytes: &[u8]) -> tg::Result<AnalyzeOutputFileOutput> {
 					name,
 					needed_libraries,
 					entrypoint: None,
+					rpath: vec![],
+					runpath: vec![],
 				}
 			},
 		},
@@ -1611,7 +1968,124 @@ impl std::hash::Hash for DirectoryWithSubpath {
 
 #[cfg(test)]
 mod tests {
-	use super::{AnalyzeOutputFileOutput, InterpreterRequirement, analyze_output_file};
+	use super::{
+		AnalyzeOutputFileOutput, DirectoryWithSubpath, InterpreterRequirement, Runpaths,
+		analyze_output_file, expand_runpath, follow_library_candidate, join_subpath,
+		loader_search_paths, strip_origin,
+	};
+	use itertools::Itertools as _;
+	use std::{
+		collections::{BTreeMap, HashSet},
+		path::{Path, PathBuf},
+	};
+	use tangram_client::prelude::*;
+
+	#[test]
+	fn runpaths() {
+		assert_eq!(strip_origin("$ORIGIN/../lib"), Some("/../lib"));
+		assert_eq!(strip_origin("${ORIGIN}"), Some(""));
+		assert_eq!(strip_origin("$ORIGINAL/lib"), None);
+		assert_eq!(strip_origin("/usr/lib"), None);
+
+		assert_eq!(expand_runpath("$ORIGIN/../$LIB").len(), 2);
+		assert_eq!(
+			expand_runpath("/opt/${PLATFORM}"),
+			[format!("/opt/{}", std::env::consts::ARCH)]
+		);
+
+		assert_eq!(
+			join_subpath(Some(Path::new("lib")), "/../lib64"),
+			Some(PathBuf::from("lib64"))
+		);
+		assert_eq!(
+			join_subpath(Some(Path::new("lib")), "/.."),
+			Some(PathBuf::new())
+		);
+		assert_eq!(join_subpath(None, "/../lib"), None);
+	}
+
+	#[test]
+	fn search_order() {
+		let id = tg::Directory::with_entries(BTreeMap::new()).id();
+		let dir = |subpath: &str| DirectoryWithSubpath {
+			id: id.clone(),
+			subpath: Some(PathBuf::from(subpath)),
+			token: None,
+		};
+		let library_paths = HashSet::from([dir("library")]);
+		let loader_rpaths = [dir("loader"), dir("output")];
+		let subpaths = |runpaths: &Runpaths| {
+			loader_search_paths(runpaths, &loader_rpaths, &library_paths)
+				.map(|dir| dir.subpath.clone().unwrap())
+				.collect_vec()
+		};
+
+		// An object without a `DT_RUNPATH` searches its `DT_RPATH` and those of its loaders first.
+		let runpaths = Runpaths {
+			rpath: vec![dir("rpath")],
+			runpath: vec![],
+		};
+		assert_eq!(
+			subpaths(&runpaths),
+			["rpath", "loader", "output", "library"].map(PathBuf::from)
+		);
+
+		// An object with a `DT_RUNPATH` ignores the `DT_RPATH` of its loaders.
+		let runpaths = Runpaths {
+			rpath: vec![],
+			runpath: vec![dir("runpath")],
+		};
+		assert_eq!(
+			subpaths(&runpaths),
+			["library", "runpath"].map(PathBuf::from)
+		);
+	}
+
+	#[test]
+	fn linker_script_inputs() {
+		// The script is in one library path, and the library it names with `-l` is in another.
+		let root = tempfile::TempDir::new().unwrap();
+		let (a, b, host) = (
+			root.path().join("a"),
+			root.path().join("b"),
+			root.path().join("host"),
+		);
+		for directory in [&a, &b, &host] {
+			std::fs::create_dir_all(directory).unwrap();
+		}
+		std::fs::write(
+			a.join("libncursesw.so"),
+			"INPUT(libncursesw.so.6 -ltinfo)\n",
+		)
+		.unwrap();
+		std::fs::write(a.join("libncursesw.so.6"), b"\x7fELF\x02\x01\x01").unwrap();
+		std::fs::write(b.join("libtinfo.so"), b"\x7fELF\x02\x01\x01").unwrap();
+
+		let found = follow_library_candidate(&a.join("libncursesw.so"), &[a.clone(), b.clone()]);
+		assert_eq!(
+			found,
+			[
+				a.join("libncursesw.so.6").canonicalize().unwrap(),
+				b.join("libtinfo.so").canonicalize().unwrap(),
+			]
+		);
+
+		// An absolute path outside an artifact is searched for by its file name, and never used as is.
+		std::fs::write(host.join("libc.so.6"), b"\x7fELF\x02\x01\x01").unwrap();
+		std::fs::write(host.join("libhost.so"), b"\x7fELF\x02\x01\x01").unwrap();
+		std::fs::write(a.join("libc.so.6"), b"\x7fELF\x02\x01\x01").unwrap();
+		std::fs::write(
+			a.join("libc.so"),
+			format!(
+				"GROUP({} {})\n",
+				host.join("libc.so.6").display(),
+				host.join("libhost.so").display()
+			),
+		)
+		.unwrap();
+		let found = follow_library_candidate(&a.join("libc.so"), &[a.clone(), b.clone()]);
+		assert_eq!(found, [a.join("libc.so.6").canonicalize().unwrap()]);
+	}
 
 	#[tokio::test]
 	async fn read_output_files() {
@@ -0,0 +1,192 @@
+//! Parse the text files that stand in for libraries: GNU ld linker scripts such as `libc.so`, and libtool archives.
+
+/// An input named by a linker script or a libtool archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+	/// A file, which may be relative.
+	Path(String),
+
+	/// A library named with `-l`, which is searched for in the library paths. A name starting with `:` is a file name.
+	Library(String),
+
+	/// A library path added with `-L`.
+	LibraryPath(String),
+}
+
+/// The fields of a libtool archive that name libraries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LibtoolArchive {
+	/// The name of the shared object.
+	pub dlname: Option<String>,
+
+	/// The directory the library is installed to.
+	pub libdir: Option<String>,
+
+	/// The libraries the library depends on.
+	pub dependency_libs: Vec<Input>,
+}
+
+/// Parse the inputs named by the `INPUT`, `GROUP` and `AS_NEEDED` commands of a linker script. Returns `None` if the contents are not a linker script with one of these commands.
+pub fn parse_linker_script(contents: &str) -> Option<Vec<Input>> {
+	let tokens = tokenize(contents)?;
+	let mut inputs = Vec::new();
+	let mut commands = Vec::new();
+	let mut found = false;
+	let mut index = 0;
+	while let Some(token) = tokens.get(index) {
+		index += 1;
+		match token.as_str() {
+			")" => {
+				commands.pop()?;
+			},
+			"(" => return None,
+			word if tokens.get(index).is_some_and(|token| token == "(") => {
+				index += 1;
+				found |= matches!(word, "INPUT" | "GROUP");
+				commands.push(word);
+			},
+			word => {
+				if commands
+					.last()
+					.is_some_and(|command| matches!(*command, "INPUT" | "GROUP" | "AS_NEEDED"))
+				{
+					let input = match word.strip_prefix("-l") {
+						Some(name) => Input::Library(name.to_owned()),
+						None => Input::Path(word.to_owned()),
+					};
+					inputs.push(input);
+				}
+			},
+		}
+	}
+	(found && commands.is_empty()).then_some(inputs)
+}
+
+/// Parse a libtool archive. Returns `None` if the contents are not a libtool archive.
+pub fn parse_libtool_archive(contents: &str) -> Option<LibtoolArchive> {
+	let mut archive = LibtoolArchive::default();
+	let mut found = false;
+	for line in contents.lines() {
+		let Some((key, value)) = line.trim().split_once('=') else {
+			continue;
+		};
+		let value = value.trim().trim_matches('\'').trim_matches('"');
+		match key {
+			"dlname" => {
+				found = true;
+				archive.dlname = (!value.is_empty()).then(|| value.to_owned());
+			},
+			"libdir" => {
+				archive.libdir = (!value.is_empty()).then(|| value.to_owned());
+			},
+			"dependency_libs" => {
+				archive.dependency_libs = value
+					.split_whitespace()
+					.filter_map(|word| {
+						if let Some(path) = word.strip_prefix("-L") {
+							Some(Input::LibraryPath(path.to_owned()))
+						} else if let Some(name) = word.strip_prefix("-l") {
+							Some(Input::Library(name.to_owned()))
+						} else if word.starts_with('-') {
+							None
+						} else {
+							Some(Input::Path(word.to_owned()))
+						}
+					})
+					.collect();
+			},
+			_ => (),
+		}
+	}
+	found.then_some(archive)
+}
+
+/// Split a linker script into words and parentheses, dropping comments and commas. Returns `None` if a comment or a quoted word is not terminated.
+fn tokenize(contents: &str) -> Option<Vec<String>> {
+	let mut tokens = Vec::new();
+	let mut word = String::new();
+	let mut chars = contents.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'/' if chars.peek() == Some(&'*') => {
+				chars.next();
+				let mut previous = None;
+				loop {
+					let c = chars.next()?;
+					if previous == Some('*') && c == '/' {
+						break;
+					}
+					previous = Some(c);
+				}
+			},
+			'"' => loop {
+				match chars.next()? {
+					'"' => break,
+					c => word.push(c),
+				}
+			},
+			'(' | ')' | ',' => {
+				if !word.is_empty() {
+					tokens.push(std::mem::take(&mut word));
+				}
+				if c != ',' {
+					tokens.push(c.to_string());
+				}
+			},
+			c if c.is_whitespace() => {
+				if !word.is_empty() {
+					tokens.push(std::mem::take(&mut word));
+				}
+			},
+			c => word.push(c),
+		}
+	}
+	if !word.is_empty() {
+		tokens.push(word);
+	}
+	Some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Input, parse_libtool_archive, parse_linker_script};
+
+	#[test]
+	fn linker_script() {
+		let libc = "/* GNU ld script\n   Use the shared library, but some functions are only in\n   the static library.  */\nOUTPUT_FORMAT(elf64-x86-64)\nGROUP ( /lib/libc.so.6 /usr/lib/libc_nonshared.a  AS_NEEDED ( /lib/ld-linux-x86-64.so.2 ) )\n";
+		assert_eq!(
+			parse_linker_script(libc),
+			Some(vec![
+				Input::Path("/lib/libc.so.6".to_owned()),
+				Input::Path("/usr/lib/libc_nonshared.a".to_owned()),
+				Input::Path("/lib/ld-linux-x86-64.so.2".to_owned()),
+			])
+		);
+		assert_eq!(
+			parse_linker_script("INPUT(libncursesw.so.6 -ltinfo)"),
+			Some(vec![
+				Input::Path("libncursesw.so.6".to_owned()),
+				Input::Library("tinfo".to_owned()),
+			])
+		);
+		assert_eq!(parse_linker_script("OUTPUT_FORMAT(elf64-x86-64)"), None);
+		assert_eq!(parse_linker_script("GROUP ( /lib/libc.so.6"), None);
+	}
+
+	#[test]
+	fn libtool_archive() {
+		let archive = "# libfoo.la - a libtool library file\ndlname='libfoo.so.1'\nlibrary_names='libfoo.so.1.0.0 libfoo.so.1 libfoo.so'\ndependency_libs=' -L/opt/lib -lbar /opt/lib/libbaz.la -pthread'\ninstalled=no\nlibdir='/usr/local/lib'\n";
+		let archive = parse_libtool_archive(archive).unwrap();
+		assert_eq!(archive.dlname.as_deref(), Some("libfoo.so.1"));
+		assert_eq!(archive.libdir.as_deref(), Some("/usr/local/lib"));
+		assert_eq!(
+			archive.dependency_libs,
+			[
+				Input::LibraryPath("/opt/lib".to_owned()),
+				Input::Library("bar".to_owned()),
+				Input::Path("/opt/lib/libbaz.la".to_owned()),
+			]
+		);
+		assert_eq!(parse_libtool_archive("GROUP ( libc.so.6 )"), None);
+	}
+}
@@ -1,13 +1,13 @@
 use std::{os::unix::fs::PermissionsExt, path::PathBuf};
 
-use common::{Manifest, manifest};
+use common::{
+	Manifest,
+	config::{Config, Kind, Setting},
+	manifest,
+};
 use tangram_client::prelude::*;
 
 fn main() {
-	// Setup tracing.
-	#[cfg(feature = "tracing")]
-	common::tracing::setup("TGSTRIP_TRACING");
-
 	if let Err(e) = main_inner() {
 		common::error::print_error(e);
 		std::process::exit(1);
@@ -15,7 +15,14 @@ fn main() {
 }
 
 fn main_inner() -> tg::Result<()> {
-	let options = Options::parse()?;
+	// Read the config from the config file, the environment and arguments.
+	let config = Config::load("tgstrip", SETTINGS)?;
+
+	// Setup tracing.
+	#[cfg(feature = "tracing")]
+	config.setup_tracing();
+
+	let options = Options::parse(&config)?;
 	#[cfg(feature = "tracing")]
 	tracing::info!(?options, "parsed options");
 
@@ -33,12 +40,7 @@ fn main_inner() -> tg::Result<()> {
 	if options.passthrough || options.strip_targets.is_empty() {
 		#[cfg(feature = "tracing")]
 		tracing::info!("passing through, running strip with unmodified arguments");
-		let target_refs: Vec<&std::path::Path> = options
-			.strip_targets
-			.iter()
-			.map(std::path::PathBuf::as_path)
-			.collect();
-		run_strip(&options.strip_program, &options.strip_args, &target_refs)?;
+		run_strip(&options.strip_program, &options.passthrough_args, &[])?;
 		return Ok(());
 	}
 
@@ -111,6 +113,7 @@ fn main_inner() -> tg::Result<()> {
 }
 
 #[allow(clippy::too_many_lines)]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(?target_path)))]
 async fn run_proxy(
 	strip_program: &std::path::Path,
 	strip_args: &[String],
@@ -264,6 +267,9 @@ struct Options {
 	/// Should we skip the proxy and pass through the arguments to strip unchanged?
 	passthrough: bool,
 
+	/// The original arguments to strip, with any response files unexpanded.
+	passthrough_args: Vec<String>,
+
 	/// Arguments to pass to strip.
 	strip_args: Vec<String>,
 
@@ -277,46 +283,61 @@ struct Options {
 	strip_runtime_library_path: Option<String>,
 }
 
+// The settings read from the config file, the environment and flags.
+const SETTINGS: &[Setting] = &[
+	Setting {
+		env: "TGSTRIP_COMMAND_PATH",
+		flag: None,
+		kind: Kind::String,
+		default: None,
+		doc: "The path to the strip program that will be invoked. Required.",
+	},
+	Setting {
+		env: "TGSTRIP_PASSTHROUGH",
+		flag: Some("--tg-passthrough"),
+		kind: Kind::Bool,
+		default: None,
+		doc: "Run strip with unmodified arguments, even on wrappers.",
+	},
+	Setting {
+		env: "TGSTRIP_RUNTIME_LIBRARY_PATH",
+		flag: None,
+		kind: Kind::String,
+		default: None,
+		doc: "Library paths required by the strip program at runtime.",
+	},
+];
+
 impl Options {
-	fn parse() -> tg::Result<Self> {
-		// Read env for options.
-		let mut passthrough = std::env::var("TGSTRIP_PASSTHROUGH").is_ok();
-		let strip_program = std::env::var("TGSTRIP_COMMAND_PATH")
-			.map_err(|error| tg::error!(source = error, "TGSTRIP_COMMAND_PATH not set"))?
+	fn parse(config: &Config) -> tg::Result<Self> {
+		// Read the options from the config.
+		let passthrough = config.bool("TGSTRIP_PASSTHROUGH");
+		let strip_program = config
+			.get("TGSTRIP_COMMAND_PATH")
+			.ok_or_else(|| tg::error!("TGSTRIP_COMMAND_PATH not set"))?
 			.into();
-		let strip_runtime_library_path =
-			std::env::var("TGSTRIP_RUNTIME_LIBRARY_PATH").unwrap_or_default();
-		let strip_runtime_library_path = if strip_runtime_library_path.is_empty() {
-			None
-		} else {
-			Some(strip_runtime_library_path)
-		};
+		let strip_runtime_library_path = config
+			.get("TGSTRIP_RUNTIME_LIBRARY_PATH")
+			.map(ToOwned::to_owned);
 
 		// Parse the arguments.
 		let mut strip_targets = Vec::new();
 		let mut strip_args = vec![];
 
-		for arg in std::env::args().skip(1) {
-			// Catch any --tg- args.
-			if arg.starts_with("--tg-") {
-				// Handle --tg-passthrough.
-				if arg == "--tg-passthrough" {
-					passthrough = true;
-				}
+		for arg in &config.args {
+			// If the argument starts with `-`, it's an argument to strip.
+			if arg.starts_with('-') {
+				strip_args.push(arg.clone());
 			} else {
-				// If the argument starts with `-`, it's an argument to strip.
-				if arg.starts_with('-') {
-					strip_args.push(arg);
-				} else {
-					// This is a target file to strip.
-					strip_targets.push(arg.into());
-				}
+				// This is a target file to strip.
+				strip_targets.push(arg.into());
 			}
 		}
 
 		// Construct options struct.
 		let options = Options {
 			passthrough,
+			passthrough_args: config.passthrough_args.clone(),
 			strip_args,
 			strip_targets,
 			strip_program,
@@ -327,6 +348,7 @@ impl Options {
 }
 
 /// Execute the underlying `strip` command with the given arguments and targets.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 fn run_strip(
 	strip_program: &std::path::Path,
 	strip_args: &[String],
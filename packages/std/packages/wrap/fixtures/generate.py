@@ -0,0 +1,149 @@
+#!/usr/bin/env python3
+"""Generate the fixtures used by the tests in src/elf.rs and src/mach.rs.
+
+Each ELF fixture is a minimal executable with a PT_LOAD segment, a PT_INTERP segment and a section header table, in each combination of class and byte order.
+
+Each Mach-O slice is a minimal MH_EXECUTE with a __TEXT segment, a __LINKEDIT segment and a placeholder code signature at the end of __LINKEDIT, which is the layout `wrap` relies on. The universal binary is assembled with `llvm-lipo`, so its fat header is produced by an independent implementation.
+"""
+
+import pathlib
+import struct
+import subprocess
+
+MH_MAGIC_64 = 0xFEEDFACF
+MH_EXECUTE = 2
+LC_SEGMENT_64 = 0x19
+LC_CODE_SIGNATURE = 0x1D
+CSMAGIC_EMBEDDED_SIGNATURE = 0xFADE0CC0
+
+TEXT_SIZE = 0x1000
+SIGNATURE_SIZE = 0x100
+
+
+def segment(name, vmaddr, vmsize, fileoff, filesize):
+	return struct.pack(
+		"<II16sQQQQiiII",
+		LC_SEGMENT_64,
+		72,
+		name.encode(),
+		vmaddr,
+		vmsize,
+		fileoff,
+		filesize,
+		7,
+		7,
+		0,
+		0,
+	)
+
+
+def slice(cputype, cpusubtype):
+	commands = [
+		segment("__TEXT", 0x1_0000_0000, TEXT_SIZE, 0, TEXT_SIZE),
+		segment("__LINKEDIT", 0x1_0000_0000 + TEXT_SIZE, 0x1000, TEXT_SIZE, SIGNATURE_SIZE),
+		struct.pack("<IIII", LC_CODE_SIGNATURE, 16, TEXT_SIZE, SIGNATURE_SIZE),
+	]
+	commands = b"".join(commands)
+	header = struct.pack(
+		"<IiiIIIII", MH_MAGIC_64, cputype, cpusubtype, MH_EXECUTE, 3, len(commands), 0, 0
+	)
+	text = (header + commands).ljust(TEXT_SIZE, b"\0")
+	signature = struct.pack(">III", CSMAGIC_EMBEDDED_SIGNATURE, SIGNATURE_SIZE, 0)
+	return text + signature.ljust(SIGNATURE_SIZE, b"\0")
+
+
+EM_386 = 3
+EM_PPC = 20
+EM_S390 = 22
+EM_X86_64 = 62
+PT_LOAD = 1
+PT_INTERP = 3
+SHT_PROGBITS = 1
+SHT_STRTAB = 3
+SHF_ALLOC = 2
+SHF_EXECINSTR = 4
+
+INTERP_OFFSET = 0x100
+TEXT_OFFSET = 0x200
+SHSTRTAB_OFFSET = 0x300
+SHDR_OFFSET = 0x340
+BASE_ADDRESS = 0x10000
+
+
+def elf(bits, order, machine):
+	interp = b"/lib/ld.so\0"
+	text = b"\xcc" * 16
+	shstrtab = b"\0.interp\0.text\0.shstrtab\0"
+	address = "I" if bits == 32 else "Q"
+	if bits == 32:
+		ehdr_format, ehdr_size = order + "16sHHIIIIIHHHHHH", 52
+		phdr_format, phdr_size = order + "IIIIIIII", 32
+		shdr_format, shdr_size = order + "IIIIIIIIII", 40
+	else:
+		ehdr_format, ehdr_size = order + "16sHHIQQQIHHHHHH", 64
+		phdr_format, phdr_size = order + "IIQQQQQQ", 56
+		shdr_format, shdr_size = order + "IIQQQQIIQQ", 64
+
+	def phdr(p_type, flags, offset, size, align):
+		address = BASE_ADDRESS + offset
+		if bits == 32:
+			return struct.pack(phdr_format, p_type, offset, address, address, size, size, flags, align)
+		return struct.pack(phdr_format, p_type, flags, offset, address, address, size, size, align)
+
+	def shdr(name, sh_type, flags, offset, size, align):
+		address = BASE_ADDRESS + offset if flags else 0
+		return struct.pack(shdr_format, name, sh_type, flags, address, offset, size, 0, 0, align, 0)
+
+	ident = b"\x7fELF" + bytes([1 if bits == 32 else 2, 1 if order == "<" else 2, 1])
+	ehdr = struct.pack(
+		ehdr_format,
+		ident,
+		2,
+		machine,
+		1,
+		BASE_ADDRESS + TEXT_OFFSET,
+		ehdr_size,
+		SHDR_OFFSET,
+		0,
+		ehdr_size,
+		phdr_size,
+		2,
+		shdr_size,
+		4,
+		3,
+	)
+	phdrs = phdr(PT_LOAD, 5, 0, TEXT_OFFSET + len(text), 0x1000) + phdr(
+		PT_INTERP, 4, INTERP_OFFSET, len(interp), 1
+	)
+	shdrs = (
+		bytes(shdr_size)
+		+ shdr(1, SHT_PROGBITS, SHF_ALLOC, INTERP_OFFSET, len(interp), 1)
+		+ shdr(9, SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, TEXT_OFFSET, len(text), 16)
+		+ shdr(15, SHT_STRTAB, 0, SHSTRTAB_OFFSET, len(shstrtab), 1)
+	)
+	data = bytearray(SHDR_OFFSET)
+	data[0 : len(ehdr) + len(phdrs)] = ehdr + phdrs
+	data[INTERP_OFFSET : INTERP_OFFSET + len(interp)] = interp
+	data[TEXT_OFFSET : TEXT_OFFSET + len(text)] = text
+	data[SHSTRTAB_OFFSET : SHSTRTAB_OFFSET + len(shstrtab)] = shstrtab
+	return bytes(data) + shdrs
+
+
+directory = pathlib.Path(__file__).parent
+(directory / "elf32_lsb").write_bytes(elf(32, "<", EM_386))
+(directory / "elf32_msb").write_bytes(elf(32, ">", EM_PPC))
+(directory / "elf64_lsb").write_bytes(elf(64, "<", EM_X86_64))
+(directory / "elf64_msb").write_bytes(elf(64, ">", EM_S390))
+(directory / "mach_x86_64").write_bytes(slice(0x0100_0007, 3))
+(directory / "mach_arm64").write_bytes(slice(0x0100_000C, 0))
+subprocess.run(
+	[
+		"llvm-lipo-14",
+		"-create",
+		directory / "mach_x86_64",
+		directory / "mach_arm64",
+		"-output",
+		directory / "mach_universal",
+	],
+	check=True,
+)
@@ -0,0 +1,346 @@
+use crate::{Error, Format, Result};
+use serde_json::Value;
+use std::{
+	collections::BTreeSet,
+	os::unix::fs::{MetadataExt as _, PermissionsExt as _},
+	path::{Component, Path, PathBuf},
+};
+
+/// The artifacts directory of a bundle, relative to its wrapper. Wrappers in the artifacts find it too, since it is `.tangram/artifacts` in one of their ancestors.
+const ARTIFACTS_PATH: &str = ".tangram/artifacts";
+
+/// The directory of a bundle that files at absolute paths are copied to, relative to its wrapper.
+const ROOT_PATH: &str = ".tangram/root";
+
+/// Copy a wrapper into the new directory `output` along with everything it refers to, and make the copy relocatable. The artifacts in its manifest, and every artifact they refer to by symlinks or manifests, are copied to `.tangram/artifacts`. Absolute paths in the artifacts directory become artifacts. The executable, interpreter and preloads at other absolute paths are copied to `.tangram/root`, and library paths outside the artifacts directory are an error, since a directory such as `/usr/lib` holds far more than the wrapper needs. Artifacts are resolved in `artifacts_dir`, or in the directory the wrapper would use. Returns the path of the copy of the wrapper.
+pub fn bundle(
+	path: impl AsRef<Path>,
+	format: Option<Format>,
+	output: impl AsRef<Path>,
+	artifacts_dir: Option<&Path>,
+) -> Result<PathBuf> {
+	let path = path.as_ref();
+	let output = output.as_ref();
+	let mut manifest = crate::read_manifest::<Value>(path, format)?
+		.manifest
+		.ok_or_else(|| Error::UnsupportedFormat("the file is not a wrapper".to_owned()))?;
+	let artifacts_dir = match artifacts_dir {
+		Some(artifacts_dir) => artifacts_dir.to_owned(),
+		None => crate::unwrap::manifest_artifacts_dir(&manifest, path)?,
+	};
+	let artifacts_dir = normalize(&std::path::absolute(artifacts_dir)?);
+	if crate::unwrap::is_relocatable(&manifest) {
+		crate::unwrap::expand_origin(&mut manifest, &crate::unwrap::origin(path)?);
+	}
+
+	// Copy the wrapper.
+	let name = path
+		.file_name()
+		.ok_or_else(|| Error::UnsupportedFormat("the wrapper has no file name".to_owned()))?;
+	if output.exists() {
+		return Err(std::io::Error::new(
+			std::io::ErrorKind::AlreadyExists,
+			format!("{} already exists", output.display()),
+		)
+		.into());
+	}
+	std::fs::create_dir_all(output)?;
+	let wrapper = output.join(name);
+	std::fs::copy(path, &wrapper)?;
+	std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755))?;
+
+	// Store the absolute paths in the artifacts directory as artifacts. Copy the files at other absolute paths and refer to them relative to the wrapper.
+	let mut paths = Vec::new();
+	if let Value::Object(object) = &mut manifest {
+		for (key, value) in object.iter_mut() {
+			match key.as_str() {
+				"executable" if value["kind"] == "path" => paths.push((false, &mut value["value"])),
+				"interpreter" if value.is_object() => {
+					for (key, value) in value.as_object_mut().into_iter().flatten() {
+						match (key.as_str(), value) {
+							("path", value) => paths.push((false, value)),
+							("libraryPaths", Value::Array(values)) => {
+								paths.extend(values.iter_mut().map(|value| (true, value)));
+							},
+							("preloads", Value::Array(values)) => {
+								paths.extend(values.iter_mut().map(|value| (false, value)));
+							},
+							_ => (),
+						}
+					}
+				},
+				_ => (),
+			}
+		}
+	}
+	for (library_path, template) in paths {
+		let Some(absolute) = absolute_path(template).map(|path| normalize(&path)) else {
+			continue;
+		};
+		if absolute.starts_with(&artifacts_dir) {
+			*template = crate::edit::template(
+				&absolute.display().to_string(),
+				std::slice::from_ref(&artifacts_dir),
+			);
+			continue;
+		}
+		if library_path {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!(
+					"the library path {} is not in {}",
+					absolute.display(),
+					artifacts_dir.display()
+				),
+			)
+			.into());
+		}
+		let relative = Path::new(ROOT_PATH).join(absolute.strip_prefix("/").unwrap_or(&absolute));
+		copy(&absolute, &output.join(&relative), &mut Vec::new())?;
+		*template = serde_json::json!({
+			"components": [{ "kind": "string", "value": format!("$ORIGIN/{}", relative.display()) }],
+		});
+	}
+
+	// Copy the artifacts.
+	let mut pending = BTreeSet::new();
+	crate::inspect::collect_artifacts(&manifest, &mut pending);
+	let mut bundler = Bundler {
+		src: &artifacts_dir,
+		dst: &output.join(ARTIFACTS_PATH),
+		copied: BTreeSet::new(),
+		pending,
+	};
+	bundler.run()?;
+
+	// Make the manifest relocatable.
+	manifest["artifactsPath"] = ARTIFACTS_PATH.into();
+	crate::validate(&manifest, manifest["executable"]["kind"] == "address")?;
+	crate::edit_manifest::<Value>(&wrapper, format, |stored| {
+		*stored = manifest;
+		Ok(())
+	})?;
+
+	Ok(wrapper)
+}
+
+struct Bundler<'a> {
+	src: &'a Path,
+	dst: &'a Path,
+	copied: BTreeSet<String>,
+	pending: BTreeSet<String>,
+}
+
+impl Bundler<'_> {
+	/// Copy the pending artifacts until every artifact they refer to is copied.
+	fn run(&mut self) -> Result<()> {
+		std::fs::create_dir_all(self.dst)?;
+		while let Some(id) = self.pending.pop_first() {
+			if !self.copied.insert(id.clone()) {
+				continue;
+			}
+			let src = self.src.join(&id);
+			if std::fs::symlink_metadata(&src).is_err() {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::NotFound,
+					format!("the artifact {id} is not in {}", self.src.display()),
+				)
+				.into());
+			}
+			let dst = self.dst.join(&id);
+			self.copy(&src, &dst)?;
+		}
+		Ok(())
+	}
+
+	/// Copy an artifact, keeping its symlinks and queueing the artifacts that its symlinks and wrappers refer to.
+	fn copy(&mut self, src: &Path, dst: &Path) -> Result<()> {
+		let metadata = std::fs::symlink_metadata(src)?;
+		if metadata.is_symlink() {
+			let mut target = std::fs::read_link(src)?;
+			let parent = src.parent().unwrap_or(Path::new("/"));
+			let resolved = normalize(&parent.join(&target));
+			if let Ok(rest) = resolved.strip_prefix(self.src) {
+				if let Some(Component::Normal(id)) = rest.components().next() {
+					self.pending.insert(id.to_string_lossy().into_owned());
+				}
+
+				// An absolute symlink into the artifacts directory would not be relocatable.
+				if target.is_absolute() {
+					let dst_parent = dst.parent().unwrap_or(Path::new("/"));
+					target = relative(
+						&normalize(&std::path::absolute(dst_parent)?),
+						&normalize(&std::path::absolute(self.dst.join(rest))?),
+					);
+				}
+			}
+			std::os::unix::fs::symlink(target, dst)?;
+		} else if metadata.is_dir() {
+			std::fs::create_dir_all(dst)?;
+			for entry in std::fs::read_dir(src)? {
+				let entry = entry?;
+				self.copy(&entry.path(), &dst.join(entry.file_name()))?;
+			}
+		} else {
+			std::fs::copy(src, dst)?;
+			if metadata.permissions().mode() & 0o111 != 0
+				&& let Ok(output) = crate::read_manifest::<Value>(src, None)
+				&& let Some(manifest) = output.manifest
+			{
+				crate::inspect::collect_artifacts(&manifest, &mut self.pending);
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Copy a file or directory, copying symlinks as the files they point to. `ancestors` holds the device and inode of each directory being copied, so a symlink to one of them is an error instead of endless recursion.
+fn copy(src: &Path, dst: &Path, ancestors: &mut Vec<(u64, u64)>) -> Result<()> {
+	if let Some(parent) = dst.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+	let metadata = std::fs::metadata(src)?;
+	if metadata.is_dir() {
+		let inode = (metadata.dev(), metadata.ino());
+		if ancestors.contains(&inode) {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!(
+					"{} is a symlink to a directory that contains it",
+					src.display()
+				),
+			)
+			.into());
+		}
+		ancestors.push(inode);
+		std::fs::create_dir_all(dst)?;
+		for entry in std::fs::read_dir(src)? {
+			let entry = entry?;
+			copy(&entry.path(), &dst.join(entry.file_name()), ancestors)?;
+		}
+		ancestors.pop();
+	} else {
+		std::fs::copy(src, dst)?;
+	}
+	Ok(())
+}
+
+/// Get the path of a template that is a single string with an absolute path to a file that exists.
+fn absolute_path(template: &Value) -> Option<PathBuf> {
+	let [component] = template["components"].as_array()?.as_slice() else {
+		return None;
+	};
+	if component["kind"] != "string" {
+		return None;
+	}
+	let path = Path::new(component["value"].as_str()?);
+	(path.is_absolute() && path.exists()).then(|| path.to_owned())
+}
+
+/// Remove `.` and `..` components without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => (),
+			Component::ParentDir => {
+				normalized.pop();
+			},
+			component => normalized.push(component),
+		}
+	}
+	normalized
+}
+
+/// Get the relative path from the directory `from` to `to`. Both must be absolute and normalized.
+fn relative(from: &Path, to: &Path) -> PathBuf {
+	let from = from.components().collect::<Vec<_>>();
+	let to = to.components().collect::<Vec<_>>();
+	let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+	let mut path = PathBuf::new();
+	for _ in common..from.len() {
+		path.push("..");
+	}
+	for component in &to[common..] {
+		path.push(component);
+	}
+	path
+}
+
+#[cfg(test)]
+mod tests {
+	use super::relative;
+	use crate::Options;
+	use serde_json::json;
+	use std::{collections::BTreeMap, path::Path};
+
+	#[test]
+	fn bundle_and_explain() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let temp = dir.path().canonicalize().unwrap();
+
+		// Create artifacts, where a library in a directory is a symlink to another artifact.
+		let artifacts = temp.join("artifacts");
+		std::fs::create_dir_all(artifacts.join("dir_01lib/lib")).unwrap();
+		std::fs::write(artifacts.join("fil_01ld"), "").unwrap();
+		std::fs::write(artifacts.join("fil_01exe"), "").unwrap();
+		std::fs::write(artifacts.join("fil_01foo"), "").unwrap();
+		std::os::unix::fs::symlink("../../fil_01foo", artifacts.join("dir_01lib/lib/libfoo.so"))
+			.unwrap();
+
+		// Write a wrapper with a library path in the artifacts directory.
+		let wrapper = temp.join("program");
+		let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/elf64_lsb");
+		std::fs::copy(fixture, &wrapper).unwrap();
+		let string = |value: &str| json!({ "components": [{ "kind": "string", "value": value }] });
+		let artifact = |id: &str| json!({ "components": [{ "kind": "artifact", "value": id }] });
+		let library_path = format!("{}/dir_01lib/lib", artifacts.display());
+		let mut manifest = json!({
+			"interpreter": {
+				"kind": "ld-linux",
+				"path": artifact("fil_01ld"),
+				"libraryPaths": [string(&library_path)],
+			},
+			"executable": { "kind": "path", "value": artifact("fil_01exe") },
+		});
+		crate::write_manifest(&wrapper, &manifest, None, Options::default()).unwrap();
+
+		// Bundle the wrapper and explain the copy.
+		let output = temp.join("bundle");
+		let copy = crate::bundle(&wrapper, None, &output, Some(&artifacts)).unwrap();
+		let explanation = crate::explain(&copy, None, &[], BTreeMap::new(), None).unwrap();
+		let bundled = output.join(".tangram/artifacts");
+		assert_eq!(
+			explanation.argv,
+			[
+				format!("{}/fil_01ld", bundled.display()),
+				"--inhibit-cache".to_owned(),
+				"--library-path".to_owned(),
+				format!("{}/dir_01lib/lib", bundled.display()),
+				"--argv0".to_owned(),
+				copy.display().to_string(),
+				format!("{}/fil_01exe", bundled.display()),
+			]
+		);
+		assert!(bundled.join("dir_01lib/lib/libfoo.so").exists());
+
+		// A library path outside the artifacts directory is not copied.
+		manifest["interpreter"]["libraryPaths"] = json!([string(&temp.display().to_string())]);
+		crate::write_manifest(&wrapper, &manifest, None, Options::default()).unwrap();
+		let output = temp.join("rejected");
+		let error = crate::bundle(&wrapper, None, &output, Some(&artifacts)).unwrap_err();
+		assert!(
+			matches!(&error, crate::Error::Io(error) if error.to_string().contains("library path")),
+			"{error:?}"
+		);
+	}
+
+	#[test]
+	fn relative_paths() {
+		assert_eq!(
+			relative(Path::new("/a/b/c"), Path::new("/a/d/e")),
+			Path::new("../../d/e")
+		);
+		assert_eq!(relative(Path::new("/a"), Path::new("/a/b")), Path::new("b"));
+	}
+}
@@ -0,0 +1,332 @@
+use crate::{Codec, Error, Format, Result, decode, encode, encoding};
+use serde_json::{Map, Value, json};
+use std::path::{Path, PathBuf};
+
+/// A change to the environment or default arguments of a wrapper.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Edit {
+	/// Set an environment variable.
+	Set { key: String, value: String },
+
+	/// Unset an environment variable, including one inherited by the wrapper.
+	Unset { key: String },
+
+	/// Prefix an environment variable with a value and a separator.
+	Prepend {
+		key: String,
+		value: String,
+		separator: String,
+	},
+
+	/// Suffix an environment variable with a separator and a value.
+	Append {
+		key: String,
+		value: String,
+		separator: String,
+	},
+
+	/// Add a default argument after the existing ones.
+	Arg(String),
+
+	/// Remove the default arguments.
+	ClearArgs,
+}
+
+/// Read the manifest of a wrapper, change it, and overwrite it in place with its existing encoding and compression.
+pub fn edit_manifest<T: Codec>(
+	path: impl AsRef<Path>,
+	format: Option<Format>,
+	f: impl FnOnce(&mut T) -> Result<()>,
+) -> Result<()> {
+	crate::modify(path.as_ref(), |file| {
+		let binary_format = crate::create_format(file, format)?;
+		let location = binary_format
+			.read_manifest(file)?
+			.ok_or_else(|| Error::UnsupportedFormat("the file is not a wrapper".to_owned()))?;
+		let data = file.bytes(location, "manifest")?;
+		let options = encoding::layout(data)?.options;
+		let mut manifest: T = decode(data)?;
+		f(&mut manifest)?;
+		let data = encode(&manifest, options)?;
+		binary_format.overwrite_manifest(file, &data)?;
+		if binary_format.read_manifest(file)?.is_none() {
+			return Err(Error::BadFooter("the manifest could not be read back"));
+		}
+		Ok(())
+	})
+}
+
+/// Apply edits to the manifest of a wrapper in place. Paths in one of the artifact `roots` are stored as artifacts.
+pub fn edit(
+	path: impl AsRef<Path>,
+	format: Option<Format>,
+	edits: &[Edit],
+	roots: &[PathBuf],
+) -> Result<()> {
+	edit_manifest::<Value>(path, format, |manifest| {
+		apply_edits(manifest, edits, roots)?;
+		let embedded = manifest["executable"]["kind"] == "address";
+		crate::validate(manifest, embedded)
+	})
+}
+
+/// Get the artifact roots for a wrapper, the way `common`'s `ArtifactRoots` gets them for an executable: the roots in `TANGRAM_ARTIFACTS_PATH` if it is set, or else the `.tangram/artifacts` directories in the ancestors of the wrapper followed by the sandbox root.
+#[must_use]
+pub fn artifact_roots(wrapper: &Path) -> Vec<PathBuf> {
+	if let Some(roots) = artifact_roots::explicit() {
+		return roots;
+	}
+	let wrapper = wrapper
+		.canonicalize()
+		.unwrap_or_else(|_| wrapper.to_owned());
+	artifact_roots::candidates(&wrapper)
+		.into_iter()
+		.filter(|root| root.is_dir() || root == Path::new(artifact_roots::SANDBOX_ARTIFACTS_PATH))
+		.collect()
+}
+
+/// Apply edits to an untyped manifest. The values it writes have the shape of `tg::mutation::Data`, `tg::template::Data` and `tg::value::Data`. Paths in one of the artifact `roots` are stored as artifacts.
+pub fn apply_edits(manifest: &mut Value, edits: &[Edit], roots: &[PathBuf]) -> Result<()> {
+	let manifest = manifest
+		.as_object_mut()
+		.ok_or_else(|| Error::InvalidEdit("the manifest is not an object".to_owned()))?;
+	for edit in edits {
+		match edit {
+			Edit::Set { key, value } => {
+				env(manifest)?.insert(key.clone(), value_from_template(template(value, roots)));
+			},
+			Edit::Unset { key } => {
+				env(manifest)?.insert(key.clone(), mutation(json!({ "kind": "unset" })));
+			},
+			Edit::Prepend {
+				key,
+				value,
+				separator,
+			} => {
+				let env = env(manifest)?;
+				let existing = env.remove(key);
+				let value = join(existing, "prefix", template(value, roots), separator);
+				env.insert(key.clone(), value);
+			},
+			Edit::Append {
+				key,
+				value,
+				separator,
+			} => {
+				let env = env(manifest)?;
+				let existing = env.remove(key);
+				let value = join(existing, "suffix", template(value, roots), separator);
+				env.insert(key.clone(), value);
+			},
+			Edit::Arg(value) => {
+				let args = manifest
+					.entry("args")
+					.or_insert_with(|| Value::Array(Vec::new()))
+					.as_array_mut()
+					.ok_or_else(|| Error::InvalidEdit("the args are not an array".to_owned()))?;
+				args.push(template(value, roots));
+			},
+			Edit::ClearArgs => {
+				manifest.remove("args");
+			},
+		}
+	}
+	Ok(())
+}
+
+/// Get the map of a `set` mutation of the environment, creating it if the manifest has no environment.
+fn env(manifest: &mut Map<String, Value>) -> Result<&mut Map<String, Value>> {
+	let env = manifest
+		.entry("env")
+		.or_insert_with(|| json!({ "kind": "set", "value": { "kind": "map", "value": {} } }));
+	if env.get("kind").and_then(Value::as_str) != Some("set") {
+		return Err(Error::InvalidEdit(
+			"only an environment that is set to a map can be edited".to_owned(),
+		));
+	}
+	env.get_mut("value")
+		.filter(|value| value.get("kind").and_then(Value::as_str) == Some("map"))
+		.and_then(|value| value.get_mut("value"))
+		.and_then(Value::as_object_mut)
+		.ok_or_else(|| {
+			Error::InvalidEdit("only an environment that is set to a map can be edited".to_owned())
+		})
+}
+
+/// Combine the existing value of an environment variable with a prefix or suffix. Strings and templates are joined when the manifest is edited. Anything else is followed by a mutation that joins them when the wrapper runs.
+fn join(existing: Option<Value>, kind: &str, template: Value, separator: &str) -> Value {
+	let components = |template: Value| match template {
+		Value::Object(mut object) => object.remove("components").unwrap_or_default(),
+		_ => Value::Null,
+	};
+	let string = |value: &str| json!({ "kind": "string", "value": value });
+	let existing = match existing {
+		None => {
+			return mutation(json!({ "kind": kind, "template": template, "separator": separator }));
+		},
+		Some(Value::String(value)) => Some(json!({ "components": [string(&value)] })),
+		Some(Value::Object(object))
+			if object.get("kind").and_then(Value::as_str) == Some("template") =>
+		{
+			object.get("value").cloned()
+		},
+		Some(existing) => {
+			let mut mutations = match existing {
+				Value::Array(mutations) => mutations,
+				existing if is_mutation(&existing) => vec![existing],
+				existing => vec![mutation(json!({ "kind": "set", "value": existing }))],
+			};
+			mutations.push(mutation(
+				json!({ "kind": kind, "template": template, "separator": separator }),
+			));
+			return Value::Array(mutations);
+		},
+	};
+	let existing = existing.map(components).unwrap_or_default();
+	let (first, second) = if kind == "prefix" {
+		(components(template), existing)
+	} else {
+		(existing, components(template))
+	};
+	let mut joined: Vec<Value> = Vec::new();
+	let first = first.as_array().cloned().unwrap_or_default();
+	let second = second.as_array().cloned().unwrap_or_default();
+	for component in first.into_iter().chain([string(separator)]).chain(second) {
+		// Merge adjacent strings.
+		if let (Some(last), Some(value)) = (joined.last_mut(), component["value"].as_str())
+			&& last["kind"] == "string"
+			&& component["kind"] == "string"
+		{
+			last["value"] = Value::String(format!(
+				"{}{value}",
+				last["value"].as_str().unwrap_or_default()
+			));
+			continue;
+		}
+		joined.push(component);
+	}
+	value_from_template(json!({ "components": joined }))
+}
+
+fn mutation(value: Value) -> Value {
+	tagged("mutation", value)
+}
+
+/// Create a value with a kind, such as a `tg::value::Data` mutation or template.
+fn tagged(kind: &str, value: Value) -> Value {
+	let mut object = Map::new();
+	object.insert("kind".to_owned(), Value::String(kind.to_owned()));
+	object.insert("value".to_owned(), value);
+	Value::Object(object)
+}
+
+fn is_mutation(value: &Value) -> bool {
+	value.get("kind").and_then(Value::as_str) == Some("mutation")
+}
+
+/// Store a template that is a single string as a string value.
+fn value_from_template(template: Value) -> Value {
+	match template["components"].as_array().map(Vec::as_slice) {
+		Some([component]) if component["kind"] == "string" => component["value"].clone(),
+		_ => tagged("template", template),
+	}
+}
+
+/// Unrender a string into a template, replacing the paths of artifacts in the first of the `roots` that it refers to with artifact components.
+pub(crate) fn template(string: &str, roots: &[PathBuf]) -> Value {
+	let mut components = Vec::new();
+	let mut rest = string;
+	let root = roots
+		.iter()
+		.map(|root| format!("{}/", root.display()))
+		.find(|root| string.contains(root.as_str()));
+	if let Some(root) = root {
+		while let Some(start) = rest.find(&root) {
+			let after = &rest[start + root.len()..];
+			let end = after.find('/').unwrap_or(after.len());
+			let id = &after[..end];
+			if !crate::inspect::is_artifact_id(id) {
+				break;
+			}
+			if start > 0 {
+				components.push(json!({ "kind": "string", "value": &rest[..start] }));
+			}
+			components.push(json!({ "kind": "artifact", "value": id }));
+			rest = &after[end..];
+		}
+	}
+	if !rest.is_empty() || components.is_empty() {
+		components.push(json!({ "kind": "string", "value": rest }));
+	}
+	json!({ "components": components })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Edit, apply_edits};
+	use serde_json::json;
+	use std::path::PathBuf;
+
+	#[test]
+	fn apply() {
+		let mut manifest = json!({
+			"executable": { "kind": "path", "value": { "components": [] } },
+			"env": { "kind": "set", "value": { "kind": "map", "value": { "PATH": "/bin" } } },
+			"args": [{ "components": [{ "kind": "string", "value": "--old" }] }],
+		});
+		let edits = [
+			Edit::Prepend {
+				key: "PATH".to_owned(),
+				value: "/artifacts/dir_01abc/bin".to_owned(),
+				separator: ":".to_owned(),
+			},
+			Edit::Append {
+				key: "CFLAGS".to_owned(),
+				value: "-O2".to_owned(),
+				separator: " ".to_owned(),
+			},
+			Edit::Unset {
+				key: "HOME".to_owned(),
+			},
+			Edit::ClearArgs,
+			Edit::Arg("--new".to_owned()),
+		];
+		let roots = [
+			PathBuf::from("/checkout/.tangram/artifacts"),
+			PathBuf::from("/artifacts"),
+		];
+		apply_edits(&mut manifest, &edits, &roots).unwrap();
+		let env = &manifest["env"]["value"]["value"];
+		assert_eq!(
+			env["PATH"],
+			json!({
+				"kind": "template",
+				"value": {
+					"components": [
+						{ "kind": "artifact", "value": "dir_01abc" },
+						{ "kind": "string", "value": "/bin:/bin" },
+					],
+				},
+			})
+		);
+		assert_eq!(
+			env["CFLAGS"],
+			json!({
+				"kind": "mutation",
+				"value": {
+					"kind": "suffix",
+					"separator": " ",
+					"template": { "components": [{ "kind": "string", "value": "-O2" }] },
+				},
+			})
+		);
+		assert_eq!(
+			env["HOME"],
+			json!({ "kind": "mutation", "value": { "kind": "unset" } })
+		);
+		assert_eq!(
+			manifest["args"],
+			json!([{ "components": [{ "kind": "string", "value": "--new" }] }])
+		);
+	}
+}
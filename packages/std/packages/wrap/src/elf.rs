@@ -1,22 +1,28 @@
 use crate::{
-	BinaryFormat, FileLocation,
+	BinaryFormat, Error, FileLocation, Result, Section, Segment,
 	elf::sys::{Elf32_Ehdr, Elf32_Shdr},
 	file::File,
 };
 use num::ToPrimitive as _;
 use paste::paste;
-use std::{ffi::CStr, path::Path};
-use zerocopy::{FromBytes, IntoBytes};
+use std::ffi::CStr;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 #[allow(warnings, clippy::pedantic, clippy::all)]
 pub(crate) mod sys;
 use sys::{
-	EI_CLASS, EI_DATA, ELFCLASS32, ELFCLASS64, ELFDATA2LSB, ELFMAG, Elf32_Off, Elf32_Phdr,
-	Elf64_Ehdr, Elf64_Off, Elf64_Phdr, Elf64_Shdr,
+	EI_CLASS, EI_DATA, ELFCLASS32, ELFCLASS64, ELFDATA2LSB, ELFDATA2MSB, ELFMAG, Elf32_Nhdr,
+	Elf32_Phdr, Elf64_Ehdr, Elf64_Phdr, Elf64_Shdr,
 };
 
 const TANGRAM_WRAPPER_SECTION_NAME: &CStr = c".text.tg-wrapper";
 const TANGRAM_MANIFEST_SECTION_NAME: &CStr = c".note.tg-manifest";
 
+/// The owner name of the note that holds the manifest.
+const TANGRAM_NOTE_NAME: &[u8] = b"tangram\0";
+
+/// The type of the note that holds the manifest.
+const NT_TANGRAM_MANIFEST: u32 = 0x7467;
+
 #[derive(Default)]
 pub struct Elf32;
 
@@ -25,10 +31,11 @@ pub struct Elf64;
 
 impl Elf32 {
 	fn matches(&self, file: &File) -> bool {
-		let header = self.elf_header(file);
-		header.e_ident[0..4] == ELFMAG[0..4]
-			&& header.e_ident[EI_CLASS] == ELFCLASS32
-			&& header.e_ident[EI_DATA] == ELFDATA2LSB
+		self.elf_header(file).is_ok_and(|header| {
+			header.e_ident[0..4] == ELFMAG[0..4]
+				&& header.e_ident[EI_CLASS] == ELFCLASS32
+				&& matches!(header.e_ident[EI_DATA], ELFDATA2LSB | ELFDATA2MSB)
+		})
 	}
 
 	#[allow(clippy::unused_self)]
@@ -39,10 +46,11 @@ impl Elf32 {
 
 impl Elf64 {
 	fn matches(&self, file: &File) -> bool {
-		let header = self.elf_header(file);
-		header.e_ident[0..4] == ELFMAG[0..4]
-			&& header.e_ident[EI_CLASS] == ELFCLASS64
-			&& header.e_ident[EI_DATA] == ELFDATA2LSB
+		self.elf_header(file).is_ok_and(|header| {
+			header.e_ident[0..4] == ELFMAG[0..4]
+				&& header.e_ident[EI_CLASS] == ELFCLASS64
+				&& matches!(header.e_ident[EI_DATA], ELFDATA2LSB | ELFDATA2MSB)
+		})
 	}
 
 	#[allow(clippy::unused_self)]
@@ -54,369 +62,626 @@ impl Elf64 {
 macro_rules! impl_elf {
 	($elf:ident) => {
 		impl $elf {
-			fn elf_header<'a>(&self, file: &'a File) -> &'a paste! {[<$elf _Ehdr>]} {
-				paste! {[<$elf _Ehdr>]::ref_from_bytes(&file[0..size_of::<[<$elf _Ehdr>]>()])}
-					.expect("expected an header")
+			fn elf_header(&self, file: &File) -> Result<paste! {[<$elf _Ehdr>]}> {
+				read(file, 0)
+			}
+
+			fn write_elf_header(
+				&self,
+				file: &mut File,
+				header: paste! {[<$elf _Ehdr>]},
+			) -> Result<()> {
+				write(file, 0, header)
 			}
 
-			fn elf_header_mut<'a>(&self, file: &'a mut File) -> &'a mut paste! {[<$elf _Ehdr>]} {
-				paste! {[<$elf _Ehdr>]::mut_from_bytes(&mut file[0..size_of::<[<$elf _Ehdr>]>()])}
-					.expect("expected an header")
+			fn section_header(
+				&self,
+				file: &File,
+				offset: usize,
+			) -> Result<paste! {[<$elf _Shdr>]}> {
+				read(file, offset)
 			}
 
-			fn section_string_table_index(&self, file: &File) -> usize {
-				let ehdr = self.elf_header(file);
-				let mut index = usize::try_from(ehdr.e_shstrndx).unwrap();
+			fn program_header(
+				&self,
+				file: &File,
+				offset: usize,
+			) -> Result<paste! {[<$elf _Phdr>]}> {
+				read(file, offset)
+			}
+
+			fn section_string_table_index(&self, file: &File) -> Result<usize> {
+				let ehdr = self.elf_header(file)?;
+				let mut index = usize::from(ehdr.e_shstrndx);
 				if index == sys::SHN_XINDEX {
-					let offset = usize::try_from(ehdr.e_shoff).unwrap();
-					let length = usize::try_from(ehdr.e_shentsize).unwrap();
-					index =
-						paste! {[<$elf _Shdr>]::ref_from_bytes(&file[offset..(offset + length)]) }
-							.expect("expected a section header")
-							.sh_link
-							.try_into()
-							.unwrap();
+					let offset = ehdr.e_shoff.to_usize().unwrap();
+					index = self
+						.section_header(file, offset)?
+						.sh_link
+						.to_usize()
+						.unwrap();
 				}
-				index
+				Ok(index)
 			}
 
-			fn section_string_table(&self, file: &File) -> FileLocation {
-				let ehdr = self.elf_header(file);
-				let section_string_table_index = self.section_string_table_index(file);
+			fn section_string_table(&self, file: &File) -> Result<FileLocation> {
+				let ehdr = self.elf_header(file)?;
+				let section_string_table_index = self.section_string_table_index(file)?;
+				let offset = section_string_table_index
+					.checked_mul(size_of::<paste! {[<$elf _Shdr>]}>())
+					.and_then(|offset| offset.checked_add(ehdr.e_shoff.to_usize().unwrap()))
+					.ok_or(Error::TruncatedHeader("section header table"))?;
+				let section_header = self.section_header(file, offset)?;
 				let location = FileLocation {
-					offset: ehdr.e_shoff.to_usize().unwrap()
-						+ section_string_table_index * size_of::<paste! {[<$elf _Shdr>]}>(),
-					length: size_of::<paste! {[<$elf _Shdr>]}>(),
-				};
-				let section_header = paste! {[<$elf _Shdr>]::read_from_bytes(&file[location])}
-					.expect("expected a section header");
-				FileLocation {
 					offset: section_header.sh_offset.to_usize().unwrap(),
 					length: section_header.sh_size.to_usize().unwrap(),
-				}
+				};
+				file.bytes(location, "section string table")?;
+				Ok(location)
 			}
 
-			fn section_header_table(&self, file: &File) -> FileLocation {
-				let ehdr = self.elf_header(file);
-				FileLocation {
+			fn section_header_table(&self, file: &File) -> Result<FileLocation> {
+				let ehdr = self.elf_header(file)?;
+				let location = FileLocation {
 					offset: ehdr.e_shoff.to_usize().unwrap(),
-					length: (ehdr.e_shnum * ehdr.e_shentsize).to_usize().unwrap(),
-				}
+					length: usize::from(ehdr.e_shnum) * size_of::<paste! {[<$elf _Shdr>]}>(),
+				};
+				file.bytes(location, "section header table")?;
+				Ok(location)
 			}
 
-			#[allow(clippy::too_many_lines)]
-			fn embed(&self, executable: &Path, manifest: &[u8]) -> std::io::Result<()> {
-				let wrapper_bin_path = crate::wrapper_bin_path()
-					.ok_or_else(|| std::io::Error::other("missing wrapper bin"))?;
-				let wrapper_exe_path = crate::wrapper_exe_path()
-					.ok_or_else(|| std::io::Error::other("missing wrapper exe"))?;
+			/// Whether the file has a section header table. Tools like `llvm-objcopy --strip-sections` remove it.
+			fn has_section_headers(&self, file: &File) -> Result<bool> {
+				let ehdr = self.elf_header(file)?;
+				Ok(ehdr.e_shoff != 0 && ehdr.e_shnum != 0)
+			}
 
-				let shdr_size = size_of::<paste! {[<$elf _Shdr>]}>();
+			fn program_header_table(&self, file: &File) -> Result<FileLocation> {
+				let ehdr = self.elf_header(file)?;
+				let location = FileLocation {
+					offset: ehdr.e_phoff.to_usize().unwrap(),
+					length: usize::from(ehdr.e_phnum) * size_of::<paste! {[<$elf _Phdr>]}>(),
+				};
+				file.bytes(location, "program header table")?;
+				Ok(location)
+			}
+
+			fn program_headers(&self, file: &File) -> Result<Vec<paste! {[<$elf _Phdr>]}>> {
 				let phdr_size = size_of::<paste! {[<$elf _Phdr>]}>();
+				self.program_header_table(file)?
+					.range()
+					.step_by(phdr_size)
+					.map(|offset| self.program_header(file, offset))
+					.collect()
+			}
+
+			/// Get the locations of the `PT_NOTE` segments.
+			fn note_segments(&self, file: &File) -> Result<Vec<FileLocation>> {
+				Ok(self
+					.program_headers(file)?
+					.into_iter()
+					.filter(|phdr| phdr.p_type == sys::PT_NOTE)
+					.map(|phdr| FileLocation {
+						offset: phdr.p_offset.to_usize().unwrap(),
+						length: phdr.p_filesz.to_usize().unwrap(),
+					})
+					.collect())
+			}
 
-				// Check if wrapper and manifest sections exist; add them with objcopy if not.
-				let file = File::open(executable, true)?;
-				let string_table_loc = self.section_string_table(&file);
-				let string_table = file[string_table_loc].to_vec();
-				let ehdr = self.elf_header(&file);
-				let shoff = ehdr.e_shoff.to_usize().unwrap();
-				let shnum = ehdr.e_shnum.to_usize().unwrap();
-
-				let mut needs_wrapper = true;
-				let mut needs_manifest = true;
-				for i in 0..shnum {
-					let offset = shoff + i * shdr_size;
-					let shdr = paste! {[<$elf _Shdr>]::read_from_bytes(&file[offset..offset + shdr_size])}
-						.expect("invalid section header");
-					let name_offset = shdr.sh_name.to_usize().unwrap();
-					if name_offset >= string_table.len() {
+			/// Find the offsets of the section headers of the wrapper and manifest sections.
+			fn find_sections(&self, file: &File) -> Result<(Option<usize>, Option<usize>)> {
+				if !self.has_section_headers(file)? {
+					return Ok((None, None));
+				}
+				let string_table =
+					file.bytes(self.section_string_table(file)?, "section string table")?;
+				let section_header_table = self.section_header_table(file)?;
+				let shdr_size = size_of::<paste! {[<$elf _Shdr>]}>();
+				let mut wrapper = None;
+				let mut manifest = None;
+				for offset in section_header_table.range().step_by(shdr_size) {
+					let shdr = self.section_header(file, offset)?;
+					let Some(name) = section_name(string_table, shdr.sh_name) else {
 						continue;
-					}
-					if let Ok(name) = CStr::from_bytes_until_nul(&string_table[name_offset..]) {
-						if name == TANGRAM_WRAPPER_SECTION_NAME {
-							needs_wrapper = false;
-						} else if name == TANGRAM_MANIFEST_SECTION_NAME {
-							needs_manifest = false;
-						}
+					};
+					if name == TANGRAM_WRAPPER_SECTION_NAME {
+						wrapper = Some(offset);
+					} else if name == TANGRAM_MANIFEST_SECTION_NAME {
+						manifest = Some(offset);
 					}
 				}
-				drop(file);
-
-				if needs_wrapper || needs_manifest {
-					let objcopy = crate::objcopy_path();
-					let mut cmd = std::process::Command::new(&objcopy);
-					if needs_wrapper {
-						cmd.arg(format!(
-							"--add-section={}=/dev/null",
-							TANGRAM_WRAPPER_SECTION_NAME.to_str().unwrap()
-						));
-					}
-					if needs_manifest {
-						cmd.arg(format!(
-							"--add-section={}=/dev/null",
-							TANGRAM_MANIFEST_SECTION_NAME.to_str().unwrap()
-						));
+				Ok((wrapper, manifest))
+			}
+
+			/// Find the offset of the section header with the given name.
+			fn find_section(&self, file: &File, name: &CStr) -> Result<Option<usize>> {
+				if !self.has_section_headers(file)? {
+					return Ok(None);
+				}
+				let string_table =
+					file.bytes(self.section_string_table(file)?, "section string table")?;
+				let shdr_size = size_of::<paste! {[<$elf _Shdr>]}>();
+				for offset in self.section_header_table(file)?.range().step_by(shdr_size) {
+					let shdr = self.section_header(file, offset)?;
+					if section_name(string_table, shdr.sh_name) == Some(name) {
+						return Ok(Some(offset));
 					}
-					cmd.arg(executable);
-					let output = cmd.output()?;
-					if !output.status.success() {
-						tracing::error!(
-							stdout = %String::from_utf8_lossy(&output.stdout),
-							stderr = %String::from_utf8_lossy(&output.stderr),
-							"objcopy failed",
-						);
-						return Err(std::io::Error::other("objcopy failed"));
+				}
+				Ok(None)
+			}
+
+			/// Add sections with the given names, types and data. The data, a new section string table and a new section header table are appended to the file so that no existing data moves. The old tables are removed if nothing follows them, and are otherwise left in place unreferenced.
+			fn add_sections(
+				&self,
+				file: &mut File,
+				sections: &[(&CStr, u32, &[u8])],
+			) -> Result<()> {
+				let shdr_size = size_of::<paste! {[<$elf _Shdr>]}>();
+
+				// Get the string table and section table locations.
+				let string_table_index = self.section_string_table_index(file)?;
+				let string_table_location = self.section_string_table(file)?;
+				let section_table_location = self.section_header_table(file)?;
+
+				// Get the actual data.
+				let mut string_table = file
+					.bytes(string_table_location, "section string table")?
+					.to_vec();
+				let mut section_table = file
+					.bytes(section_table_location, "section header table")?
+					.to_vec();
+
+				// Remove the old tables if they are at the end of the file.
+				if section_table_location.end() == file.file_size()?.to_usize().unwrap() {
+					file.delete(section_table_location)?;
+					if string_table_location.end() == file.file_size()?.to_usize().unwrap() {
+						file.delete(string_table_location)?;
 					}
 				}
 
+				// Append the data of each section and create its header.
+				for (name, sh_type, data) in sections {
+					let name_index = string_table.len();
+					string_table.extend_from_slice(name.to_bytes_with_nul());
+					let alignment = if *sh_type == sys::SHT_NOTE { 4 } else { 1 };
+					let file_size = file.file_size()?.to_usize().unwrap();
+					let offset = align(file_size, alignment);
+					file.append(&vec![0u8; offset - file_size])?;
+					file.append(data)?;
+					let header = paste! {[<$elf _Shdr>]{
+						sh_name: crate::convert(name_index)?,
+						sh_type: *sh_type,
+						sh_flags: 0,
+						sh_addr: 0,
+						sh_offset: crate::convert(offset)?,
+						sh_size: crate::convert(data.len())?,
+						sh_link: 0,
+						sh_info: 0,
+						sh_addralign: crate::convert(alignment)?,
+						sh_entsize: 0,
+					}};
+					section_table.extend_from_slice(file_order(file, header)?.as_bytes());
+				}
+
+				// Append the new string table.
+				let string_table_offset = file.file_size()?;
+				file.append(&string_table)?;
+				let offset = string_table_index * shdr_size;
+				let chunk = section_table
+					.get_mut(offset..offset + shdr_size)
+					.ok_or(Error::TruncatedHeader("section header"))?;
+				let header = paste! {[<$elf _Shdr>]::read_from_bytes(chunk)}
+					.map_err(|_| Error::TruncatedHeader("section header"))?;
+				let mut header = file_order(file, header)?;
+				header.sh_offset = crate::convert(string_table_offset)?;
+				header.sh_size = crate::convert(string_table.len())?;
+				chunk.copy_from_slice(file_order(file, header)?.as_bytes());
+
+				// Append the new section table.
+				let file_size = file.file_size()?.to_usize().unwrap();
+				let section_table_offset = align(file_size, align_of::<paste! {[<$elf _Shdr>]}>());
+				file.append(&vec![0u8; section_table_offset - file_size])?;
+				file.append(&section_table)?;
+
+				// Update the elf header.
+				let mut ehdr = self.elf_header(file)?;
+				ehdr.e_shoff = crate::convert(section_table_offset)?;
+				ehdr.e_shnum = crate::convert(section_table.len() / shdr_size)?;
+				self.write_elf_header(file, ehdr)?;
+				Ok(())
+			}
+
+			/// Embed the wrapper and manifest in a new segment at the end of the file.
+			fn embed(&self, file: &mut File, manifest: &[u8]) -> Result<()> {
+				// Select the wrapper for the system of the file.
+				let system = BinaryFormat::system(self, file)?;
+				let (wrapper_exe_path, wrapper_bin_path) = crate::wrapper_paths(&system)?;
+
+				// Add the wrapper and manifest sections if they are missing.
+				let (wrapper_section, manifest_section) = self.find_sections(file)?;
+				let mut missing = Vec::new();
+				if wrapper_section.is_none() {
+					missing.push((
+						TANGRAM_WRAPPER_SECTION_NAME,
+						sys::SHT_PROGBITS,
+						[].as_slice(),
+					));
+				}
+				if manifest_section.is_none() {
+					missing.push((TANGRAM_MANIFEST_SECTION_NAME, sys::SHT_NOTE, [].as_slice()));
+				}
+				if !missing.is_empty() {
+					self.add_sections(file, &missing)?;
+				}
+
 				// Read wrapper binary and get entrypoint from wrapper ELF.
 				let wrapper_bin = std::fs::read(&wrapper_bin_path)?;
 				let wrapper_entry = {
-					let wrapper_exe = File::open(&wrapper_exe_path, true)?;
-					self.elf_header(&wrapper_exe).e_entry
+					let wrapper_exe = File::open(&wrapper_exe_path)?;
+					let wrapper_system =
+						crate::create_format(&wrapper_exe, None)?.system(&wrapper_exe)?;
+					if wrapper_system != system {
+						return Err(Error::WrapperMismatch {
+							wrapper: wrapper_system,
+							binary: system,
+						});
+					}
+					self.elf_header(&wrapper_exe)?.e_entry
 				};
+				self.append_segment(file, Some((&wrapper_bin, wrapper_entry.into())), manifest)
+			}
 
-				// Open the executable for modification.
-				let mut file = File::open(executable, false)?;
+			/// Append a segment that holds the wrapper if there is one, a new program header table and the manifest note, so that the note is loaded and can be found through a `PT_NOTE` header even if the section headers are stripped. The new table adds the segment after the last `PT_LOAD` header and a `PT_NOTE` header for the manifest, and points `PT_PHDR` at itself. With a wrapper, the segment replaces `PT_INTERP` and holds the entrypoint. The original table is left in place so that the wrapper can be removed.
+			#[allow(clippy::too_many_lines)]
+			fn append_segment(
+				&self,
+				file: &mut File,
+				wrapper: Option<(&[u8], u64)>,
+				manifest: &[u8],
+			) -> Result<()> {
+				let phdr_size = size_of::<paste! {[<$elf _Phdr>]}>();
+				let wrapper_bin = wrapper.map_or([].as_slice(), |(bin, _)| bin);
 				let file_size = file.file_size()?.to_usize().unwrap();
 
 				// --- Analysis ---
-				let (phdr_offset, phdr_count) = {
-					let ehdr = self.elf_header(&file);
-					(
-						ehdr.e_phoff.to_usize().unwrap(),
-						ehdr.e_phnum.to_usize().unwrap(),
-					)
-				};
+				let phdrs = self.program_headers(file)?;
 
-				// Find PT_INTERP header and compute max virtual address / alignment.
-				let mut pt_interp_index: Option<usize> = None;
+				// Find the last PT_LOAD header and compute max virtual address / alignment.
+				let mut last_load = None;
+				let mut interpreters = 0;
 				let mut max_vaddr = 0;
 				let mut max_align = 0;
-				for i in 0..phdr_count {
-					let off = phdr_offset + i * phdr_size;
-					let phdr = paste! {[<$elf _Phdr>]::read_from_bytes(&file[off..off + phdr_size])}
-						.expect("invalid program header");
+				for (i, phdr) in phdrs.iter().enumerate() {
 					if phdr.p_type == sys::PT_LOAD {
-						max_vaddr = max_vaddr.max(phdr.p_vaddr + phdr.p_memsz);
+						let end = phdr
+							.p_vaddr
+							.checked_add(phdr.p_memsz)
+							.ok_or(Error::TruncatedHeader("program header"))?;
+						max_vaddr = max_vaddr.max(end);
 						max_align = max_align.max(phdr.p_align);
+						last_load = Some(i);
 					}
-					if phdr.p_type == sys::PT_INTERP {
-						assert!(pt_interp_index.is_none(), "multiple interpreters found");
-						pt_interp_index = Some(i);
+					if phdr.p_type == sys::PT_INTERP && wrapper.is_some() {
+						interpreters += 1;
 					}
 				}
+				if interpreters > 1 {
+					return Err(Error::UnsupportedFormat(
+						"multiple interpreters found".to_owned(),
+					));
+				}
+				if !max_align.to_usize().unwrap().is_power_of_two() {
+					return Err(Error::UnsupportedFormat(
+						"invalid segment alignment".to_owned(),
+					));
+				}
+				let last_load = last_load.unwrap();
 
 				// Find wrapper and manifest section headers in a single pass.
-				let mut wrapper_shdr_offset: Option<usize> = None;
-				let mut manifest_shdr_offset: Option<usize> = None;
-				{
-					let string_table_loc = self.section_string_table(&file);
-					let string_table = file[string_table_loc].to_vec();
-					let ehdr = self.elf_header(&file);
-					let shoff = ehdr.e_shoff.to_usize().unwrap();
-					let shnum = ehdr.e_shnum.to_usize().unwrap();
-
-					for i in 0..shnum {
-						let offset = shoff + i * shdr_size;
-						let shdr = paste! {[<$elf _Shdr>]::read_from_bytes(&file[offset..offset + shdr_size])}
-							.expect("invalid section header");
-						let name_offset = shdr.sh_name.to_usize().unwrap();
-						if name_offset >= string_table.len() {
-							continue;
-						}
-						if let Ok(name) = CStr::from_bytes_until_nul(&string_table[name_offset..]) {
-							if name == TANGRAM_WRAPPER_SECTION_NAME {
-								wrapper_shdr_offset = Some(offset);
-							} else if name == TANGRAM_MANIFEST_SECTION_NAME {
-								manifest_shdr_offset = Some(offset);
-							}
-						}
-					}
-				}
+				let (wrapper_shdr_offset, manifest_shdr_offset) = self.find_sections(file)?;
 
 				// Get the offsets of each section header.
-				let wrapper_shdr_offset = wrapper_shdr_offset
-					.ok_or_else(|| std::io::Error::other("missing wrapper section"))?;
-				let manifest_shdr_offset = manifest_shdr_offset
-					.ok_or_else(|| std::io::Error::other("missing manifest section"))?;
-
-				// Compute the data layout.
-				let wrapper_data_size = wrapper_bin.len() + manifest.len();
-				let wrapper_vaddr = align(max_vaddr.to_usize().unwrap(), max_align.to_usize().unwrap())
-					.try_into()
-					.unwrap();
-				let wrapper_memsz = align(wrapper_data_size, max_align.to_usize().unwrap())
-					.try_into()
-					.unwrap();
-
-				// Determine wrapper offset and build new phdr table if needed.
-				let new_phdr_table: Option<(Vec<u8>, usize)>;
-				let wrapper_offset: usize;
-
-				if let Some(interpreter_index) = pt_interp_index {
-					// Reuse PT_INTERP header for the stub segment.
-					wrapper_offset = align(file_size, max_align.to_usize().unwrap());
-					new_phdr_table = None;
-
-					let stub_segment = paste! {[<$elf _Phdr>] {
-						p_type: sys::PT_LOAD,
-						p_flags: sys::PF_R | sys::PF_X,
-						p_offset: wrapper_offset.try_into().unwrap(),
-						p_vaddr: wrapper_vaddr,
-						p_paddr: wrapper_vaddr,
-						p_filesz: wrapper_data_size.try_into().unwrap(),
-						p_memsz: wrapper_memsz,
-						p_align: max_align,
-					}};
-					let offset = phdr_offset + interpreter_index * phdr_size;
-					file[offset..offset + phdr_size].copy_from_slice(stub_segment.as_bytes());
-				} else {
-					// Create a new section header if there's no PT_INTERP we can abuse.
-					let headers_offset = align(file_size, 64);
-					let new_count = phdr_count + 1;
-					let headers_size = new_count * phdr_size;
-					wrapper_offset = align(headers_offset + headers_size, max_align.to_usize().unwrap());
-
-					let stub_segment = paste! {[<$elf _Phdr>]{
-						p_type: sys::PT_LOAD,
-						p_flags: sys::PF_R | sys::PF_X,
-						p_offset: wrapper_offset.try_into().unwrap(),
-						p_vaddr: wrapper_vaddr,
-						p_paddr: wrapper_vaddr,
-						p_filesz: wrapper_data_size.try_into().unwrap(),
-						p_memsz: wrapper_memsz,
-						p_align: max_align,
-					}};
-
-					let mut bytes = Vec::with_capacity(headers_size);
-
-					// Copy existing loadable segments first.
-					for i in 0..phdr_count {
-						let off = phdr_offset + i * phdr_size;
-						let phdr = paste! {[<$elf _Phdr>]::read_from_bytes(&file[off..off + phdr_size])}.unwrap();
-						assert!(phdr.p_type != sys::PT_PHDR, "unexpected PT_PHDR");
-						if phdr.p_type == sys::PT_LOAD {
-							bytes.extend_from_slice(phdr.as_bytes());
-						}
+				let wrapper_shdr_offset = match (wrapper, wrapper_shdr_offset) {
+					(Some(_), None) => {
+						return Err(Error::MissingSection(
+							TANGRAM_WRAPPER_SECTION_NAME.to_str().unwrap(),
+						));
+					},
+					(Some(_), offset) => offset,
+					(None, _) => None,
+				};
+				let manifest_shdr_offset = manifest_shdr_offset.ok_or_else(|| {
+					Error::MissingSection(TANGRAM_MANIFEST_SECTION_NAME.to_str().unwrap())
+				})?;
+
+				// Compute the data layout: the wrapper, then the program header table, then the manifest note.
+				let note = note(file, manifest)?;
+				let wrapper_offset = align(file_size, max_align.to_usize().unwrap());
+				let headers_count = phdrs.len() - interpreters + 2;
+				let headers_offset = align(
+					wrapper_offset + wrapper_bin.len(),
+					align_of::<paste! {[<$elf _Phdr>]}>(),
+				);
+				let headers_size = headers_count * phdr_size;
+				let note_offset = headers_offset + headers_size;
+				let wrapper_data_size = note_offset + note.len() - wrapper_offset;
+				let wrapper_vaddr: u64 = crate::convert(align(
+					max_vaddr.to_usize().unwrap(),
+					max_align.to_usize().unwrap(),
+				))?;
+				let vaddr = |offset: usize| -> Result<_> {
+					crate::convert(wrapper_vaddr + (offset - wrapper_offset).to_u64().unwrap())
+				};
+				let wrapper_memsz =
+					crate::convert(align(wrapper_data_size, max_align.to_usize().unwrap()))?;
+
+				// Create the new program header table.
+				let stub_segment = paste! {[<$elf _Phdr>] {
+					p_type: sys::PT_LOAD,
+					p_flags: if wrapper.is_some() { sys::PF_R | sys::PF_X } else { sys::PF_R },
+					p_offset: crate::convert(wrapper_offset)?,
+					p_vaddr: vaddr(wrapper_offset)?,
+					p_paddr: vaddr(wrapper_offset)?,
+					p_filesz: crate::convert(wrapper_data_size)?,
+					p_memsz: wrapper_memsz,
+					p_align: max_align,
+				}};
+				let note_segment = paste! {[<$elf _Phdr>] {
+					p_type: sys::PT_NOTE,
+					p_flags: sys::PF_R,
+					p_offset: crate::convert(note_offset)?,
+					p_vaddr: vaddr(note_offset)?,
+					p_paddr: vaddr(note_offset)?,
+					p_filesz: crate::convert(note.len())?,
+					p_memsz: crate::convert(note.len())?,
+					p_align: 4,
+				}};
+				let mut headers = Vec::with_capacity(headers_size);
+				for (i, mut phdr) in phdrs.into_iter().enumerate() {
+					match phdr.p_type {
+						sys::PT_INTERP if wrapper.is_some() => continue,
+						sys::PT_PHDR => {
+							phdr.p_offset = crate::convert(headers_offset)?;
+							phdr.p_vaddr = vaddr(headers_offset)?;
+							phdr.p_paddr = phdr.p_vaddr;
+							phdr.p_filesz = crate::convert(headers_size)?;
+							phdr.p_memsz = phdr.p_filesz;
+						},
+						_ => (),
 					}
+					headers.extend_from_slice(file_order(file, phdr)?.as_bytes());
 
-					// Add the new stub segment.
-					bytes.extend_from_slice(stub_segment.as_bytes());
-
-					// Copy non-loadable segments.
-					for i in 0..phdr_count {
-						let off = phdr_offset + i * phdr_size;
-						let phdr = paste! {[<$elf _Phdr>]::read_from_bytes(&file[off..off + phdr_size])}.unwrap();
-						if phdr.p_type != sys::PT_LOAD {
-							bytes.extend_from_slice(phdr.as_bytes());
-						}
+					// The PT_LOAD headers must be sorted by address, so the stub segment follows the last one.
+					if i == last_load {
+						headers.extend_from_slice(file_order(file, stub_segment)?.as_bytes());
 					}
-
-					assert_eq!(bytes.len(), headers_size);
-					new_phdr_table = Some((bytes, headers_offset));
 				}
+				headers.extend_from_slice(file_order(file, note_segment)?.as_bytes());
+				debug_assert_eq!(headers.len(), headers_size);
 
 				// Patch section headers.
-				let mut wrapper_shdr = paste! {[<$elf _Shdr>]::read_from_bytes(
-					&file[wrapper_shdr_offset..wrapper_shdr_offset + shdr_size],
-				)}
-				.unwrap();
-				wrapper_shdr.sh_type = sys::SHT_PROGBITS;
-				wrapper_shdr.sh_flags = (sys::SHF_ALLOC | sys::SHF_EXECINSTR).try_into().unwrap();
-				wrapper_shdr.sh_addr = wrapper_vaddr;
-				wrapper_shdr.sh_offset = wrapper_offset.try_into().unwrap();
-				wrapper_shdr.sh_size = wrapper_data_size.try_into().unwrap();
-				wrapper_shdr.sh_link = 0;
-				wrapper_shdr.sh_addralign = max_align;
-				wrapper_shdr.sh_entsize = 0;
-				file[wrapper_shdr_offset..wrapper_shdr_offset + shdr_size]
-					.copy_from_slice(wrapper_shdr.as_bytes());
-
-				let mut manifest_shdr = paste! {[<$elf _Shdr>]::read_from_bytes(
-					&file[manifest_shdr_offset..manifest_shdr_offset + shdr_size],
-				)}
-				.unwrap();
+				if let Some(wrapper_shdr_offset) = wrapper_shdr_offset {
+					let mut wrapper_shdr = self.section_header(file, wrapper_shdr_offset)?;
+					wrapper_shdr.sh_type = sys::SHT_PROGBITS;
+					wrapper_shdr.sh_flags =
+						(sys::SHF_ALLOC | sys::SHF_EXECINSTR).try_into().unwrap();
+					wrapper_shdr.sh_addr = vaddr(wrapper_offset)?;
+					wrapper_shdr.sh_offset = crate::convert(wrapper_offset)?;
+					wrapper_shdr.sh_size = crate::convert(wrapper_bin.len())?;
+					wrapper_shdr.sh_link = 0;
+					wrapper_shdr.sh_addralign = max_align;
+					wrapper_shdr.sh_entsize = 0;
+					write(file, wrapper_shdr_offset, wrapper_shdr)?;
+				}
+
+				let mut manifest_shdr = self.section_header(file, manifest_shdr_offset)?;
 				manifest_shdr.sh_type = sys::SHT_NOTE;
-				manifest_shdr.sh_flags = 0;
-				manifest_shdr.sh_addr = (wrapper_vaddr.to_usize().unwrap() + wrapper_bin.len()).try_into().unwrap();
-				manifest_shdr.sh_offset =
-					(wrapper_offset.to_usize().unwrap() + wrapper_bin.len()).try_into().unwrap();
-				manifest_shdr.sh_size =
-					(manifest.len() + size_of::<crate::Footer>()).try_into().unwrap();
+				manifest_shdr.sh_flags = sys::SHF_ALLOC.try_into().unwrap();
+				manifest_shdr.sh_addr = vaddr(note_offset)?;
+				manifest_shdr.sh_offset = crate::convert(note_offset)?;
+				manifest_shdr.sh_size = crate::convert(note.len())?;
 				manifest_shdr.sh_link = 0;
-				manifest_shdr.sh_addralign = 0;
+				manifest_shdr.sh_addralign = 4;
 				manifest_shdr.sh_entsize = 0;
-				file[manifest_shdr_offset..manifest_shdr_offset + shdr_size]
-					.copy_from_slice(manifest_shdr.as_bytes());
-
-				// Create the footer.
-				let footer = crate::Footer {
-					size: manifest.len().to_u64().unwrap(),
-					version: crate::VERSION,
-					magic: crate::MAGIC,
-				};
+				write(file, manifest_shdr_offset, manifest_shdr)?;
 
-				// Patch the entrypoint.
-				let ehdr = self.elf_header_mut(&mut file);
-				ehdr.e_entry = wrapper_vaddr + wrapper_entry;
+				// Patch the entrypoint and the program header table.
+				let mut ehdr = self.elf_header(file)?;
+				if let Some((_, entry)) = wrapper {
+					ehdr.e_entry = vaddr(wrapper_offset)?
+						+ crate::convert::<_, paste! {sys::[<$elf _Addr>]}>(entry)?;
+				}
+				ehdr.e_phoff = crate::convert(headers_offset)?;
+				ehdr.e_phnum = crate::convert(headers_count)?;
+				self.write_elf_header(file, ehdr)?;
 
-				// Patch program header table or sort existing headers.
-				if let Some((_, headers_offset)) = &new_phdr_table {
-					let ehdr = self.elf_header_mut(&mut file);
-					ehdr.e_phoff = (*headers_offset).try_into().unwrap();
-					ehdr.e_phnum = (phdr_count + 1).to_u16().unwrap();
-				} else {
-					// Collect PT_LOAD indices and their raw bytes.
-					let mut load_positions = Vec::new();
-					let mut load_entries: Vec<Vec<u8>> = Vec::new();
-					for i in 0..phdr_count {
-						let off = phdr_offset + i * phdr_size;
-						let phdr = paste! {[<$elf _Phdr>]::ref_from_bytes(&file[off..off + phdr_size])}.unwrap();
-						if phdr.p_type == sys::PT_LOAD {
-							load_positions.push(i);
-							load_entries.push(file[off..off + phdr_size].to_vec());
-						}
-					}
+				// Append the wrapper binary, the program header table and the manifest note.
+				file.append(&vec![0u8; wrapper_offset - file_size])?;
+				file.append(&wrapper_bin)?;
+				file.append(&vec![
+					0u8;
+					headers_offset - wrapper_offset - wrapper_bin.len()
+				])?;
+				file.append(&headers)?;
+				file.append(&note)?;
 
-					load_entries.sort_unstable_by_key(|bytes| {
-						paste! {[<$elf _Phdr>]::ref_from_bytes(bytes)}.unwrap().p_vaddr
-					});
+				Ok(())
+			}
+		}
 
-					// Write sorted entries back to their original PT_LOAD positions.
-					for (&pos, entry) in load_positions.iter().zip(load_entries.iter()) {
-						let off = phdr_offset + pos * phdr_size;
-						file[off..off + phdr_size].copy_from_slice(entry);
-					}
+		impl $elf {
+			/// Remove an embedded wrapper, restoring the original entrypoint and program headers. Padding added before the wrapper is not removed.
+			#[allow(clippy::too_many_lines)]
+			fn unwrap(&self, file: &mut File, entrypoint: u64) -> Result<()> {
+				let shdr_size = size_of::<paste! {[<$elf _Shdr>]}>();
+				let phdr_size = size_of::<paste! {[<$elf _Phdr>]}>();
+
+				// Find the wrapper and manifest sections.
+				let (Some(wrapper_shdr_offset), manifest_shdr_offset) = self.find_sections(file)?
+				else {
+					return Err(Error::UnsupportedFormat(
+						"the file has no embedded wrapper".to_owned(),
+					));
+				};
+				let wrapper = self.section_header(file, wrapper_shdr_offset)?;
+				let mut end = range_end(wrapper.sh_offset, wrapper.sh_size, "section header")?;
+				if let Some(offset) = manifest_shdr_offset {
+					let manifest = self.section_header(file, offset)?;
+					end = end.max(range_end(
+						manifest.sh_offset,
+						manifest.sh_size,
+						"section header",
+					)?);
 				}
 
-				// Write new program header table if necessary.
-				if let Some((phdr_bytes, headers_offset)) = new_phdr_table {
-					let padding = headers_offset - file_size;
-					if padding > 0 {
-						file.append(&vec![0u8; padding])?;
+				// Find the segment that loads the wrapper.
+				let mut ehdr = self.elf_header(file)?;
+				let phdr_offset = ehdr.e_phoff.to_usize().unwrap();
+				let phdr_count = usize::from(ehdr.e_phnum);
+				let phdrs = (0..phdr_count)
+					.map(|i| self.program_header(file, phdr_offset + i * phdr_size))
+					.collect::<Result<Vec<_>>>()?;
+				let stub = phdrs
+					.iter()
+					.position(|phdr| {
+						phdr.p_type == sys::PT_LOAD
+							&& phdr.p_offset == wrapper.sh_offset
+							&& phdr.p_vaddr == wrapper.sh_addr
+					})
+					.ok_or_else(|| {
+						Error::UnsupportedFormat(
+							"the wrapper section is not loaded by a segment".to_owned(),
+						)
+					})?;
+
+				// Restore the program headers.
+				let interp = self.find_section(file, c".interp")?;
+				let has_interp = phdrs.iter().any(|phdr| phdr.p_type == sys::PT_INTERP);
+				let stub_range = phdrs[stub].p_offset.to_usize().unwrap()
+					..range_end(phdrs[stub].p_offset, phdrs[stub].p_filesz, "program header")?;
+				let mut restored_table = false;
+				if stub_range.contains(&phdr_offset) {
+					// The program header table is in the wrapper's segment. The original table follows the ELF header, and has the PT_INTERP header in place of the wrapper's segment and the manifest's PT_NOTE header.
+					let original = usize::from(ehdr.e_ehsize);
+					let count = phdr_count - 2 + usize::from(interp.is_some());
+					let intact = (0..count).all(|i| {
+						self.program_header(file, original + i * phdr_size)
+							.is_ok_and(|phdr| {
+								matches!(phdr.p_type, sys::PT_INTERP | sys::PT_PHDR)
+									|| phdrs.iter().any(|p| p.as_bytes() == phdr.as_bytes())
+							})
+					});
+					if !intact {
+						return Err(Error::UnsupportedFormat(
+							"the original program header table was overwritten".to_owned(),
+						));
 					}
-					file.append(&phdr_bytes)?;
-					let padding = wrapper_offset - headers_offset - phdr_bytes.len();
-					if padding > 0 {
-						file.append(&vec![0u8; padding])?;
+					ehdr.e_phoff = crate::convert(original)?;
+					ehdr.e_phnum = crate::convert(count)?;
+					restored_table = true;
+				} else if let (Some(interp), false) = (interp, has_interp) {
+					// The PT_INTERP header was replaced by the wrapper's segment and the PT_LOAD headers were sorted. PT_INTERP precedes every PT_LOAD, so it goes back in the first PT_LOAD position.
+					let interp = self.section_header(file, interp)?;
+					let positions = (0..phdr_count)
+						.filter(|i| phdrs[*i].p_type == sys::PT_LOAD)
+						.collect::<Vec<_>>();
+					let loads = phdrs
+						.iter()
+						.enumerate()
+						.filter(|(i, phdr)| *i != stub && phdr.p_type == sys::PT_LOAD)
+						.map(|(_, phdr)| *phdr)
+						.collect::<Vec<_>>();
+					let interp_segment = paste! {[<$elf _Phdr>] {
+						p_type: sys::PT_INTERP,
+						p_flags: sys::PF_R,
+						p_offset: interp.sh_offset,
+						p_vaddr: interp.sh_addr,
+						p_paddr: interp.sh_addr,
+						p_filesz: interp.sh_size,
+						p_memsz: interp.sh_size,
+						p_align: 1,
+					}};
+					write(file, phdr_offset + positions[0] * phdr_size, interp_segment)?;
+					for (position, load) in positions[1..].iter().zip(loads) {
+						write(file, phdr_offset + position * phdr_size, load)?;
 					}
 				} else {
-					// Pad to stub offset.
-					let padding = wrapper_offset - file_size;
-					if padding > 0 {
-						file.append(&vec![0u8; padding])?;
+					// A new program header table was appended. Use the original one if it is intact after the ELF header, and otherwise remove the wrapper's segment from the new one.
+					let phdrs = phdrs
+						.iter()
+						.enumerate()
+						.filter(|(i, _)| *i != stub)
+						.map(|(_, phdr)| *phdr)
+						.collect::<Vec<_>>();
+					let original = usize::from(ehdr.e_ehsize);
+					let intact = original != phdr_offset
+						&& (0..phdrs.len()).all(|i| {
+							self.program_header(file, original + i * phdr_size)
+								.is_ok_and(|phdr| {
+									phdrs.iter().any(|p| p.as_bytes() == phdr.as_bytes())
+								})
+						});
+					if intact {
+						ehdr.e_phoff = crate::convert(original)?;
+						restored_table = true;
+					} else {
+						for (i, phdr) in phdrs.iter().enumerate() {
+							write(file, phdr_offset + i * phdr_size, *phdr)?;
+						}
 					}
+					ehdr.e_phnum = crate::convert(phdrs.len())?;
 				}
 
-				// Append wrapper binary.
-				file.append(&wrapper_bin)?;
-
-				// Append manifest.
-				file.append(manifest)?;
-
-				// Append footer.
-				file.append(footer.as_bytes())?;
+				// Remove the wrapper and manifest section headers, updating the indices of the sections that follow them.
+				let section_table = self.section_header_table(file)?;
+				let removed = [Some(wrapper_shdr_offset), manifest_shdr_offset]
+					.into_iter()
+					.flatten()
+					.map(|offset| (offset - section_table.offset) / shdr_size)
+					.collect::<Vec<_>>();
+				let reindex = |index: usize| {
+					index - removed.iter().filter(|removed| **removed < index).count()
+				};
+				let mut headers = Vec::new();
+				for (index, offset) in section_table.range().step_by(shdr_size).enumerate() {
+					if removed.contains(&index) {
+						continue;
+					}
+					let mut header = self.section_header(file, offset)?;
+					header.sh_link = crate::convert(reindex(header.sh_link.to_usize().unwrap()))?;
+					if matches!(header.sh_type, sys::SHT_REL | sys::SHT_RELA)
+						|| u64::from(header.sh_flags) & u64::from(sys::SHF_INFO_LINK) != 0
+					{
+						header.sh_info =
+							crate::convert(reindex(header.sh_info.to_usize().unwrap()))?;
+					}
+					headers.push(header);
+				}
+				for (index, header) in headers.iter().enumerate() {
+					write(file, section_table.offset + index * shdr_size, *header)?;
+				}
+				let start = section_table.offset + headers.len() * shdr_size;
+				file.patch(start, &vec![0; section_table.end() - start])?;
+				ehdr.e_shnum = crate::convert(headers.len())?;
+				ehdr.e_shstrndx = crate::convert(reindex(usize::from(ehdr.e_shstrndx)))?;
+
+				// Restore the entrypoint.
+				ehdr.e_entry = crate::convert(entrypoint)?;
+				self.write_elf_header(file, ehdr)?;
+
+				// Remove the wrapper, and the appended program header table if it is no longer used.
+				let start = if restored_table {
+					phdr_offset.min(wrapper.sh_offset.to_usize().unwrap())
+				} else {
+					wrapper.sh_offset.to_usize().unwrap()
+				};
+				let file_size = file.file_size()?.to_usize().unwrap();
+				if end == file_size && section_table.end() <= start {
+					file.delete(FileLocation {
+						offset: start,
+						length: file_size - start,
+					})?;
+				}
 
 				Ok(())
 			}
@@ -431,177 +696,207 @@ macro_rules! impl_elf {
 				self.name()
 			}
 
-			fn read_manifest(&self, file: &File) -> Option<FileLocation> {
-				// Convert the name.
-				let name = TANGRAM_MANIFEST_SECTION_NAME.to_owned();
-
-				// Get the index of the section containing the section-name string table.
-				let string_table = &file[self.section_string_table(file)];
-				let sections = &file[self.section_header_table(file)];
-
-				// Iterate the string table.
-				sections
-					.chunks_exact(size_of::<paste! {[<$elf _Shdr>]}>())
-					.map(|chunk| {
-						paste! {[<$elf _Shdr>]::read_from_bytes(chunk)}
-							.expect("expected a section_header")
-					})
-					.find_map(|section| {
-						let slice = &string_table[section.sh_name.to_usize().unwrap()..];
-						if slice[0] == 0 {
-							return None;
-						}
-						let section_name = CStr::from_bytes_until_nul(
-							&string_table[section.sh_name.to_usize().unwrap()..],
-						)
-						.expect("expected a null-terminated string");
-						if section_name == &name {
-							let location = FileLocation {
-								offset: section.sh_offset.to_usize().unwrap(),
-								length: section.sh_size.to_usize().unwrap(),
-							};
-							return Some(location);
-						}
-						None
-					})
+			fn system(&self, file: &File) -> Result<String> {
+				let ehdr = self.elf_header(file)?;
+				let arch = match u32::from(ehdr.e_machine) {
+					sys::EM_X86_64 => "x86_64",
+					sys::EM_AARCH64 => "aarch64",
+					sys::EM_386 => "i686",
+					sys::EM_ARM => "arm",
+					sys::EM_RISCV if ehdr.e_ident[EI_CLASS] == ELFCLASS64 => "riscv64",
+					sys::EM_RISCV => "riscv32",
+					sys::EM_PPC => "powerpc",
+					sys::EM_PPC64 => "powerpc64",
+					sys::EM_S390 => "s390x",
+					machine => {
+						return Err(Error::UnsupportedFormat(format!(
+							"unknown machine {machine:#x}"
+						)));
+					},
+				};
+				Ok(format!("{arch}-linux"))
 			}
 
-			fn write_manifest(&self, file: &mut File, data: &[u8]) {
-				let name = TANGRAM_MANIFEST_SECTION_NAME.to_bytes_with_nul();
-				let name_len = name.len();
-
-				// Get the string table and section table locations.
-				let string_table_index = self.section_string_table_index(file);
-				let string_table_location = self.section_string_table(file);
-				let mut section_table_location = self.section_header_table(file);
+			fn read_manifest(&self, file: &File) -> Result<Option<FileLocation>> {
+				let (_, manifest) = self.find_sections(file)?;
+				if let Some(offset) = manifest {
+					let section = self.section_header(file, offset)?;
+					let location = FileLocation {
+						offset: section.sh_offset.to_usize().unwrap(),
+						length: section.sh_size.to_usize().unwrap(),
+					};
+					file.bytes(location, "manifest section")?;
+
+					// Manifests written before the section became a note fill the whole section.
+					return Ok(Some(find_note(file, location)?.unwrap_or(location)));
+				}
 
-				// Get the actual data.
-				let mut string_table = file[string_table_location].to_vec();
-				let mut section_table = file[section_table_location].to_vec();
-
-				// Add to the string table.
-				let name_index = string_table.len();
-				string_table.extend_from_slice(name);
-				string_table.push(0);
-
-				// Update existing sections.
-				for (index, chunk) in section_table
-					.chunks_exact_mut(size_of::<paste! {[<$elf _Shdr>]}>())
-					.enumerate()
-				{
-					let header = paste! {[<$elf _Shdr>]::mut_from_bytes(chunk)}
-						.expect("expected a section header");
-					if index == string_table_index {
-						header.sh_size += <paste! {[<$elf _Off>]}>::try_from(name_len).unwrap();
-						continue;
-					}
-					// If this section occurs after the original string_table location, update its offset.
-					if header.sh_offset > string_table_location.end().try_into().unwrap() {
-						header.sh_offset = <paste! {[<$elf _Off>]}>::try_from(name_len).unwrap();
+				// Look for the note in the `PT_NOTE` segments, for example if the section headers were stripped.
+				for location in self.note_segments(file)? {
+					if let Some(location) = find_note(file, location)? {
+						return Ok(Some(location));
 					}
 				}
+				Ok(None)
+			}
 
-				// Now the hard part:
-				// Insert the new string table at the offset of the original.
-				file.insert(&string_table, string_table_location.offset as u64)
-					.expect("failed to insert string table");
-
-				// Delete the old section table.
-				section_table_location.offset += name_len;
-				file.delete(section_table_location)
-					.expect("failed to delete section table");
-
-				// Get the offset of the end of the file.
-				let new_section_offset = file.file_size().expect("failed to get the file size");
-
-				// Write the new section.
-				file.append(data)
-					.expect("failed to write new section to end of file");
-
-				// Create the new section.
-				let sh_type = sys::SHT_NOTE;
-				let sh_flags = 0;
-				let new_section_header = paste! {[<$elf _Shdr>]{
-					sh_type: sh_type .try_into().unwrap(),
-					sh_offset: new_section_offset .try_into().unwrap(),
-					sh_size: data.len() .try_into().unwrap(),
-					sh_addr: 0,
-					sh_name: name_index .try_into().unwrap(),
-					sh_flags: sh_flags .try_into().unwrap(),
-					sh_link: 0,
-					sh_info: 0,
-					sh_addralign: 0,
-					sh_entsize: 0,
-				}};
-
-				// Write the new section to the section table.
-				section_table.extend_from_slice(new_section_header.as_bytes());
-
-				// Get the offset of the new section table.
-				let section_table_offset = file.file_size().expect("failed to get the file size");
+			fn manifest_ranges(&self, file: &File) -> Result<Vec<FileLocation>> {
+				let ehdr = FileLocation {
+					offset: 0,
+					length: size_of::<paste! {[<$elf _Ehdr>]}>(),
+				};
+				let mut ranges = vec![ehdr, self.program_header_table(file)?];
+				if self.has_section_headers(file)? {
+					ranges.push(self.section_header_table(file)?);
+					ranges.push(self.section_string_table(file)?);
+				}
+				if self.find_sections(file)?.1.is_none() {
+					ranges.extend(self.note_segments(file)?);
+				}
+				Ok(ranges)
+			}
 
-				// Append the new section table.
-				file.append(&section_table)
-					.expect("failed to write the new section table to the file");
+			fn sections(&self, file: &File) -> Result<Vec<Section>> {
+				// Collect the program headers so each section can be matched to its segment.
+				let ehdr = self.elf_header(file)?;
+				let phdr_offset = ehdr.e_phoff.to_usize().unwrap();
+				let phdr_count = usize::from(ehdr.e_phnum);
+				let phdr_size = size_of::<paste! {[<$elf _Phdr>]}>();
+				let segments = (0..phdr_count)
+					.map(|i| {
+						let segment = self.program_header(file, phdr_offset + i * phdr_size)?;
+						let end = range_end(segment.p_offset, segment.p_filesz, "program header")?;
+						Ok((segment, end))
+					})
+					.collect::<Result<Vec<_>>>()?;
+
+				// Find the wrapper and manifest sections.
+				let (wrapper, manifest) = self.find_sections(file)?;
+				let string_table =
+					file.bytes(self.section_string_table(file)?, "section string table")?;
+				let mut sections = Vec::new();
+				for offset in [wrapper, manifest].into_iter().flatten() {
+					let section = self.section_header(file, offset)?;
+					let name = section_name(string_table, section.sh_name).unwrap_or_default();
+					let offset = section.sh_offset.to_usize().unwrap();
+					let length = section.sh_size.to_usize().unwrap();
+					let end = range_end(section.sh_offset, section.sh_size, "section header")?;
+					let address = (section.sh_addr != 0).then(|| section.sh_addr.into());
+					let segment = segments
+						.iter()
+						.find(|(segment, segment_end)| {
+							segment.p_filesz > 0
+								&& segment.p_type != sys::PT_PHDR
+								&& segment.p_offset.to_usize().unwrap() <= offset
+								&& end <= *segment_end
+						})
+						.map(|(segment, _)| Segment {
+							kind: segment_kind(segment.p_type),
+							offset: segment.p_offset.to_usize().unwrap(),
+							length: segment.p_filesz.to_usize().unwrap(),
+							address: segment.p_vaddr.into(),
+							memory_size: segment.p_memsz.into(),
+						});
+					sections.push(Section {
+						name: name.to_string_lossy().into_owned(),
+						offset,
+						length,
+						address,
+						segment,
+					});
+				}
+				Ok(sections)
+			}
 
-				// Update the elf header.
-				let ehdr = self.elf_header_mut(file);
-				ehdr.e_shnum += 1;
-				ehdr.e_shoff = <paste! {[<$elf _Off>]}>::try_from(section_table_offset).unwrap();
+			fn write_manifest(&self, file: &mut File, data: &[u8]) -> Result<()> {
+				// Add the manifest section if it is missing, and load the note like an embedded wrapper's.
+				if self.find_sections(file)?.1.is_none() {
+					self.add_sections(
+						file,
+						&[(TANGRAM_MANIFEST_SECTION_NAME, sys::SHT_NOTE, &[])],
+					)?;
+				}
+				self.append_segment(file, None, data)
 			}
 
-			fn overwrite_manifest(&self, file: &mut File, data: &[u8]) {
-				// Get the existing manifest location.
-				let Some(old) = self.read_manifest(file) else {
-					self.write_manifest(file, data);
-					return;
+			fn overwrite_manifest(&self, file: &mut File, data: &[u8]) -> Result<()> {
+				// Get the existing manifest section.
+				let (_, Some(manifest_shdr_offset)) = self.find_sections(file)? else {
+					return self.write_manifest(file, data);
 				};
+				let section = self.section_header(file, manifest_shdr_offset)?;
 
-				// Compute the difference of the two sections.
-				let diff = if data.len() >= old.length {
-					isize::try_from(data.len() - old.length).unwrap()
-				} else {
-					-isize::try_from(old.length - data.len()).unwrap()
+				// Manifests written by older versions are not loaded, so write them again.
+				if u64::from(section.sh_flags) & u64::from(sys::SHF_ALLOC) == 0 {
+					return self.write_manifest(file, data);
+				}
+				let old = FileLocation {
+					offset: section.sh_offset.to_usize().unwrap(),
+					length: section.sh_size.to_usize().unwrap(),
 				};
+				let data = note(file, data)?;
+				let data = data.as_slice();
 
-				// Get the string table and section table locations.
-				let string_table_location = self.section_string_table(file);
-				let section_table_location = self.section_header_table(file);
+				// Resize the segments that end with the manifest, such as the embedded wrapper's `PT_LOAD` and `PT_NOTE` segments.
+				let phdr_table = self.program_header_table(file)?;
+				let phdr_size = size_of::<paste! {[<$elf _Phdr>]}>();
+				for offset in phdr_table.range().step_by(phdr_size) {
+					let mut header = self.program_header(file, offset)?;
+					let start = header.p_offset.to_usize().unwrap();
+					let end = range_end(header.p_offset, header.p_filesz, "program header")?;
+					if header.p_filesz == 0 || start > old.offset || end != old.end() {
+						continue;
+					}
+					let filesz = end - start - old.length + data.len();
+					let memsz = header
+						.p_memsz
+						.to_usize()
+						.unwrap()
+						.checked_sub(old.length)
+						.ok_or(Error::TruncatedHeader("program header"))?
+						+ data.len();
+					header.p_filesz = crate::convert(filesz)?;
+					header.p_memsz = crate::convert(memsz.max(filesz))?;
+					write(file, offset, header)?;
+				}
 
-				// Get the actual data.
-				let string_table = file[string_table_location].to_vec();
-				let section_table = &mut file[section_table_location];
-
-				// Update existing sections.
-				for chunk in section_table.chunks_exact_mut(size_of::<paste! {[<$elf _Shdr>]}>()) {
-					let header = paste! {[<$elf _Shdr>]::mut_from_bytes(chunk)}
-						.expect("expected a section header");
-					let name = CStr::from_bytes_until_nul(
-						&string_table[usize::try_from(header.sh_name).unwrap()..],
-					)
-					.unwrap();
-					if name == TANGRAM_MANIFEST_SECTION_NAME {
-						let old_size = isize::try_from(header.sh_size).unwrap();
-						let new_size = old_size + diff;
-						header.sh_size = new_size.try_into().unwrap();
-						break;
+				// Update the size of the manifest section and the offsets of the sections that follow it.
+				let shift = |offset: usize| {
+					if offset >= old.end() {
+						offset - old.length + data.len()
+					} else {
+						offset
 					}
+				};
+				let shdr_size = size_of::<paste! {[<$elf _Shdr>]}>();
+				for offset in self.section_header_table(file)?.range().step_by(shdr_size) {
+					let mut header = self.section_header(file, offset)?;
+					if offset == manifest_shdr_offset {
+						header.sh_size = crate::convert(data.len())?;
+					} else {
+						header.sh_offset =
+							crate::convert(shift(header.sh_offset.to_usize().unwrap()))?;
+					}
+					write(file, offset, header)?;
 				}
 
 				// Update the header.
-				let old_offset = isize::try_from(self.elf_header(file).e_shoff).unwrap();
-				if old_offset >= isize::try_from(old.offset).unwrap() {
-					let new_offset = old_offset + diff;
-					self.elf_header_mut(file).e_shoff = new_offset.try_into().unwrap();
-				}
+				let mut ehdr = self.elf_header(file)?;
+				ehdr.e_shoff = crate::convert(shift(ehdr.e_shoff.to_usize().unwrap()))?;
+				ehdr.e_phoff = crate::convert(shift(ehdr.e_phoff.to_usize().unwrap()))?;
+				self.write_elf_header(file, ehdr)?;
 
 				// Replace it.
-				file.replace(old, data)
-					.expect("failed to overwrite existing manifest");
+				file.replace(old, data)?;
+				Ok(())
+			}
+
+			fn embed(&self, file: &mut File, data: &[u8]) -> Result<()> {
+				self.embed(file, data)
 			}
 
-			fn embed(&self, path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
-				self.embed(path, data)
+			fn unwrap(&self, file: &mut File, entrypoint: u64) -> Result<()> {
+				self.unwrap(file, entrypoint)
 			}
 		}
 	};
@@ -609,6 +904,425 @@ macro_rules! impl_elf {
 impl_elf!(Elf32);
 impl_elf!(Elf64);
 
+/// An ELF header whose fields are stored in the byte order of the file.
+trait Endian: FromBytes + IntoBytes + KnownLayout + Immutable + Copy {
+	/// Reverse the byte order of every field.
+	#[must_use]
+	fn swap_bytes(self) -> Self;
+}
+
+macro_rules! impl_endian {
+	($type:ty, $($field:ident),* $(,)?) => {
+		impl Endian for $type {
+			fn swap_bytes(mut self) -> Self {
+				$(self.$field = self.$field.swap_bytes();)*
+				self
+			}
+		}
+	};
+}
+
+impl_endian!(
+	Elf32_Ehdr,
+	e_type,
+	e_machine,
+	e_version,
+	e_entry,
+	e_phoff,
+	e_shoff,
+	e_flags,
+	e_ehsize,
+	e_phentsize,
+	e_phnum,
+	e_shentsize,
+	e_shnum,
+	e_shstrndx,
+);
+impl_endian!(
+	Elf64_Ehdr,
+	e_type,
+	e_machine,
+	e_version,
+	e_entry,
+	e_phoff,
+	e_shoff,
+	e_flags,
+	e_ehsize,
+	e_phentsize,
+	e_phnum,
+	e_shentsize,
+	e_shnum,
+	e_shstrndx,
+);
+impl_endian!(
+	Elf32_Shdr,
+	sh_name,
+	sh_type,
+	sh_flags,
+	sh_addr,
+	sh_offset,
+	sh_size,
+	sh_link,
+	sh_info,
+	sh_addralign,
+	sh_entsize,
+);
+impl_endian!(
+	Elf64_Shdr,
+	sh_name,
+	sh_type,
+	sh_flags,
+	sh_addr,
+	sh_offset,
+	sh_size,
+	sh_link,
+	sh_info,
+	sh_addralign,
+	sh_entsize,
+);
+impl_endian!(Elf32_Nhdr, n_namesz, n_descsz, n_type);
+impl_endian!(
+	Elf32_Phdr, p_type, p_offset, p_vaddr, p_paddr, p_filesz, p_memsz, p_flags, p_align,
+);
+impl_endian!(
+	Elf64_Phdr, p_type, p_flags, p_offset, p_vaddr, p_paddr, p_filesz, p_memsz, p_align,
+);
+
+/// Whether the byte order of the file differs from the byte order of this machine.
+fn is_swapped(file: &File) -> Result<bool> {
+	let ident = FileLocation {
+		offset: EI_DATA,
+		length: 1,
+	};
+	let big_endian = file.bytes(ident, "ELF header")? == [ELFDATA2MSB];
+	Ok(big_endian != cfg!(target_endian = "big"))
+}
+
+/// Convert a header between the byte order of the file and the byte order of this machine.
+fn file_order<T: Endian>(file: &File, value: T) -> Result<T> {
+	if is_swapped(file)? {
+		Ok(value.swap_bytes())
+	} else {
+		Ok(value)
+	}
+}
+
+/// Read a header, converting it to the byte order of this machine.
+fn read<T: Endian>(file: &File, offset: usize) -> Result<T> {
+	file_order(file, file.read_at_unaligned::<T>(offset)?)
+}
+
+/// Write a header, converting it to the byte order of the file.
+fn write<T: Endian>(file: &mut File, offset: usize, value: T) -> Result<()> {
+	let value = file_order(file, value)?;
+	file.patch(offset, value.as_bytes())
+}
+
+/// Wrap manifest data in an ELF note with the name "tangram", padded to four bytes.
+fn note(file: &File, data: &[u8]) -> Result<Vec<u8>> {
+	let header = Elf32_Nhdr {
+		n_namesz: crate::convert(TANGRAM_NOTE_NAME.len())?,
+		n_descsz: crate::convert(data.len())?,
+		n_type: NT_TANGRAM_MANIFEST,
+	};
+	let mut note = file_order(file, header)?.as_bytes().to_vec();
+	note.extend_from_slice(TANGRAM_NOTE_NAME);
+	note.extend_from_slice(data);
+	note.resize(align(note.len(), 4), 0);
+	Ok(note)
+}
+
+/// Find the manifest note among the notes at a location, and get the location of its data.
+fn find_note(file: &File, location: FileLocation) -> Result<Option<FileLocation>> {
+	let mut offset = location.offset;
+	while offset + size_of::<Elf32_Nhdr>() <= location.end() {
+		let header = read::<Elf32_Nhdr>(file, offset)?;
+		let name = offset + size_of::<Elf32_Nhdr>();
+		let desc = name + align(header.n_namesz.to_usize().unwrap(), 4);
+		let length = header.n_descsz.to_usize().unwrap();
+		if desc + length > location.end() {
+			break;
+		}
+		if header.n_type == NT_TANGRAM_MANIFEST
+			&& header.n_namesz.to_usize() == Some(TANGRAM_NOTE_NAME.len())
+			&& file.bytes(
+				FileLocation {
+					offset: name,
+					length: TANGRAM_NOTE_NAME.len(),
+				},
+				"note",
+			)? == TANGRAM_NOTE_NAME
+		{
+			return Ok(Some(FileLocation {
+				offset: desc,
+				length,
+			}));
+		}
+		offset = desc + align(length, 4);
+	}
+	Ok(None)
+}
+
+/// Get the end of a range in a header, or an error if it overflows.
+fn range_end<T: num::ToPrimitive + Copy>(
+	offset: T,
+	length: T,
+	what: &'static str,
+) -> Result<usize> {
+	offset
+		.to_usize()
+		.zip(length.to_usize())
+		.and_then(|(offset, length)| offset.checked_add(length))
+		.ok_or(Error::TruncatedHeader(what))
+}
+
+fn segment_kind(p_type: u32) -> String {
+	match p_type {
+		sys::PT_LOAD => "PT_LOAD".to_owned(),
+		sys::PT_NOTE => "PT_NOTE".to_owned(),
+		sys::PT_INTERP => "PT_INTERP".to_owned(),
+		sys::PT_DYNAMIC => "PT_DYNAMIC".to_owned(),
+		p_type => format!("{p_type:#x}"),
+	}
+}
+
 fn align(m: usize, n: usize) -> usize {
 	(m + n - 1) & !(n - 1)
 }
+
+/// Look up a section name in the section string table.
+fn section_name(string_table: &[u8], offset: u32) -> Option<&CStr> {
+	let name = string_table.get(offset.to_usize()?..)?;
+	let name = CStr::from_bytes_until_nul(name).ok()?;
+	(!name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{Format, Options};
+	use std::path::{Path, PathBuf};
+
+	/// Every combination of class and byte order.
+	const FIXTURES: [(&str, Format); 4] = [
+		("elf32_lsb", Format::Elf32),
+		("elf32_msb", Format::Elf32),
+		("elf64_lsb", Format::Elf64),
+		("elf64_msb", Format::Elf64),
+	];
+
+	fn fixture_path(name: &str) -> PathBuf {
+		Path::new(env!("CARGO_MANIFEST_DIR"))
+			.join("fixtures")
+			.join(name)
+	}
+
+	fn fixture(name: &str) -> tempfile::NamedTempFile {
+		let file = tempfile::NamedTempFile::new().unwrap();
+		std::fs::copy(fixture_path(name), file.path()).unwrap();
+		file
+	}
+
+	/// Use the fixtures as wrappers. Each fixture is for a different system, so the wrapper that is selected has the same class and byte order as the output.
+	fn wrappers() {
+		static WRAPPERS: std::sync::LazyLock<tempfile::TempDir> = std::sync::LazyLock::new(|| {
+			let directory = tempfile::TempDir::new().unwrap();
+			for (name, _) in FIXTURES {
+				let system = crate::system(fixture_path(name), None).unwrap();
+				let path = directory.path().join(system);
+				std::fs::create_dir(&path).unwrap();
+				std::fs::copy(fixture_path(name), path.join("wrapper.exe")).unwrap();
+			}
+			directory
+		});
+		crate::set_wrappers_path(WRAPPERS.path());
+	}
+
+	fn manifest(arg: &str) -> serde_json::Value {
+		serde_json::json!({ "args": [arg] })
+	}
+
+	fn read(path: &Path) -> Option<serde_json::Value> {
+		crate::read_manifest::<serde_json::Value>(path, None)
+			.unwrap()
+			.manifest
+	}
+
+	#[test]
+	fn detect_format() {
+		for (name, format) in FIXTURES {
+			let path = fixture_path(name);
+			assert_eq!(crate::detect_format(&path).unwrap(), Some(format), "{name}");
+			assert_eq!(crate::get_format(&path).unwrap(), Some(format), "{name}");
+		}
+	}
+
+	#[test]
+	fn write_and_overwrite() {
+		for (name, _) in FIXTURES {
+			let file = fixture(name);
+			assert_eq!(read(file.path()), None);
+			crate::write_manifest(file.path(), &manifest("first"), None, Options::default())
+				.unwrap();
+			assert_eq!(read(file.path()), Some(manifest("first")), "{name}");
+			let first = std::fs::read(file.path()).unwrap();
+			crate::write_manifest(file.path(), &manifest("second"), None, Options::default())
+				.unwrap();
+			assert_eq!(read(file.path()), Some(manifest("second")), "{name}");
+			crate::write_manifest(file.path(), &manifest("first"), None, Options::default())
+				.unwrap();
+			assert_eq!(std::fs::read(file.path()).unwrap(), first, "{name}");
+			assert!(crate::verify(file.path(), None).unwrap().is_ok(), "{name}");
+		}
+	}
+
+	#[test]
+	fn embed() {
+		wrappers();
+		for (name, _) in FIXTURES {
+			let file = fixture(name);
+			crate::embed(file.path(), &manifest("embedded"), None, Options::default()).unwrap();
+			assert_eq!(read(file.path()), Some(manifest("embedded")), "{name}");
+			let verification = crate::verify(file.path(), None).unwrap();
+			assert!(verification.is_ok(), "{name}: {:?}", verification.problems);
+		}
+	}
+
+	#[test]
+	fn select_wrapper() {
+		wrappers();
+		let systems = FIXTURES.map(|(name, _)| crate::system(fixture_path(name), None).unwrap());
+		assert_eq!(
+			systems,
+			["i686-linux", "powerpc-linux", "x86_64-linux", "s390x-linux"]
+		);
+		let (exe, _) = crate::wrapper_paths("x86_64-linux").unwrap();
+		assert_eq!(
+			std::fs::read(exe).unwrap(),
+			std::fs::read(fixture_path("elf64_lsb")).unwrap()
+		);
+		assert!(matches!(
+			crate::wrapper_paths("aarch64-linux"),
+			Err(crate::Error::MissingWrapper(system)) if system == "aarch64-linux"
+		));
+	}
+
+	#[test]
+	fn stripped_section_headers() {
+		wrappers();
+		for (name, format) in FIXTURES {
+			// Both embedded wrappers and wrappers with a written manifest load the note.
+			for embed in [true, false] {
+				let file = fixture(name);
+				if embed {
+					crate::embed(file.path(), &manifest("noted"), None, Options::default())
+						.unwrap();
+				} else {
+					crate::write_manifest(
+						file.path(),
+						&manifest("noted"),
+						None,
+						Options::default(),
+					)
+					.unwrap();
+				}
+				let sections = crate::inspect(file.path(), None).unwrap().sections;
+				let note = sections
+					.iter()
+					.find(|section| section.name == ".note.tg-manifest")
+					.unwrap();
+				assert!(note.address.is_some(), "{name} ({embed})");
+
+				// Remove the section header table, like `llvm-objcopy --strip-sections`.
+				let mut bytes = std::fs::read(file.path()).unwrap();
+				let (shoff, shnum) = if format == Format::Elf32 {
+					(32..36, 48..52)
+				} else {
+					(40..48, 60..64)
+				};
+				bytes[shoff].fill(0);
+				bytes[shnum].fill(0);
+				std::fs::write(file.path(), &bytes).unwrap();
+
+				// The manifest is found through its PT_NOTE segment.
+				assert_eq!(
+					read(file.path()),
+					Some(manifest("noted")),
+					"{name} ({embed})"
+				);
+				let output =
+					crate::read_manifest_from_bytes::<serde_json::Value>(&bytes, None).unwrap();
+				let location = output.location.unwrap();
+				let header = &bytes[location.offset - 20..location.offset];
+				assert_eq!(&header[12..], b"tangram\0", "{name} ({embed})");
+			}
+		}
+	}
+
+	#[test]
+	fn atomic_write() {
+		use std::os::unix::fs::{MetadataExt as _, PermissionsExt as _};
+		let file = fixture("elf64_lsb");
+		std::fs::set_permissions(file.path(), std::fs::Permissions::from_mode(0o751)).unwrap();
+		let inode = std::fs::metadata(file.path()).unwrap().ino();
+		let manifest = serde_json::json!({
+			"executable": { "kind": "address", "value": 0x10200 },
+		});
+		crate::write_manifest(file.path(), &manifest, None, Options::default()).unwrap();
+		let metadata = std::fs::metadata(file.path()).unwrap();
+		assert_ne!(metadata.ino(), inode);
+		assert_eq!(metadata.permissions().mode() & 0o7777, 0o751);
+
+		// A failed modification leaves the file untouched.
+		let before = std::fs::read(file.path()).unwrap();
+		assert!(crate::unwrap(file.path(), None, None).is_err());
+		assert_eq!(std::fs::read(file.path()).unwrap(), before);
+	}
+
+	#[test]
+	fn bytes() {
+		wrappers();
+		for (name, _) in FIXTURES {
+			let file = fixture(name);
+			crate::embed(file.path(), &manifest("embedded"), None, Options::default()).unwrap();
+			let bytes = std::fs::read(fixture_path(name)).unwrap();
+			let bytes =
+				crate::embed_into_bytes(bytes, &manifest("embedded"), None, Options::default())
+					.unwrap();
+			assert_eq!(bytes, std::fs::read(file.path()).unwrap(), "{name}");
+			let output =
+				crate::read_manifest_from_bytes::<serde_json::Value>(&bytes, None).unwrap();
+			assert_eq!(output.manifest, Some(manifest("embedded")), "{name}");
+		}
+	}
+
+	#[test]
+	fn unwrap() {
+		wrappers();
+		for (name, format) in FIXTURES {
+			let file = fixture(name);
+			let manifest = serde_json::json!({
+				"executable": { "kind": "address", "value": 0x10200 },
+			});
+			crate::embed(file.path(), &manifest, None, Options::default()).unwrap();
+			let unwrapped = crate::unwrap(file.path(), None, None).unwrap();
+			assert!(matches!(unwrapped, crate::Unwrapped::Restored), "{name}");
+			assert_eq!(read(file.path()), None, "{name}");
+
+			// The identification and entrypoint are restored.
+			let original = std::fs::read(fixture_path(name)).unwrap();
+			let restored = std::fs::read(file.path()).unwrap();
+			let end = if format == Format::Elf32 { 28 } else { 32 };
+			assert_eq!(restored[..end], original[..end], "{name}");
+
+			// The sections and the segments that map them are restored.
+			let sections = |path: &Path| {
+				serde_json::to_value(crate::inspect(path, None).unwrap().sections).unwrap()
+			};
+			assert_eq!(
+				sections(file.path()),
+				sections(&fixture_path(name)),
+				"{name}"
+			);
+		}
+	}
+}
@@ -3236,7 +3236,15 @@ pub struct Elf64_auxv_t {
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(
+	Debug,
+	Copy,
+	Clone,
+	zerocopy::FromBytes,
+	zerocopy::IntoBytes,
+	zerocopy::KnownLayout,
+	zerocopy::Immutable,
+)]
 pub struct Elf32_Nhdr {
 	pub n_namesz: Elf32_Word,
 	pub n_descsz: Elf32_Word,
@@ -0,0 +1,424 @@
+use crate::{Error, Footer, MAGIC, Result};
+use num::ToPrimitive as _;
+use std::{borrow::Cow, ops::Range};
+use zerocopy::{FromZeros as _, IntoBytes as _};
+
+/// The version of manifests that are stored as bare JSON.
+pub(crate) const VERSION_0: u64 = 0;
+
+/// The version of manifests whose descriptor is preceded by a BLAKE3 checksum of the stored data. Version 1 manifests are followed by a [`Descriptor`] alone.
+pub(crate) const VERSION_2: u64 = 2;
+
+/// The version written by this version of `wrap`.
+pub(crate) const VERSION: u64 = VERSION_2;
+
+/// How a manifest is encoded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encoding {
+	/// JSON, which the wrapper runtime can read.
+	#[default]
+	Json,
+
+	/// The compact `tangram_serialize` encoding.
+	Serialize,
+}
+
+/// How an encoded manifest is compressed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+	#[default]
+	None,
+	Zstd,
+}
+
+/// Options for writing a manifest.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+	pub encoding: Encoding,
+	pub compression: Compression,
+}
+
+/// A manifest that can be stored in a binary.
+pub trait Codec: Sized {
+	fn encode(&self, encoding: Encoding) -> Result<Vec<u8>>;
+	fn decode(data: &[u8], encoding: Encoding) -> Result<Self>;
+}
+
+/// The decompressed data of a manifest, kept in the encoding it was stored with. A payload can be stored again with a different footer or compression, but converting it to another encoding needs a typed manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Payload {
+	pub encoding: Encoding,
+	pub data: Vec<u8>,
+}
+
+/// Describes the data of a version 1 manifest. It is stored between the data and the footer, so that the data of a JSON manifest still starts at the beginning of the section.
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	zerocopy::FromBytes,
+	zerocopy::IntoBytes,
+	zerocopy::Immutable,
+	zerocopy::KnownLayout,
+)]
+#[repr(C)]
+pub(crate) struct Descriptor {
+	pub encoding: u8,
+	pub compression: u8,
+	pub reserved: [u8; 6],
+	pub size: u64,
+}
+
+/// A decoded footer and descriptor.
+#[derive(Clone, Debug)]
+pub(crate) struct Layout {
+	pub footer: Footer,
+	pub options: Options,
+
+	/// The size of the manifest once decoded, if it is known.
+	pub size: Option<u64>,
+
+	/// The checksum of the stored data, if the version has one.
+	pub checksum: Option<blake3::Hash>,
+
+	/// The range of the stored, possibly compressed, manifest data.
+	pub data: Range<usize>,
+}
+
+impl Codec for serde_json::Value {
+	fn encode(&self, encoding: Encoding) -> Result<Vec<u8>> {
+		match encoding {
+			Encoding::Json => Ok(serde_json::to_vec(self)?),
+			Encoding::Serialize => Err(Error::UnsupportedEncoding(
+				"untyped manifests can only be encoded as JSON".to_owned(),
+			)),
+		}
+	}
+
+	fn decode(data: &[u8], encoding: Encoding) -> Result<Self> {
+		match encoding {
+			Encoding::Json => Ok(serde_json::from_slice(data)?),
+			Encoding::Serialize => Err(Error::UnsupportedEncoding(
+				"untyped manifests can only be decoded from JSON".to_owned(),
+			)),
+		}
+	}
+}
+
+impl Codec for Payload {
+	fn encode(&self, encoding: Encoding) -> Result<Vec<u8>> {
+		if encoding != self.encoding {
+			return Err(Error::UnsupportedEncoding(format!(
+				"a {:?} manifest cannot be encoded as {encoding:?} without its type",
+				self.encoding
+			)));
+		}
+		Ok(self.data.clone())
+	}
+
+	fn decode(data: &[u8], encoding: Encoding) -> Result<Self> {
+		Ok(Self {
+			encoding,
+			data: data.to_vec(),
+		})
+	}
+}
+
+impl Encoding {
+	fn from_u8(value: u8) -> Result<Self> {
+		match value {
+			0 => Ok(Self::Json),
+			1 => Ok(Self::Serialize),
+			value => Err(Error::UnsupportedEncoding(format!(
+				"unknown encoding {value}"
+			))),
+		}
+	}
+}
+
+impl Compression {
+	fn from_u8(value: u8) -> Result<Self> {
+		match value {
+			0 => Ok(Self::None),
+			1 => Ok(Self::Zstd),
+			value => Err(Error::UnsupportedEncoding(format!(
+				"unknown compression {value}"
+			))),
+		}
+	}
+}
+
+/// Encode a manifest along with its checksum, descriptor and footer.
+pub fn encode(manifest: &impl Codec, options: Options) -> Result<Vec<u8>> {
+	let data = manifest.encode(options.encoding)?;
+	let size = data.len().to_u64().unwrap();
+	let mut data = match options.compression {
+		Compression::None => data,
+		Compression::Zstd => {
+			zstd::bulk::compress(&data, 0).map_err(|error| Error::Codec(error.into()))?
+		},
+	};
+	let checksum = blake3::hash(&data);
+	data.extend_from_slice(checksum.as_bytes());
+	let descriptor = Descriptor {
+		encoding: options.encoding as u8,
+		compression: options.compression as u8,
+		reserved: [0; 6],
+		size,
+	};
+	data.extend_from_slice(descriptor.as_bytes());
+	let footer = Footer {
+		size: data.len().to_u64().unwrap(),
+		version: VERSION,
+		magic: MAGIC,
+	};
+	data.extend_from_slice(footer.as_bytes());
+	Ok(data)
+}
+
+/// Decode a manifest from data that ends with a footer.
+pub fn decode<T: Codec>(data: &[u8]) -> Result<T> {
+	let (layout, payload) = payload(data)?;
+	T::decode(&payload, layout.options.encoding)
+}
+
+/// Read the footer, descriptor and checksum from the end of the data.
+pub(crate) fn layout(data: &[u8]) -> Result<Layout> {
+	let footer = Footer::read(data)?;
+	let mut end = footer.payload(data)?.len();
+	if footer.version == VERSION_0 {
+		return Ok(Layout {
+			footer,
+			options: Options::default(),
+			size: Some(end.to_u64().unwrap()),
+			checksum: None,
+			data: 0..end,
+		});
+	}
+
+	// Read the descriptor.
+	end = end
+		.checked_sub(size_of::<Descriptor>())
+		.ok_or(Error::TruncatedHeader("manifest descriptor"))?;
+	let mut descriptor = Descriptor::new_zeroed();
+	descriptor
+		.as_mut_bytes()
+		.copy_from_slice(&data[end..end + size_of::<Descriptor>()]);
+	let options = Options {
+		encoding: Encoding::from_u8(descriptor.encoding)?,
+		compression: Compression::from_u8(descriptor.compression)?,
+	};
+
+	// Read the checksum.
+	let checksum = if footer.version >= VERSION_2 {
+		end = end
+			.checked_sub(blake3::OUT_LEN)
+			.ok_or(Error::TruncatedHeader("manifest checksum"))?;
+		let bytes: [u8; blake3::OUT_LEN] = data[end..end + blake3::OUT_LEN].try_into().unwrap();
+		Some(blake3::Hash::from_bytes(bytes))
+	} else {
+		None
+	};
+
+	Ok(Layout {
+		footer,
+		options,
+		size: Some(descriptor.size),
+		checksum,
+		data: 0..end,
+	})
+}
+
+/// Get the decompressed data of a manifest from data that ends with a footer, verifying its checksum.
+pub(crate) fn payload(data: &[u8]) -> Result<(Layout, Cow<'_, [u8]>)> {
+	let layout = layout(data)?;
+	let stored = &data[layout.data.clone()];
+	if let Some(expected) = layout.checksum {
+		let found = blake3::hash(stored);
+		if found != expected {
+			return Err(Error::ChecksumMismatch {
+				expected: expected.to_hex().to_string(),
+				found: found.to_hex().to_string(),
+			});
+		}
+	}
+	let payload = match layout.options.compression {
+		Compression::None => Cow::Borrowed(stored),
+		Compression::Zstd => {
+			let capacity = layout
+				.size
+				.and_then(|size| size.to_usize())
+				.ok_or(Error::BadFooter("invalid manifest size"))?;
+			let data = zstd::bulk::decompress(stored, capacity)
+				.map_err(|error| Error::Codec(error.into()))?;
+			Cow::Owned(data)
+		},
+	};
+	if payload.len().to_u64() != layout.size {
+		return Err(Error::BadFooter(
+			"the manifest size does not match its descriptor",
+		));
+	}
+	Ok((layout, payload))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Codec, Compression, Descriptor, Encoding, Options, Payload, VERSION_0, decode};
+	use crate::{Error, Footer, MAGIC};
+	use std::path::Path;
+	use zerocopy::IntoBytes as _;
+
+	/// A typed manifest whose `Serialize` encoding stands in for `tangram_serialize`.
+	#[derive(Debug, PartialEq, Eq)]
+	struct Manifest {
+		args: Vec<String>,
+	}
+
+	impl Codec for Manifest {
+		fn encode(&self, encoding: Encoding) -> crate::Result<Vec<u8>> {
+			match encoding {
+				Encoding::Json => Ok(serde_json::to_vec(
+					&serde_json::json!({ "args": self.args }),
+				)?),
+				Encoding::Serialize => Ok(self.args.join("\0").into_bytes()),
+			}
+		}
+
+		fn decode(data: &[u8], encoding: Encoding) -> crate::Result<Self> {
+			let args = match encoding {
+				Encoding::Json => {
+					let value: serde_json::Value = serde_json::from_slice(data)?;
+					serde_json::from_value(value["args"].clone())?
+				},
+				Encoding::Serialize => String::from_utf8(data.to_vec())
+					.unwrap()
+					.split('\0')
+					.map(str::to_owned)
+					.collect(),
+			};
+			Ok(Self { args })
+		}
+	}
+
+	fn manifest() -> Manifest {
+		Manifest {
+			args: vec!["a".to_owned(), "b".to_owned()],
+		}
+	}
+
+	fn footer(data: &mut Vec<u8>, version: u64) {
+		let footer = Footer {
+			size: data.len().try_into().unwrap(),
+			version,
+			magic: MAGIC,
+		};
+		data.extend_from_slice(footer.as_bytes());
+	}
+
+	fn v0() -> Vec<u8> {
+		let mut data = manifest().encode(Encoding::Json).unwrap();
+		footer(&mut data, VERSION_0);
+		data
+	}
+
+	fn v1() -> Vec<u8> {
+		let encoded = manifest().encode(Encoding::Serialize).unwrap();
+		let mut data = zstd::bulk::compress(&encoded, 0).unwrap();
+		let descriptor = Descriptor {
+			encoding: Encoding::Serialize as u8,
+			compression: Compression::Zstd as u8,
+			reserved: [0; 6],
+			size: encoded.len().try_into().unwrap(),
+		};
+		data.extend_from_slice(descriptor.as_bytes());
+		footer(&mut data, 1);
+		data
+	}
+
+	#[test]
+	fn versions() {
+		assert_eq!(decode::<Manifest>(&v0()).unwrap(), manifest());
+		assert_eq!(decode::<Manifest>(&v1()).unwrap(), manifest());
+		for encoding in [Encoding::Json, Encoding::Serialize] {
+			for compression in [Compression::None, Compression::Zstd] {
+				let options = Options {
+					encoding,
+					compression,
+				};
+				let data = super::encode(&manifest(), options).unwrap();
+				let layout = super::layout(&data).unwrap();
+				assert_eq!(layout.footer.version, super::VERSION_2);
+				assert_eq!(layout.options, options);
+				assert!(layout.checksum.is_some());
+				assert_eq!(decode::<Manifest>(&data).unwrap(), manifest());
+			}
+		}
+
+		// A payload keeps its encoding, and cannot be converted to another one.
+		let payload: Payload = decode(&v1()).unwrap();
+		assert_eq!(payload.encoding, Encoding::Serialize);
+		assert_eq!(payload.data, b"a\0b");
+		let options = Options {
+			encoding: Encoding::Serialize,
+			compression: Compression::None,
+		};
+		let data = super::encode(&payload, options).unwrap();
+		assert_eq!(decode::<Manifest>(&data).unwrap(), manifest());
+		let payload: Payload = decode(&v0()).unwrap();
+		assert!(matches!(
+			super::encode(&payload, options),
+			Err(Error::UnsupportedEncoding(_))
+		));
+	}
+
+	#[test]
+	fn checksum() {
+		let mut data = super::encode(&manifest(), Options::default()).unwrap();
+		let checksum = super::layout(&data).unwrap().checksum.unwrap();
+		data[0] ^= 0xff;
+		assert!(matches!(
+			decode::<Manifest>(&data),
+			Err(Error::ChecksumMismatch { expected, .. }) if expected == checksum.to_hex().as_str()
+		));
+	}
+
+	#[test]
+	fn migrate() {
+		let file = tempfile::NamedTempFile::new().unwrap();
+		let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/elf64_lsb");
+		std::fs::copy(fixture, file.path()).unwrap();
+		crate::modify(file.path(), |file| {
+			crate::create_format(file, None)?.overwrite_manifest(file, &v0())
+		})
+		.unwrap();
+
+		// A typed manifest is converted from JSON.
+		let options = Options {
+			encoding: Encoding::Serialize,
+			compression: Compression::Zstd,
+		};
+		assert!(crate::migrate::<Manifest>(file.path(), None, options).unwrap());
+		assert!(!crate::migrate::<Manifest>(file.path(), None, options).unwrap());
+		let output = crate::read_manifest::<Payload>(file.path(), None).unwrap();
+		assert_eq!(
+			output.manifest,
+			Some(Payload {
+				encoding: Encoding::Serialize,
+				data: b"a\0b".to_vec(),
+			})
+		);
+
+		// A payload is migrated as long as its encoding is kept.
+		let options = Options {
+			encoding: Encoding::Serialize,
+			compression: Compression::None,
+		};
+		assert!(crate::migrate::<Payload>(file.path(), None, options).unwrap());
+		let output = crate::read_manifest::<Manifest>(file.path(), None).unwrap();
+		assert_eq!(output.manifest, Some(manifest()));
+		assert!(crate::migrate::<Payload>(file.path(), None, Options::default()).is_err());
+	}
+}
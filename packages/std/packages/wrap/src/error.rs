@@ -0,0 +1,131 @@
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error produced while reading or writing a wrapped binary.
+#[derive(Debug)]
+pub enum Error {
+	/// The file is not in a binary format `wrap` understands.
+	UnsupportedFormat(String),
+
+	/// A header or table extends past the end of the file.
+	TruncatedHeader(&'static str),
+
+	/// A section that `wrap` needs is missing from the binary.
+	MissingSection(&'static str),
+
+	/// The manifest footer is malformed or points outside of the file.
+	BadFooter(&'static str),
+
+	/// The manifest was written with a newer format version.
+	VersionMismatch { expected: u64, found: u64 },
+
+	/// The manifest does not match its checksum.
+	ChecksumMismatch { expected: String, found: String },
+
+	/// The manifest uses an encoding or compression that is not supported.
+	UnsupportedEncoding(String),
+
+	/// There is no wrapper for the system of the binary.
+	MissingWrapper(String),
+
+	/// The wrapper was built for a different system than the binary.
+	WrapperMismatch { wrapper: String, binary: String },
+
+	/// An edit cannot be applied to the manifest.
+	InvalidEdit(String),
+
+	/// The manifest is not one the wrapper runtime accepts.
+	Invalid(crate::ValidationError),
+
+	/// The manifest could not be encoded or decoded as JSON.
+	Json(serde_json::Error),
+
+	/// The manifest could not be encoded, decoded, compressed or decompressed.
+	Codec(Box<dyn std::error::Error + Send + Sync>),
+
+	/// An I/O error.
+	Io(std::io::Error),
+}
+
+impl Error {
+	/// Whether this error means the file is not a wrapper or is a corrupt one, as opposed to a failure to access it.
+	#[must_use]
+	pub fn is_malformed(&self) -> bool {
+		matches!(
+			self,
+			Self::UnsupportedFormat(_)
+				| Self::TruncatedHeader(_)
+				| Self::MissingSection(_)
+				| Self::BadFooter(_)
+				| Self::VersionMismatch { .. }
+				| Self::ChecksumMismatch { .. }
+				| Self::UnsupportedEncoding(_)
+				| Self::Json(_)
+				| Self::Codec(_)
+		)
+	}
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnsupportedFormat(message) => write!(f, "unsupported file format: {message}"),
+			Self::TruncatedHeader(what) => write!(f, "truncated {what}"),
+			Self::MissingSection(name) => write!(f, "missing section {name}"),
+			Self::BadFooter(message) => write!(f, "invalid manifest footer: {message}"),
+			Self::VersionMismatch { expected, found } => write!(
+				f,
+				"unsupported manifest version {found}, expected at most {expected}"
+			),
+			Self::ChecksumMismatch { expected, found } => write!(
+				f,
+				"manifest checksum mismatch: expected {expected}, found {found}"
+			),
+			Self::UnsupportedEncoding(message) => {
+				write!(f, "unsupported manifest encoding: {message}")
+			},
+			Self::MissingWrapper(system) => write!(f, "no wrapper is available for {system}"),
+			Self::WrapperMismatch { wrapper, binary } => write!(
+				f,
+				"the wrapper is for {wrapper}, but the binary is for {binary}"
+			),
+			Self::InvalidEdit(message) => write!(f, "cannot edit the manifest: {message}"),
+			Self::Invalid(_) => write!(f, "invalid manifest"),
+			Self::Json(_) => write!(f, "invalid manifest JSON"),
+			Self::Codec(_) => write!(f, "failed to encode or decode the manifest"),
+			Self::Io(_) => write!(f, "an I/O error occurred"),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Invalid(error) => Some(error),
+			Self::Json(error) => Some(error),
+			Self::Codec(error) => Some(error.as_ref()),
+			Self::Io(error) => Some(error),
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(value: std::io::Error) -> Self {
+		Self::Io(value)
+	}
+}
+
+impl From<serde_json::Error> for Error {
+	fn from(value: serde_json::Error) -> Self {
+		Self::Json(value)
+	}
+}
+
+impl From<Error> for std::io::Error {
+	fn from(value: Error) -> Self {
+		match value {
+			Error::Io(error) => error,
+			error => std::io::Error::new(std::io::ErrorKind::InvalidData, error),
+		}
+	}
+}
@@ -0,0 +1,409 @@
+use crate::{Error, Format, Result};
+use serde_json::Value;
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	path::Path,
+};
+
+/// What a wrapper would exec, computed from its manifest without running anything.
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Explanation {
+	/// The path passed to `execve`. If there is none, the wrapper jumps to the entrypoint of the executable embedded in it.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+
+	/// The rendered script of a content executable, which the wrapper writes to a temporary file.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content: Option<String>,
+
+	/// The arguments, starting with `argv[0]`.
+	pub argv: Vec<String>,
+
+	/// The environment.
+	pub env: BTreeMap<String, String>,
+}
+
+/// Compute the argv and env that a wrapper would exec when run with `arguments` in `env`, with artifacts in `artifacts_dir`, or in the directory the wrapper would use. These are the semantics of the wrapper runtime, which `common::explain` implements for typed manifests.
+pub fn explain(
+	path: impl AsRef<Path>,
+	format: Option<Format>,
+	arguments: &[String],
+	env: BTreeMap<String, String>,
+	artifacts_dir: Option<&Path>,
+) -> Result<Explanation> {
+	let path = path.as_ref();
+	let mut manifest = crate::read_manifest::<Value>(path, format)?
+		.manifest
+		.ok_or_else(|| Error::UnsupportedFormat("the file is not a wrapper".to_owned()))?;
+	let artifacts_dir = match artifacts_dir {
+		Some(artifacts_dir) => artifacts_dir.to_owned(),
+		None => crate::unwrap::manifest_artifacts_dir(&manifest, path)?,
+	};
+	if crate::unwrap::is_relocatable(&manifest) {
+		crate::unwrap::expand_origin(&mut manifest, &crate::unwrap::origin(path)?);
+	}
+	let cx = Cx {
+		artifacts_dir: &artifacts_dir,
+	};
+	cx.explain(&manifest, &path.display().to_string(), arguments, env)
+}
+
+struct Cx<'a> {
+	artifacts_dir: &'a Path,
+}
+
+impl Cx<'_> {
+	fn explain(
+		&self,
+		manifest: &Value,
+		wrapper: &str,
+		arguments: &[String],
+		mut env: BTreeMap<String, String>,
+	) -> Result<Explanation> {
+		// Render the executable.
+		let executable = &manifest["executable"];
+		let (executable, content) = match executable["kind"].as_str() {
+			Some("path") => (Some(self.render(&executable["value"])?), None),
+			Some("content") => (
+				Some("/dev/fd/3".to_owned()),
+				Some(self.render(&executable["value"])?),
+			),
+			Some("address") => (None, None),
+			_ => return Err(invalid("unknown executable kind")),
+		};
+
+		// Apply the environment mutations.
+		if !manifest["env"].is_null() {
+			self.apply_env(&mut env, &manifest["env"])?;
+		}
+
+		// Render the interpreter.
+		let interpreter = &manifest["interpreter"];
+		let kind = interpreter["kind"].as_str();
+		let path = match kind {
+			Some("normal" | "ld-linux" | "ld-musl") => Some(self.render(&interpreter["path"])?),
+			_ => None,
+		};
+		let interpreter_args = self.render_all(&interpreter["args"])?;
+		let library_paths = self.render_all(&interpreter["libraryPaths"])?;
+		let preloads = self.render_all(&interpreter["preloads"])?;
+		let loader = match kind {
+			Some("ld-linux" | "ld-musl") => Some(("LD_LIBRARY_PATH", "LD_PRELOAD")),
+			Some("dyld") => Some(("DYLD_LIBRARY_PATH", "DYLD_INSERT_LIBRARIES")),
+			_ => None,
+		};
+
+		// Combine the library paths and preloads with the ones in the environment.
+		let mut library_path = None;
+		let mut preload = None;
+		if let Some((library_path_key, preload_key)) = loader {
+			let inject = kind == Some("dyld") || executable.is_none();
+			library_path = inject_paths(
+				&mut env,
+				&library_paths,
+				library_path_key,
+				inject.then_some((
+					"TANGRAM_INJECTION_LIBRARY_PATH",
+					"TANGRAM_INJECTION_CLEAR_LIBRARY_PATH",
+				)),
+			);
+			preload = inject_paths(
+				&mut env,
+				&preloads,
+				preload_key,
+				inject.then_some((
+					"TANGRAM_INJECTION_PRELOAD",
+					"TANGRAM_INJECTION_CLEAR_PRELOAD",
+				)),
+			);
+			env.insert(
+				"TANGRAM_INJECTION_IDENTITY_PATH".to_owned(),
+				wrapper.to_owned(),
+			);
+		}
+
+		// Append the arguments that are not for the wrapper to the arguments from the manifest.
+		let mut manifest_args = self.render_all(&manifest["args"])?;
+		manifest_args.extend(
+			arguments
+				.iter()
+				.filter(|arg| !arg.starts_with("--tangram-"))
+				.cloned(),
+		);
+
+		// Create argv.
+		let mut argv = Vec::new();
+		let path = match (executable, path) {
+			(Some(executable), Some(path)) => {
+				argv.push(path.clone());
+				argv.extend(interpreter_args);
+				if kind == Some("ld-linux") {
+					argv.push("--inhibit-cache".to_owned());
+				}
+				if matches!(kind, Some("ld-linux" | "ld-musl")) {
+					if let Some(library_path) = library_path {
+						argv.push("--library-path".to_owned());
+						argv.push(library_path);
+					}
+					if let Some(preload) = preload {
+						argv.push("--preload".to_owned());
+						argv.push(preload);
+					}
+					argv.push("--argv0".to_owned());
+					argv.push(wrapper.to_owned());
+				}
+				if kind == Some("ld-musl") {
+					argv.push("--".to_owned());
+				}
+				argv.push(executable);
+				Some(path)
+			},
+			(executable, _) => {
+				argv.push(wrapper.to_owned());
+				executable
+			},
+		};
+		argv.extend(manifest_args);
+
+		Ok(Explanation {
+			path,
+			content,
+			argv,
+			env,
+		})
+	}
+
+	/// Apply the mutation of the whole environment, which is either `unset` or a map of the mutations of each variable.
+	fn apply_env(&self, env: &mut BTreeMap<String, String>, mutation: &Value) -> Result<()> {
+		match mutation["kind"].as_str() {
+			Some("unset") => env.clear(),
+			Some("set" | "merge") if mutation["value"]["kind"] == "map" => {
+				let map = mutation["value"]["value"]
+					.as_object()
+					.ok_or_else(|| invalid("expected a map"))?;
+				for (key, value) in map {
+					self.apply_value(env, key, value)?;
+				}
+			},
+			_ => return Err(invalid("unsupported mutation of the environment")),
+		}
+		Ok(())
+	}
+
+	/// Apply the value of a variable in the environment map. An array is a list of mutations, a mutation is applied, and anything else is rendered.
+	fn apply_value(
+		&self,
+		env: &mut BTreeMap<String, String>,
+		key: &str,
+		value: &Value,
+	) -> Result<()> {
+		match value {
+			Value::Array(values) => {
+				for value in values {
+					let mutation = if value["kind"] == "mutation" {
+						&value["value"]
+					} else {
+						value
+					};
+					self.apply_mutation(env, key, mutation)?;
+				}
+			},
+			value if value["kind"] == "mutation" => {
+				self.apply_mutation(env, key, &value["value"])?;
+			},
+			value => {
+				env.insert(key.to_owned(), self.render_value(value)?);
+			},
+		}
+		Ok(())
+	}
+
+	/// Apply a mutation to a variable.
+	fn apply_mutation(
+		&self,
+		env: &mut BTreeMap<String, String>,
+		key: &str,
+		mutation: &Value,
+	) -> Result<()> {
+		let values = || -> Result<Vec<String>> {
+			mutation["values"]
+				.as_array()
+				.ok_or_else(|| invalid("expected an array"))?
+				.iter()
+				.map(|value| self.render_value(value))
+				.collect()
+		};
+		let separator = mutation["separator"].as_str().unwrap_or_default();
+		match mutation["kind"].as_str() {
+			Some("unset") => {
+				env.remove(key);
+			},
+			Some("set") => {
+				self.apply_value(env, key, &mutation["value"])?;
+			},
+			Some("set_if_unset") => {
+				if !env.contains_key(key) {
+					self.apply_value(env, key, &mutation["value"])?;
+				}
+			},
+			Some("prepend") => {
+				let mut values = values()?;
+				values.extend(env.get(key).cloned());
+				env.insert(key.to_owned(), values.join(":"));
+			},
+			Some("append") => {
+				let values = env.get(key).cloned().into_iter().chain(values()?);
+				env.insert(key.to_owned(), values.collect::<Vec<_>>().join(":"));
+			},
+			Some("prefix") => {
+				let value = self.render(&mutation["template"])?;
+				let value = match env.get(key) {
+					Some(existing) => format!("{value}{separator}{existing}"),
+					None => value,
+				};
+				env.insert(key.to_owned(), value);
+			},
+			Some("suffix") => {
+				let value = self.render(&mutation["template"])?;
+				let value = match env.get(key) {
+					Some(existing) => format!("{existing}{separator}{value}"),
+					None => value,
+				};
+				env.insert(key.to_owned(), value);
+			},
+			Some("merge") => {
+				return Err(invalid(
+					"merge mutations are not supported for environment variables",
+				));
+			},
+			_ => return Err(invalid("unknown mutation kind")),
+		}
+		Ok(())
+	}
+
+	/// Render a value that is set in the environment.
+	fn render_value(&self, value: &Value) -> Result<String> {
+		match value {
+			Value::Bool(value) => Ok(value.to_string()),
+			Value::Number(value) => Ok(value.to_string()),
+			Value::String(value) => Ok(value.clone()),
+			value => match value["kind"].as_str() {
+				Some("template") => self.render(&value["value"]),
+				Some("directory" | "file" | "symlink" | "object") => {
+					let mut ids = BTreeSet::new();
+					crate::inspect::collect_artifacts(&value["value"], &mut ids);
+					let id = ids.pop_first().ok_or_else(|| invalid("invalid artifact"))?;
+					Ok(self.artifacts_dir.join(id).display().to_string())
+				},
+				_ => Err(invalid("cannot render the value in this context")),
+			},
+		}
+	}
+
+	/// Render a template, resolving artifacts in the artifacts directory.
+	fn render(&self, template: &Value) -> Result<String> {
+		crate::unwrap::resolve(template, self.artifacts_dir)
+	}
+
+	fn render_all(&self, templates: &Value) -> Result<Vec<String>> {
+		templates
+			.as_array()
+			.into_iter()
+			.flatten()
+			.map(|template| self.render(template))
+			.collect()
+	}
+}
+
+/// Join paths from the manifest with the existing value of the variable. If `restore` is set, the variable is set in the environment along with the variable that restores the original value.
+fn inject_paths(
+	env: &mut BTreeMap<String, String>,
+	paths: &[String],
+	key: &str,
+	restore: Option<(&str, &str)>,
+) -> Option<String> {
+	let original = env.get(key).cloned();
+	let joined = paths.join(":");
+	let value = match (&original, joined.is_empty()) {
+		(_, true) => original.clone(),
+		(Some(original), false) => Some(format!("{joined}:{original}")),
+		(None, false) => Some(joined),
+	};
+	if let (Some((restore, clear)), Some(value)) = (restore, &value)
+		&& !value.is_empty()
+	{
+		env.insert(key.to_owned(), value.clone());
+		match original {
+			Some(original) => env.insert(restore.to_owned(), original),
+			None => env.insert(clear.to_owned(), "true".to_owned()),
+		};
+	}
+	value
+}
+
+fn invalid(message: &str) -> Error {
+	Error::UnsupportedFormat(format!("invalid manifest: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Cx;
+	use serde_json::json;
+	use std::{collections::BTreeMap, path::Path};
+
+	#[test]
+	fn explain_ld_linux() {
+		let manifest = json!({
+			"interpreter": {
+				"kind": "ld-linux",
+				"path": { "components": [{ "kind": "artifact", "value": "fil_01ld" }] },
+				"libraryPaths": [{ "components": [{ "kind": "string", "value": "/lib" }] }],
+			},
+			"executable": {
+				"kind": "path",
+				"value": { "components": [{ "kind": "string", "value": "/bin/program" }] },
+			},
+			"env": {
+				"kind": "set",
+				"value": {
+					"kind": "map",
+					"value": {
+						"PATH": [{
+							"kind": "mutation",
+							"value": {
+								"kind": "prefix",
+								"separator": ":",
+								"template": { "components": [{ "kind": "string", "value": "/tangram/bin" }] },
+							},
+						}],
+					},
+				},
+			},
+		});
+		let cx = Cx {
+			artifacts_dir: Path::new("/artifacts"),
+		};
+		let env = BTreeMap::from([("PATH".to_owned(), "/usr/bin".to_owned())]);
+		let arguments = ["input".to_owned(), "--tangram-print-manifest".to_owned()];
+		let explanation = cx.explain(&manifest, "/wrapper", &arguments, env).unwrap();
+		assert_eq!(explanation.path.as_deref(), Some("/artifacts/fil_01ld"));
+		assert_eq!(
+			explanation.argv,
+			[
+				"/artifacts/fil_01ld",
+				"--inhibit-cache",
+				"--library-path",
+				"/lib",
+				"--argv0",
+				"/wrapper",
+				"/bin/program",
+				"input",
+			]
+		);
+		assert_eq!(explanation.env["PATH"], "/tangram/bin:/usr/bin");
+		assert_eq!(
+			explanation.env["TANGRAM_INJECTION_IDENTITY_PATH"],
+			"/wrapper"
+		);
+	}
+}
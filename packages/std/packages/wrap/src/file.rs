@@ -1,10 +1,10 @@
-use crate::FileLocation;
+use crate::{Error, FileLocation};
 use num::ToPrimitive as _;
 use rustix::mm::{MapFlags, ProtFlags};
 use std::{
-	cmp::Ordering,
-	fmt::Debug,
-	ops::{Deref, DerefMut},
+	borrow::Cow,
+	cell::RefCell,
+	ops::Range,
 	os::{
 		raw::c_void,
 		unix::fs::{FileExt as _, MetadataExt as _},
@@ -13,206 +13,648 @@ use std::{
 };
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
-#[allow(clippy::struct_field_names)]
-pub struct File {
+/// The size of the buffer used to copy files when `copy_file_range` is not supported.
+const COPY_BUFFER_SIZE: usize = 1 << 20;
+
+/// The bytes of a binary. A file is either a read-only memory mapping, a staged edit of a file on disk, or a buffer in memory. A borrowed buffer is copied the first time it is modified.
+pub struct File<'a> {
+	backing: Backing<'a>,
+}
+
+enum Backing<'a> {
+	Mapped(Mapped),
+	Staged(Staged),
+	Memory(Cow<'a, [u8]>),
+}
+
+/// A read-only mapping of a file.
+struct Mapped {
+	data: *mut c_void,
+	len: usize,
+}
+
+/// An edit of a file that is written out in a single pass.
+///
+/// Edits only change the list of `pieces` that make up the file, and persisting it streams the pieces, so the ranges of the original file are copied without reading them into memory. Reads see an image of the edited file at the start of a large anonymous reservation, which is brought up to date lazily when a `stale` range is read. Ranges of the original file that an edit moved by a multiple of the page size are mapped from the file again, so only the pages holding inserted bytes or unaligned moves use memory.
+struct Staged {
 	file: std::fs::File,
-	readonly: bool,
 	data: *mut c_void,
-	len: u64,
+	capacity: usize,
+	len: usize,
+	pieces: Vec<Piece>,
+	stale: RefCell<Vec<Range<usize>>>,
 }
 
-impl File {
-	pub(crate) fn open(path: impl AsRef<Path>, readonly: bool) -> std::io::Result<Self> {
-		let file = std::fs::OpenOptions::new()
-			.read(true)
-			.write(!readonly)
-			.open(path)?;
-		let mut file = Self {
-			file,
-			readonly,
-			data: std::ptr::null_mut(),
-			len: 0,
-		};
+/// A part of an edited file.
+enum Piece {
+	/// A range of the original file.
+	File(Range<usize>),
 
-		file.mmap()?;
+	/// Bytes added by an edit.
+	Bytes(Vec<u8>),
+}
 
-		Ok(file)
+impl File<'static> {
+	/// Map a file to read it.
+	pub(crate) fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+		let file = std::fs::File::open(path)?;
+		let len = file.metadata()?.size().to_usize().unwrap();
+		let data = if len == 0 {
+			// Empty files cannot be mapped.
+			std::ptr::null_mut()
+		} else {
+			unsafe {
+				rustix::mm::mmap(
+					std::ptr::null_mut(),
+					len,
+					ProtFlags::READ,
+					MapFlags::SHARED,
+					&file,
+					0,
+				)?
+			}
+		};
+		Ok(Self {
+			backing: Backing::Mapped(Mapped { data, len }),
+		})
 	}
 
-	pub(crate) fn delete(&mut self, location: FileLocation) -> std::io::Result<()> {
-		self.munmap()?;
-		let len = self.file.metadata()?.len();
+	/// Open a file to edit it. The file is not modified until the edit is persisted.
+	pub(crate) fn edit(path: impl AsRef<Path>) -> std::io::Result<Self> {
+		let file = std::fs::File::open(path)?;
+		let len = file.metadata()?.size().to_usize().unwrap();
 
-		// Read the range of bytes from the end of the file
-		let position = location.end().to_u64().unwrap();
-		let mut buf = vec![0u8; (len - position).to_usize().unwrap()];
-		self.file.read_exact_at(&mut buf, position)?;
+		// Reserve room for the file to grow. The reservation only uses address space until it is written.
+		let capacity = align(len + (len / 2).max(1 << 30), rustix::param::page_size());
+		let data = unsafe {
+			rustix::mm::mmap_anonymous(
+				std::ptr::null_mut(),
+				capacity,
+				ProtFlags::READ | ProtFlags::WRITE,
+				MapFlags::PRIVATE | MapFlags::NORESERVE,
+			)?
+		};
+		let staged = Staged {
+			file,
+			data,
+			capacity,
+			len,
+			pieces: if len > 0 {
+				vec![Piece::File(0..len)]
+			} else {
+				Vec::new()
+			},
+			stale: RefCell::new(Vec::new()),
+		};
+		if len > 0 {
+			unsafe {
+				rustix::mm::mmap(
+					data,
+					align(len, rustix::param::page_size()),
+					ProtFlags::READ | ProtFlags::WRITE,
+					MapFlags::PRIVATE | MapFlags::FIXED,
+					&staged.file,
+					0,
+				)?;
+			}
+		}
+		Ok(Self {
+			backing: Backing::Staged(staged),
+		})
+	}
 
-		// Write the bytes.
-		self.file
-			.write_all_at(&buf, location.offset.to_u64().unwrap())?;
+	/// Create a file from a buffer it owns.
+	#[must_use]
+	pub fn from_vec(bytes: Vec<u8>) -> Self {
+		Self {
+			backing: Backing::Memory(Cow::Owned(bytes)),
+		}
+	}
+}
 
-		// Truncate the file.
-		self.file.set_len(len - location.length.to_u64().unwrap())?;
+impl<'a> File<'a> {
+	/// Create a file from a borrowed buffer.
+	#[must_use]
+	pub fn from_bytes(bytes: &'a [u8]) -> Self {
+		Self {
+			backing: Backing::Memory(Cow::Borrowed(bytes)),
+		}
+	}
 
-		// Map the file again.
-		self.mmap()?;
-		Ok(())
+	/// Get the contents of the file.
+	pub fn into_vec(self) -> std::io::Result<Vec<u8>> {
+		match self.backing {
+			Backing::Mapped(_) | Backing::Staged(_) => Ok(self.as_slice()?.to_vec()),
+			Backing::Memory(bytes) => Ok(bytes.into_owned()),
+		}
 	}
 
-	pub fn insert(&mut self, bytes: &[u8], position: u64) -> std::io::Result<()> {
-		// Unmap the file.
-		self.munmap()?;
+	/// Get the contents of the file. This brings the whole image of an edited file up to date, so prefer [`File::bytes`] for a range of it.
+	pub fn as_slice(&self) -> std::io::Result<&[u8]> {
+		match &self.backing {
+			Backing::Mapped(mapped) => Ok(mapped.as_slice()),
+			Backing::Staged(staged) => staged.as_slice(),
+			Backing::Memory(bytes) => Ok(bytes),
+		}
+	}
 
-		// Get its length.
-		let len = self.file.metadata()?.len();
+	/// Write an edited file to `path`, replacing it atomically.
+	pub(crate) fn persist(&self, path: &Path) -> std::io::Result<()> {
+		match &self.backing {
+			Backing::Staged(staged) => write_atomic(path, |output| staged.write_to(output)),
+			Backing::Mapped(_) | Backing::Memory(_) => {
+				write_atomic(path, |output| output.write_all_at(self.as_slice()?, 0))
+			},
+		}
+	}
 
+	pub(crate) fn delete(&mut self, location: FileLocation) -> std::io::Result<()> {
+		self.splice(location.range(), &[])
+	}
+
+	pub fn insert(&mut self, bytes: &[u8], position: u64) -> std::io::Result<()> {
 		// Check to make sure the position isn't past the end of the file.
-		if position > len {
+		let position = position.to_usize().unwrap();
+		if position > self.len() {
 			return Err(std::io::Error::other("position past the end of the file"));
 		}
+		self.splice(position..position, bytes)
+	}
 
-		// Read the range of bytes from the end of the file
-		let mut buf = vec![0u8; (len - position).try_into().unwrap()];
-		self.file.read_exact_at(&mut buf, position)?;
-
-		// Insert the new data.
-		self.file.write_all_at(bytes, position)?;
-		rustix::fs::fsync(&self.file)?;
+	pub fn append(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+		self.splice(self.len()..self.len(), bytes)
+	}
 
-		// Write the old data.
-		self.file
-			.write_all_at(&buf, position + bytes.len().to_u64().unwrap())?;
+	pub fn replace(&mut self, location: FileLocation, bytes: &[u8]) -> std::io::Result<()> {
+		self.splice(location.range(), bytes)
+	}
 
-		// Re-map the file.
-		self.mmap()?;
+	/// Overwrite bytes without changing the size of the file.
+	pub(crate) fn patch(&mut self, offset: usize, bytes: &[u8]) -> crate::Result<()> {
+		let range = offset..offset + bytes.len();
+		if range.end > self.len() {
+			return Err(Error::TruncatedHeader("patch"));
+		}
+		match &mut self.backing {
+			Backing::Mapped(_) => {
+				return Err(std::io::Error::other("the file is read-only").into());
+			},
+			Backing::Staged(staged) => staged.splice(range, bytes)?,
+			Backing::Memory(data) => data.to_mut()[range].copy_from_slice(bytes),
+		}
 		Ok(())
 	}
 
-	pub fn append(&mut self, bytes: &[u8]) -> std::io::Result<()> {
-		self.munmap()?;
-		let len = self.file.metadata()?.len();
-		self.file.write_at(bytes, len)?;
-		rustix::fs::fsync(&self.file)?;
-		self.mmap()?;
-		Ok(())
+	/// Read a header, modify it, and write it back.
+	pub(crate) fn update<T>(
+		&mut self,
+		offset: impl TryInto<usize> + Copy,
+		f: impl FnOnce(&mut T),
+	) -> crate::Result<()>
+	where
+		T: FromBytes + KnownLayout + Sized + Immutable + IntoBytes,
+	{
+		let mut value = self.read_at_unaligned::<T>(offset)?;
+		f(&mut value);
+		let offset = offset
+			.try_into()
+			.map_err(|_| Error::TruncatedHeader(type_name::<T>()))?;
+		self.patch(offset, value.as_bytes())
 	}
 
-	pub fn replace(&mut self, location: FileLocation, bytes: &[u8]) -> std::io::Result<()> {
-		let range = location.range();
-		let original_size: usize = self.file_size()?.try_into().unwrap();
-		match range.len().cmp(&bytes.len()) {
-			Ordering::Equal => {},
-			Ordering::Greater => {
-				self[range.start..(range.start + bytes.len())].copy_from_slice(bytes);
-				self.copy_within(range.end..original_size, range.start + bytes.len());
-				self.munmap()?;
-				let diff = range.len() - bytes.len();
-				let new_size = original_size - diff;
-				self.file.set_len(new_size.try_into().unwrap())?;
-				self.mmap()?;
-			},
-			Ordering::Less => {
-				let diff = bytes.len() - range.len();
-				let new_size = original_size + diff;
-				self.munmap()?;
-				self.file.set_len(new_size.try_into().unwrap())?;
-				self.mmap()?;
-				self.copy_within(range.end..original_size, range.end + diff);
-				self[range.start..(range.start + bytes.len())].copy_from_slice(bytes);
+	/// Replace a range of the file with bytes.
+	fn splice(&mut self, range: Range<usize>, bytes: &[u8]) -> std::io::Result<()> {
+		if range.start > range.end || range.end > self.len() {
+			return Err(std::io::Error::other("range past the end of the file"));
+		}
+		match &mut self.backing {
+			Backing::Mapped(_) => Err(std::io::Error::other("the file is read-only")),
+			Backing::Staged(staged) => staged.splice(range, bytes),
+			Backing::Memory(data) => {
+				data.to_mut().splice(range, bytes.iter().copied());
+				Ok(())
 			},
 		}
-		Ok(())
 	}
 
 	pub fn file_size(&self) -> std::io::Result<u64> {
-		self.file.metadata().map(|meta| meta.size())
+		Ok(self.len().to_u64().unwrap())
 	}
 
-	pub(crate) fn read_at<T>(&self, offset: impl TryInto<usize, Error: Debug>) -> &T
-	where
-		T: FromBytes + KnownLayout + Sized + Immutable,
-	{
-		let offset = offset.try_into().unwrap();
-		T::ref_from_bytes(&self[offset..(offset + size_of::<T>())]).unwrap()
+	/// Get the size of the file. Unlike [`File::as_slice`], this does not bring the image of an edited file up to date.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		match &self.backing {
+			Backing::Mapped(mapped) => mapped.len,
+			Backing::Staged(staged) => staged.len,
+			Backing::Memory(bytes) => bytes.len(),
+		}
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Get a range of the file, bringing only that range of an edited file up to date.
+	fn view(&self, range: Range<usize>) -> std::io::Result<Option<&[u8]>> {
+		if range.start > range.end || range.end > self.len() {
+			return Ok(None);
+		}
+		match &self.backing {
+			Backing::Mapped(mapped) => Ok(Some(&mapped.as_slice()[range])),
+			Backing::Staged(staged) => staged.view(range).map(Some),
+			Backing::Memory(bytes) => Ok(Some(&bytes[range])),
+		}
 	}
 
-	pub(crate) fn read_at_mut<T>(&mut self, offset: impl TryInto<usize, Error: Debug>) -> &'_ mut T
+	pub(crate) fn read_at<T>(&self, offset: impl TryInto<usize>) -> crate::Result<&T>
 	where
-		T: FromBytes + KnownLayout + Sized + Immutable + IntoBytes,
+		T: FromBytes + KnownLayout + Sized + Immutable,
 	{
-		let offset = offset.try_into().unwrap();
-		T::mut_from_bytes(&mut self[offset..(offset + size_of::<T>())]).unwrap()
+		let range = Self::range_of::<T>(offset)?;
+		let bytes = self
+			.view(range)?
+			.ok_or(Error::TruncatedHeader(type_name::<T>()))?;
+		T::ref_from_bytes(bytes).map_err(|_| Error::TruncatedHeader(type_name::<T>()))
 	}
 
-	pub(crate) fn read_at_unaligned<T>(&self, offset: impl TryInto<usize, Error: Debug>) -> T
+	pub(crate) fn read_at_unaligned<T>(&self, offset: impl TryInto<usize>) -> crate::Result<T>
 	where
 		T: FromBytes + KnownLayout + Sized + Immutable + IntoBytes,
 	{
+		let range = Self::range_of::<T>(offset)?;
+		let bytes = self
+			.view(range)?
+			.ok_or(Error::TruncatedHeader(type_name::<T>()))?;
 		let mut value = T::new_zeroed();
-		let offset = offset.try_into().unwrap();
-		let range = offset..(offset + size_of::<T>());
-		value.as_mut_bytes().copy_from_slice(&self[range]);
-		value
-	}
-
-	fn mmap(&mut self) -> std::io::Result<()> {
-		assert!(self.data.is_null());
-		self.len = align(self.file.metadata()?.size(), 4096);
-		let mut prot = ProtFlags::READ;
-		if !self.readonly {
-			prot |= ProtFlags::WRITE;
-		}
-		let flags = MapFlags::SHARED;
-		self.data = unsafe {
-			rustix::mm::mmap(
-				std::ptr::null_mut(),
-				self.len.to_usize().unwrap(),
-				prot,
-				flags,
-				&self.file,
-				0,
-			)?
+		value.as_mut_bytes().copy_from_slice(bytes);
+		Ok(value)
+	}
+
+	/// Get the bytes at a location, failing if it extends past the end of the file.
+	pub(crate) fn bytes(&self, location: FileLocation, what: &'static str) -> crate::Result<&[u8]> {
+		self.view(location.range())?
+			.ok_or(Error::TruncatedHeader(what))
+	}
+
+	fn range_of<T>(offset: impl TryInto<usize>) -> crate::Result<Range<usize>> {
+		let offset = offset
+			.try_into()
+			.map_err(|_| Error::TruncatedHeader(type_name::<T>()))?;
+		let end = offset
+			.checked_add(size_of::<T>())
+			.ok_or(Error::TruncatedHeader(type_name::<T>()))?;
+		Ok(offset..end)
+	}
+}
+
+impl Mapped {
+	fn as_slice(&self) -> &[u8] {
+		if self.data.is_null() {
+			return &[];
+		}
+		unsafe { std::slice::from_raw_parts(self.data.cast(), self.len) }
+	}
+}
+
+impl Staged {
+	fn as_slice(&self) -> std::io::Result<&[u8]> {
+		self.view(0..self.len)
+	}
+
+	/// Get a range of the image, bringing it up to date first.
+	fn view(&self, range: Range<usize>) -> std::io::Result<&[u8]> {
+		let mut rebuild = Vec::new();
+		let mut stale = self.stale.borrow_mut();
+		for stale_range in std::mem::take(&mut *stale) {
+			let start = stale_range.start.max(range.start);
+			let end = stale_range.end.min(range.end);
+			if start >= end {
+				stale.push(stale_range);
+				continue;
+			}
+			rebuild.push(start..end);
+			if stale_range.start < start {
+				stale.push(stale_range.start..start);
+			}
+			if end < stale_range.end {
+				stale.push(end..stale_range.end);
+			}
+		}
+		drop(stale);
+		for (i, rebuild_range) in rebuild.iter().enumerate() {
+			if let Err(error) = self.rebuild(rebuild_range.clone()) {
+				// The ranges that were not rebuilt are still stale.
+				self.stale.borrow_mut().extend(rebuild[i..].iter().cloned());
+				return Err(error);
+			}
+		}
+		Ok(unsafe {
+			std::slice::from_raw_parts(self.data.cast::<u8>().add(range.start), range.len())
+		})
+	}
+
+	/// Write the pieces of the file to a range of the image. No reference to the range may be live.
+	fn rebuild(&self, range: Range<usize>) -> std::io::Result<()> {
+		let page_size = rustix::param::page_size();
+		let data = self.data.cast::<u8>();
+		let mut position = 0;
+		for piece in &self.pieces {
+			let piece_range = position..position + piece.len();
+			position = piece_range.end;
+			let start = piece_range.start.max(range.start);
+			let end = piece_range.end.min(range.end);
+			if start >= end {
+				continue;
+			}
+			match piece {
+				Piece::Bytes(bytes) => unsafe {
+					let bytes = &bytes[start - piece_range.start..end - piece_range.start];
+					std::ptr::copy_nonoverlapping(bytes.as_ptr(), data.add(start), bytes.len());
+				},
+				Piece::File(source) => {
+					let offset = source.start + start - piece_range.start;
+
+					// Map the whole pages whose position matches their offset in the file, and read the rest.
+					let (first, last) = if start % page_size == offset % page_size {
+						(
+							align(start, page_size).min(end),
+							end / page_size * page_size,
+						)
+					} else {
+						(end, end)
+					};
+					if first < last {
+						unsafe {
+							rustix::mm::mmap(
+								data.add(first).cast(),
+								last - first,
+								ProtFlags::READ | ProtFlags::WRITE,
+								MapFlags::PRIVATE | MapFlags::FIXED,
+								&self.file,
+								(offset + first - start).to_u64().unwrap(),
+							)?;
+						}
+					}
+					for range in [start..first, last.max(first)..end] {
+						if range.is_empty() {
+							continue;
+						}
+						let buffer = unsafe {
+							std::slice::from_raw_parts_mut(data.add(range.start), range.len())
+						};
+						self.file.read_exact_at(
+							buffer,
+							(offset + range.start - start).to_u64().unwrap(),
+						)?;
+					}
+				},
+			}
+		}
+		Ok(())
+	}
+
+	fn splice(&mut self, range: Range<usize>, bytes: &[u8]) -> std::io::Result<()> {
+		let len = self.len - range.len() + bytes.len();
+		if len > self.capacity {
+			return Err(std::io::Error::other("the edit exceeds the reserved size"));
+		}
+
+		// Replace the pieces in the range.
+		let (mut pieces, rest) = split_pieces(std::mem::take(&mut self.pieces), range.start);
+		let (_, rest) = split_pieces(rest, range.len());
+		if !bytes.is_empty() {
+			pieces.push(Piece::Bytes(bytes.to_vec()));
+		}
+		pieces.extend(rest);
+		self.pieces = pieces;
+
+		// Everything after the start of the range moves if the size changes.
+		let end = if range.len() == bytes.len() {
+			range.end
+		} else {
+			len
 		};
+		self.stale.get_mut().push(range.start..end);
+		self.len = len;
 		Ok(())
 	}
 
-	fn munmap(&mut self) -> std::io::Result<()> {
-		assert!(!self.data.is_null());
-		unsafe {
-			rustix::mm::munmap(self.data, self.len.to_usize().unwrap())?;
+	/// Write the edited file. The ranges of the original file are copied without reading them into memory.
+	fn write_to(&self, output: &std::fs::File) -> std::io::Result<()> {
+		let mut position = 0;
+		for piece in &self.pieces {
+			match piece {
+				Piece::File(range) => copy_range(&self.file, output, range.clone(), position)?,
+				Piece::Bytes(bytes) => output.write_all_at(bytes, position.to_u64().unwrap())?,
+			}
+			position += piece.len();
 		}
-		self.data = std::ptr::null_mut();
-		self.len = 0;
+		output.set_len(self.len.to_u64().unwrap())?;
 		Ok(())
 	}
 }
 
-impl Drop for File {
-	fn drop(&mut self) {
-		self.munmap().ok();
+impl Piece {
+	fn len(&self) -> usize {
+		match self {
+			Self::File(range) => range.len(),
+			Self::Bytes(bytes) => bytes.len(),
+		}
+	}
+
+	fn split_at(self, at: usize) -> (Self, Self) {
+		match self {
+			Self::File(range) => (
+				Self::File(range.start..range.start + at),
+				Self::File(range.start + at..range.end),
+			),
+			Self::Bytes(mut bytes) => {
+				let rest = bytes.split_off(at);
+				(Self::Bytes(bytes), Self::Bytes(rest))
+			},
+		}
 	}
 }
 
-impl Deref for File {
-	type Target = [u8];
-	fn deref(&self) -> &Self::Target {
-		if self.len == 0 {
-			return &[];
+/// Split a list of pieces at a position.
+fn split_pieces(pieces: Vec<Piece>, at: usize) -> (Vec<Piece>, Vec<Piece>) {
+	let mut head = Vec::new();
+	let mut tail = Vec::new();
+	let mut position = 0;
+	for piece in pieces {
+		let len = piece.len();
+		if position + len <= at {
+			head.push(piece);
+		} else if position >= at {
+			tail.push(piece);
+		} else {
+			let (first, second) = piece.split_at(at - position);
+			head.push(first);
+			tail.push(second);
 		}
-		unsafe { std::slice::from_raw_parts(self.data.cast(), self.len.to_usize().unwrap()) }
+		position += len;
 	}
+	(head, tail)
 }
 
-impl DerefMut for File {
-	fn deref_mut(&mut self) -> &mut Self::Target {
-		if self.len == 0 {
-			return &mut [];
+/// Copy a range of one file to an offset of another, with `copy_file_range` if possible.
+fn copy_range(
+	input: &std::fs::File,
+	output: &std::fs::File,
+	range: Range<usize>,
+	offset: usize,
+) -> std::io::Result<()> {
+	let mut offset_in = range.start.to_u64().unwrap();
+	let mut offset_out = offset.to_u64().unwrap();
+	let end = range.end.to_u64().unwrap();
+	#[cfg(target_os = "linux")]
+	while offset_in < end {
+		let length = (end - offset_in).to_usize().unwrap();
+		match rustix::fs::copy_file_range(
+			input,
+			Some(&mut offset_in),
+			output,
+			Some(&mut offset_out),
+			length,
+		) {
+			Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+			Ok(_) => (),
+			Err(
+				rustix::io::Errno::XDEV
+				| rustix::io::Errno::NOSYS
+				| rustix::io::Errno::OPNOTSUPP
+				| rustix::io::Errno::INVAL,
+			) => break,
+			Err(error) => return Err(error.into()),
 		}
-		unsafe { std::slice::from_raw_parts_mut(self.data.cast(), self.len.to_usize().unwrap()) }
 	}
+
+	// Copy the rest through a buffer.
+	let mut buffer = vec![0; COPY_BUFFER_SIZE.min((end - offset_in).to_usize().unwrap())];
+	while offset_in < end {
+		let length = buffer.len().min((end - offset_in).to_usize().unwrap());
+		input.read_exact_at(&mut buffer[..length], offset_in)?;
+		output.write_all_at(&buffer[..length], offset_out)?;
+		offset_in += length.to_u64().unwrap();
+		offset_out += length.to_u64().unwrap();
+	}
+	Ok(())
 }
 
-fn align(m: u64, n: u64) -> u64 {
+/// Replace the contents of a file by writing a temporary file in the same directory and renaming it over the original. The mode and ownership of the original are preserved. Symlinks are followed, so the file they point to is replaced.
+fn write_atomic(
+	path: &Path,
+	write: impl FnOnce(&std::fs::File) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+	let path = std::fs::canonicalize(path)?;
+	let metadata = std::fs::metadata(&path)?;
+	let directory = path
+		.parent()
+		.ok_or_else(|| std::io::Error::other("the file has no parent directory"))?;
+
+	// Write the contents to a temporary file. It is removed if any step fails.
+	let temp = tempfile::NamedTempFile::new_in(directory)?;
+	let file = temp.as_file();
+	write(file)?;
+
+	// Restore the ownership before the mode, because changing the owner clears the setuid and setgid bits.
+	let owner = file.metadata()?;
+	if (owner.uid(), owner.gid()) != (metadata.uid(), metadata.gid()) {
+		rustix::fs::fchown(
+			file,
+			Some(rustix::fs::Uid::from_raw(metadata.uid())),
+			Some(rustix::fs::Gid::from_raw(metadata.gid())),
+		)?;
+	}
+	file.set_permissions(metadata.permissions())?;
+	file.sync_all()?;
+
+	// Rename the temporary file over the original and sync the directory, so the rename is durable.
+	temp.persist(&path).map_err(|error| error.error)?;
+	std::fs::File::open(directory)?.sync_all()?;
+
+	Ok(())
+}
+
+impl Drop for Mapped {
+	fn drop(&mut self) {
+		if !self.data.is_null() {
+			unsafe {
+				rustix::mm::munmap(self.data, self.len).ok();
+			}
+		}
+	}
+}
+
+impl Drop for Staged {
+	fn drop(&mut self) {
+		unsafe {
+			rustix::mm::munmap(self.data, self.capacity).ok();
+		}
+	}
+}
+
+/// Get the unqualified name of a type, for error messages.
+pub(crate) fn type_name<T>() -> &'static str {
+	let name = std::any::type_name::<T>();
+	name.rsplit("::").next().unwrap_or(name)
+}
+
+fn align(m: usize, n: usize) -> usize {
 	(m + n - 1) & !(n - 1)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::File;
+	use crate::FileLocation;
+
+	#[test]
+	fn staged_edit() {
+		let original = (0..100_000u32)
+			.flat_map(u32::to_le_bytes)
+			.collect::<Vec<_>>();
+		let temp = tempfile::NamedTempFile::new().unwrap();
+		std::fs::write(temp.path(), &original).unwrap();
+
+		// Apply the same edits to a staged file and to a buffer, reading between them. Inserting a page moves the rest of the file by a multiple of the page size.
+		let edit = |file: &mut File| {
+			let mut reads = Vec::new();
+			file.patch(10, b"patched").unwrap();
+			file.insert(b"inserted", 300_000).unwrap();
+			reads.push(file.read_at_unaligned::<[u8; 16]>(299_996).unwrap());
+			file.insert(&vec![1; rustix::param::page_size()], 8192)
+				.unwrap();
+			reads.push(file.read_at_unaligned::<[u8; 16]>(8190).unwrap());
+			reads.push(file.read_at_unaligned::<[u8; 16]>(200_000).unwrap());
+			file.patch(100, b"after").unwrap();
+			file.delete(FileLocation {
+				offset: 350_000,
+				length: 1000,
+			})
+			.unwrap();
+			file.replace(
+				FileLocation {
+					offset: 200,
+					length: 4,
+				},
+				b"same",
+			)
+			.unwrap();
+			file.append(b"appended").unwrap();
+			reads
+		};
+		let mut staged = File::edit(temp.path()).unwrap();
+		let reads = edit(&mut staged);
+		let mut expected = File::from_vec(original);
+		assert_eq!(reads, edit(&mut expected));
+		assert_eq!(staged.as_slice().unwrap(), expected.as_slice().unwrap());
+		staged.persist(temp.path()).unwrap();
+		assert_eq!(
+			std::fs::read(temp.path()).unwrap(),
+			expected.into_vec().unwrap()
+		);
+	}
+}
@@ -0,0 +1,273 @@
+use crate::{
+	Compression, Encoding, Error, FileLocation, Footer, Format, MAGIC, Result, Section, file::File,
+};
+use serde_json::Value;
+use std::{collections::BTreeSet, path::Path};
+
+/// Everything `wrap` knows about a (possibly) wrapped binary.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Inspection {
+	pub format: Format,
+	pub file_size: u64,
+	pub sections: Vec<Section>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub location: Option<FileLocation>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub footer: Option<FooterInfo>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub manifest: Option<ManifestInfo>,
+}
+
+/// The decoded manifest footer.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct FooterInfo {
+	pub size: u64,
+	pub version: u64,
+	pub magic: String,
+	pub valid: bool,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub encoding: Option<Encoding>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub compression: Option<Compression>,
+
+	/// The BLAKE3 checksum of the stored manifest, in hex.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub checksum: Option<String>,
+}
+
+/// A summary of the manifest, with templates rendered to strings.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ManifestInfo {
+	pub executable: ExecutableInfo,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub interpreter: Option<InterpreterInfo>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub env: Option<Value>,
+
+	pub args: Vec<String>,
+
+	/// The IDs of every artifact the manifest refers to.
+	pub artifacts: Vec<String>,
+
+	/// The manifest as it was stored.
+	pub raw: Value,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ExecutableInfo {
+	pub kind: String,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content: Option<String>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub address: Option<u64>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct InterpreterInfo {
+	pub kind: String,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+
+	pub library_paths: Vec<String>,
+	pub preloads: Vec<String>,
+	pub args: Vec<String>,
+}
+
+/// Inspect a binary, reporting its format, the location of the wrapper sections, and the decoded manifest.
+pub fn inspect(path: impl AsRef<Path>, format: Option<Format>) -> Result<Inspection> {
+	let path = path.as_ref();
+	let format = match format {
+		Some(format) => format,
+		None => crate::detect_format(path)?
+			.ok_or_else(|| Error::UnsupportedFormat("unknown input file format".to_owned()))?,
+	};
+	let file = File::open(path)?;
+	let binary_format = crate::create_format(&file, Some(format))?;
+	let file_size = file.file_size()?;
+	let sections = binary_format.sections(&file)?;
+	let Some(location) = binary_format.read_manifest(&file)? else {
+		return Ok(Inspection {
+			format,
+			file_size,
+			sections,
+			location: None,
+			footer: None,
+			manifest: None,
+		});
+	};
+
+	// Decode the footer.
+	let data = file.bytes(location, "manifest")?;
+	let footer = Footer::read(data)?;
+	let layout = (footer.magic == MAGIC && footer.version <= crate::VERSION)
+		.then(|| crate::encoding::layout(data))
+		.transpose()?;
+	let footer_info = FooterInfo {
+		size: footer.size,
+		version: footer.version,
+		magic: footer.magic.escape_ascii().to_string(),
+		valid: layout.is_some(),
+		encoding: layout.as_ref().map(|layout| layout.options.encoding),
+		compression: layout.as_ref().map(|layout| layout.options.compression),
+		checksum: layout
+			.as_ref()
+			.and_then(|layout| layout.checksum)
+			.map(|checksum| checksum.to_hex().to_string()),
+	};
+
+	// Decode the manifest. Only JSON manifests can be decoded without knowing their type.
+	let manifest = match layout {
+		Some(layout) if layout.options.encoding == Encoding::Json => {
+			let raw: Value = crate::decode(data)?;
+			Some(ManifestInfo::new(raw))
+		},
+		_ => None,
+	};
+
+	Ok(Inspection {
+		format,
+		file_size,
+		sections,
+		location: Some(location),
+		footer: Some(footer_info),
+		manifest,
+	})
+}
+
+impl ManifestInfo {
+	fn new(raw: Value) -> Self {
+		let executable = raw.get("executable").map_or_else(
+			|| ExecutableInfo {
+				kind: "unknown".to_owned(),
+				path: None,
+				content: None,
+				address: None,
+			},
+			|executable| {
+				let kind = executable
+					.get("kind")
+					.and_then(Value::as_str)
+					.unwrap_or("unknown")
+					.to_owned();
+				let value = executable.get("value");
+				ExecutableInfo {
+					path: (kind == "path").then(|| value.map(render)).flatten(),
+					content: (kind == "content").then(|| value.map(render)).flatten(),
+					address: value.and_then(Value::as_u64),
+					kind,
+				}
+			},
+		);
+		let interpreter = raw.get("interpreter").map(|interpreter| InterpreterInfo {
+			kind: interpreter
+				.get("kind")
+				.and_then(Value::as_str)
+				.unwrap_or("unknown")
+				.to_owned(),
+			path: interpreter.get("path").map(render),
+			library_paths: render_all(interpreter.get("libraryPaths")),
+			preloads: render_all(interpreter.get("preloads")),
+			args: render_all(interpreter.get("args")),
+		});
+		let env = raw.get("env").cloned();
+		let args = render_all(raw.get("args"));
+		let mut artifacts = BTreeSet::new();
+		collect_artifacts(&raw, &mut artifacts);
+		Self {
+			executable,
+			interpreter,
+			env,
+			args,
+			artifacts: artifacts.into_iter().collect(),
+			raw,
+		}
+	}
+}
+
+/// Render a template or other manifest value to a string. Artifacts are rendered as `${id}`.
+pub fn render(value: &Value) -> String {
+	match value {
+		Value::String(string) => string.clone(),
+		Value::Object(object) => {
+			if let Some(Value::Array(components)) = object.get("components") {
+				return components.iter().map(render_component).collect();
+			}
+			if let Some(value) = object.get("value") {
+				return render(value);
+			}
+			value.to_string()
+		},
+		Value::Array(values) => values.iter().map(render).collect::<Vec<_>>().join(" "),
+		_ => value.to_string(),
+	}
+}
+
+fn render_component(component: &Value) -> String {
+	let kind = component.get("kind").and_then(Value::as_str);
+	let value = component.get("value").unwrap_or(&Value::Null);
+	match kind {
+		Some("string") => value.as_str().unwrap_or_default().to_owned(),
+		Some("artifact") => {
+			let mut ids = BTreeSet::new();
+			collect_artifacts(value, &mut ids);
+			ids.pop_first()
+				.map_or_else(|| value.to_string(), |id| format!("${{{id}}}"))
+		},
+		Some("placeholder") => {
+			let name = value
+				.get("name")
+				.and_then(Value::as_str)
+				.unwrap_or_default();
+			format!("<{name}>")
+		},
+		_ => render(component),
+	}
+}
+
+fn render_all(values: Option<&Value>) -> Vec<String> {
+	values
+		.and_then(Value::as_array)
+		.map(|values| values.iter().map(render).collect())
+		.unwrap_or_default()
+}
+
+/// Collect every string in the value that is an artifact ID.
+pub(crate) fn collect_artifacts(value: &Value, artifacts: &mut BTreeSet<String>) {
+	match value {
+		Value::String(string) if is_artifact_id(string) => {
+			artifacts.insert(string.clone());
+		},
+		Value::Array(values) => {
+			for value in values {
+				collect_artifacts(value, artifacts);
+			}
+		},
+		Value::Object(object) => {
+			for value in object.values() {
+				collect_artifacts(value, artifacts);
+			}
+		},
+		_ => (),
+	}
+}
+
+pub(crate) fn is_artifact_id(string: &str) -> bool {
+	["dir_", "fil_", "sym_"].iter().any(|prefix| {
+		string
+			.strip_prefix(prefix)
+			.is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_alphanumeric()))
+	})
+}
@@ -1,16 +1,39 @@
+pub use bundle::bundle;
+pub use edit::{Edit, apply_edits, artifact_roots, edit, edit_manifest};
+use encoding::VERSION;
+pub use encoding::{Codec, Compression, Encoding, Options, Payload, decode, encode};
+pub use error::{Error, Result};
+pub use explain::{Explanation, explain};
 pub use file::File;
+pub use inspect::{
+	ExecutableInfo, FooterInfo, Inspection, InterpreterInfo, ManifestInfo, inspect, render,
+};
 use num::ToPrimitive;
+pub use reader::ManifestReader;
 use std::{
 	io::Read,
 	ops::Range,
 	path::{Path, PathBuf},
 	sync::Mutex,
 };
+pub use unwrap::{Unwrapped, find_artifacts_dir, unwrap};
+pub use validate::{ValidationError, validate};
+pub use verify::{Verification, verify};
 use zerocopy::{FromZeros as _, IntoBytes as _};
 
+mod bundle;
+mod edit;
 mod elf;
+mod encoding;
+mod error;
+mod explain;
 mod file;
+mod inspect;
 mod mach;
+mod reader;
+mod unwrap;
+mod validate;
+mod verify;
 
 #[derive(
 	Debug,
@@ -28,8 +51,41 @@ pub(crate) struct Footer {
 	pub magic: [u8; 8],
 }
 
+impl Footer {
+	/// Read the footer from the end of the manifest data.
+	pub(crate) fn read(data: &[u8]) -> Result<Self> {
+		let offset = data
+			.len()
+			.checked_sub(size_of::<Self>())
+			.ok_or(Error::BadFooter("the manifest section is too small"))?;
+		let mut footer = Self::new_zeroed();
+		footer.as_mut_bytes().copy_from_slice(&data[offset..]);
+		Ok(footer)
+	}
+
+	/// Check the magic number and version, and get the manifest data that precedes the footer.
+	pub(crate) fn payload<'a>(&self, data: &'a [u8]) -> Result<&'a [u8]> {
+		if self.magic != MAGIC {
+			return Err(Error::BadFooter("invalid magic number"));
+		}
+		if self.version > VERSION {
+			return Err(Error::VersionMismatch {
+				expected: VERSION,
+				found: self.version,
+			});
+		}
+		let size = self
+			.size
+			.to_usize()
+			.filter(|size| *size <= data.len() - size_of::<Self>())
+			.ok_or(Error::BadFooter(
+				"the manifest size exceeds the section size",
+			))?;
+		Ok(&data[0..size])
+	}
+}
+
 pub(crate) const MAGIC: [u8; 8] = *b"tangram\0";
-pub(crate) const VERSION: u64 = 0;
 
 #[derive(serde::Serialize)]
 pub struct Output<T> {
@@ -40,7 +96,8 @@ pub struct Output<T> {
 	pub location: Option<FileLocation>,
 }
 
-#[derive(Copy, Clone, Debug, clap::ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Format {
 	Elf32,
 	Elf64,
@@ -54,13 +111,48 @@ pub struct FileLocation {
 	pub length: usize,
 }
 
+/// A section or load command of a binary that holds wrapper data.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Section {
+	pub name: String,
+	pub offset: usize,
+	pub length: usize,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub address: Option<u64>,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub segment: Option<Segment>,
+}
+
+/// The segment that maps a [`Section`] into memory.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Segment {
+	pub kind: String,
+	pub offset: usize,
+	pub length: usize,
+	pub address: u64,
+	pub memory_size: u64,
+}
+
 pub trait BinaryFormat {
 	fn matches(&self, file: &File) -> bool;
 	fn name(&self) -> &str;
-	fn read_manifest(&self, file: &File) -> Option<FileLocation>;
-	fn write_manifest(&self, file: &mut File, data: &[u8]);
-	fn overwrite_manifest(&self, file: &mut File, data: &[u8]);
-	fn embed(&self, path: &Path, data: &[u8]) -> std::io::Result<()>;
+	fn system(&self, file: &File) -> Result<String>;
+	fn read_manifest(&self, file: &File) -> Result<Option<FileLocation>>;
+	fn manifest_ranges(&self, file: &File) -> Result<Vec<FileLocation>>;
+	fn sections(&self, file: &File) -> Result<Vec<Section>>;
+	fn write_manifest(&self, file: &mut File, data: &[u8]) -> Result<()>;
+	fn overwrite_manifest(&self, file: &mut File, data: &[u8]) -> Result<()>;
+	fn embed(&self, file: &mut File, data: &[u8]) -> Result<()>;
+	fn unwrap(&self, file: &mut File, entrypoint: u64) -> Result<()>;
+}
+
+impl Section {
+	#[must_use]
+	pub fn end(&self) -> usize {
+		self.offset + self.length
+	}
 }
 
 impl FileLocation {
@@ -90,70 +182,136 @@ impl std::ops::IndexMut<FileLocation> for [u8] {
 
 pub fn write_manifest(
 	path: impl AsRef<Path>,
-	manifest: &impl serde::Serialize,
+	manifest: &impl Codec,
 	format: Option<Format>,
-) {
-	let mut file = File::open(path.as_ref(), false).expect("failed to open output file");
-	let format = create_format(&file, format);
-	let mut data = serde_json::to_vec(&manifest).expect("failed to serialize manifest");
-	let footer = Footer {
-		size: data.len().try_into().unwrap(),
-		version: VERSION,
-		magic: MAGIC,
-	};
-	data.extend_from_slice(footer.as_bytes());
-	format.overwrite_manifest(&mut file, &data);
-	assert!(format.read_manifest(&file).is_some(), "expected a manifest");
+	options: Options,
+) -> Result<()> {
+	modify(path.as_ref(), |file| {
+		let format = create_format(file, format)?;
+		let data = encode(manifest, options)?;
+		format.overwrite_manifest(file, &data)?;
+		if format.read_manifest(file)?.is_none() {
+			return Err(Error::BadFooter("the manifest could not be read back"));
+		}
+		Ok(())
+	})
 }
 
-pub fn read_manifest<T: serde::de::DeserializeOwned>(
+pub fn read_manifest<T: Codec>(
 	path: impl AsRef<Path>,
 	format: Option<Format>,
-) -> Output<T> {
-	let file = File::open(path, true).expect("failed to open output file");
-	let format = create_format(&file, format);
-	let Some(location) = format.read_manifest(&file) else {
-		return Output {
+) -> Result<Output<T>> {
+	let file = File::open(path)?;
+	read_manifest_from_file(&file, format)
+}
+
+/// Read a manifest from the bytes of a binary.
+pub fn read_manifest_from_bytes<T: Codec>(
+	bytes: &[u8],
+	format: Option<Format>,
+) -> Result<Output<T>> {
+	read_manifest_from_file(&File::from_bytes(bytes), format)
+}
+
+pub(crate) fn read_manifest_from_file<T: Codec>(
+	file: &File,
+	format: Option<Format>,
+) -> Result<Output<T>> {
+	let format = create_format(file, format)?;
+	let Some(location) = format.read_manifest(file)? else {
+		return Ok(Output {
 			manifest: None,
 			location: None,
-		};
+		});
 	};
-	let data = &file[location];
-	let mut footer = Footer::new_zeroed();
-	footer
-		.as_mut_bytes()
-		.copy_from_slice(&data[data.len() - size_of::<Footer>()..]);
-	assert_eq!(footer.magic, MAGIC, "invalid manifest footer");
-	assert_eq!(footer.version, VERSION, "invalid manifest version");
-	let data = serde_json::from_slice(&data[0..footer.size.to_usize().unwrap()])
-		.expect("failed to deserialize the manifest");
-	Output {
-		manifest: Some(data),
+	let manifest = decode(file.bytes(location, "manifest")?)?;
+	Ok(Output {
+		manifest: Some(manifest),
 		location: Some(location),
-	}
+	})
 }
 
 pub fn embed(
 	path: impl AsRef<Path>,
-	manifest: &impl serde::Serialize,
+	manifest: &impl Codec,
+	format: Option<Format>,
+	options: Options,
+) -> Result<()> {
+	modify(path.as_ref(), |file| {
+		embed_into_file(file, manifest, format, options)
+	})
+}
+
+/// Embed a manifest and wrapper into the bytes of a binary, returning the wrapped binary.
+pub fn embed_into_bytes(
+	bytes: Vec<u8>,
+	manifest: &impl Codec,
+	format: Option<Format>,
+	options: Options,
+) -> Result<Vec<u8>> {
+	let mut file = File::from_vec(bytes);
+	embed_into_file(&mut file, manifest, format, options)?;
+	Ok(file.into_vec()?)
+}
+
+fn embed_into_file(
+	file: &mut File,
+	manifest: &impl Codec,
+	format: Option<Format>,
+	options: Options,
+) -> Result<()> {
+	let format = create_format(file, format)?;
+	let data = encode(manifest, options)?;
+	format.embed(file, &data)
+}
+
+/// Rewrite the manifest of a wrapper with the current version and the given options. Returns `false` if the file has no manifest or already uses them.
+pub fn migrate<T: Codec>(
+	path: impl AsRef<Path>,
 	format: Option<Format>,
-) -> std::io::Result<()> {
-	let file = File::open(path.as_ref(), false).expect("failed to open output file");
-	let format = create_format(&file, format);
+	options: Options,
+) -> Result<bool> {
+	let path = path.as_ref();
+	let file = File::open(path)?;
+	let binary_format = create_format(&file, format)?;
+	let Some(location) = binary_format.read_manifest(&file)? else {
+		return Ok(false);
+	};
+	let data = file.bytes(location, "manifest")?;
+	let layout = encoding::layout(data)?;
+	if layout.footer.version == VERSION && layout.options == options {
+		return Ok(false);
+	}
+	let manifest: T = decode(data)?;
 	drop(file);
-	let data = serde_json::to_vec(&manifest).expect("failed to serialize manifest");
-	format.embed(path.as_ref(), &data)
+	write_manifest(path, &manifest, format, options)?;
+	Ok(true)
 }
 
-pub fn get_format(path: impl AsRef<Path>) -> std::io::Result<Option<Format>> {
+/// Modify a file in memory and then atomically replace it, so that a failure never leaves it partially written.
+pub(crate) fn modify<T>(path: &Path, f: impl FnOnce(&mut File) -> Result<T>) -> Result<T> {
+	let mut file = File::edit(path)?;
+	let output = f(&mut file)?;
+	file.persist(path)?;
+	Ok(output)
+}
+
+pub fn get_format(path: impl AsRef<Path>) -> Result<Option<Format>> {
 	let mut magic = [0u8; 16];
-	std::fs::File::open(path)?.read_exact(&mut magic)?;
-	if magic.starts_with(elf::sys::ELFMAG) {
-		if magic[elf::sys::EI_DATA] != elf::sys::ELFDATA2LSB {
+	match std::fs::File::open(path)?.read_exact(&mut magic) {
+		Ok(()) => (),
+		Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+		Err(error) => return Err(error.into()),
+	}
+	if magic[0..4] == elf::sys::ELFMAG[0..4] {
+		if !matches!(
+			magic[elf::sys::EI_DATA],
+			elf::sys::ELFDATA2LSB | elf::sys::ELFDATA2MSB
+		) {
 			return Ok(None);
 		}
 		if magic[elf::sys::EI_CLASS] == elf::sys::ELFCLASS32 {
-			return Ok(Some(Format::Elf64));
+			return Ok(Some(Format::Elf32));
 		}
 		if magic[elf::sys::EI_CLASS] == elf::sys::ELFCLASS64 {
 			return Ok(Some(Format::Elf64));
@@ -165,7 +323,10 @@ pub fn get_format(path: impl AsRef<Path>) -> std::io::Result<Option<Format>> {
 	Ok(None)
 }
 
-fn create_format(file: &File, hint: Option<Format>) -> Box<dyn BinaryFormat + 'static> {
+pub(crate) fn create_format(
+	file: &File,
+	hint: Option<Format>,
+) -> Result<Box<dyn BinaryFormat + 'static>> {
 	let elf32 = elf::Elf32;
 	let elf64 = elf::Elf64;
 	let mach64 = mach::Mach64;
@@ -186,24 +347,29 @@ fn create_format(file: &File, hint: Option<Format>) -> Box<dyn BinaryFormat + 's
 			}
 			None
 		})
-		.expect("unknown input file format");
+		.ok_or_else(|| Error::UnsupportedFormat("unknown input file format".to_owned()))?;
 	let format = match format {
 		Format::Elf32 => Box::new(elf32) as Box<dyn BinaryFormat>,
 		Format::Elf64 => Box::new(elf64),
 		Format::Mach64 => Box::new(mach64),
 		Format::MachUniversal => Box::new(mach_universal),
 	};
-	assert!(format.matches(file), "invalid {} file", format.name());
+	if !format.matches(file) {
+		return Err(Error::UnsupportedFormat(format!(
+			"invalid {} file",
+			format.name()
+		)));
+	}
 	tracing::info!(format = %format.name(), "created format");
-	format
+	Ok(format)
 }
 
-pub fn detect_format(path: impl AsRef<Path>) -> std::io::Result<Option<Format>> {
+pub fn detect_format(path: impl AsRef<Path>) -> Result<Option<Format>> {
 	let elf32 = elf::Elf32;
 	let elf64 = elf::Elf64;
 	let mach64 = mach::Mach64;
 	let mach_universal = mach::MachUniversal;
-	let file = File::open(path, true)?;
+	let file = File::open(path)?;
 	if elf32.matches(&file) {
 		return Ok(Some(Format::Elf32));
 	}
@@ -219,13 +385,18 @@ pub fn detect_format(path: impl AsRef<Path>) -> std::io::Result<Option<Format>>
 	Ok(None)
 }
 
-static OBJCOPY_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+/// Convert an offset or size to the width of a header field.
+pub(crate) fn convert<T, U>(value: T) -> Result<U>
+where
+	U: TryFrom<T>,
+{
+	U::try_from(value)
+		.map_err(|_| Error::UnsupportedFormat("value out of range for the file format".to_owned()))
+}
+
 static WRAPPER_BIN_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
 static WRAPPER_EXE_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
-
-pub fn set_objcopy_path(p: impl AsRef<Path>) {
-	OBJCOPY_PATH.lock().unwrap().replace(p.as_ref().to_owned());
-}
+static WRAPPERS_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
 
 pub fn set_wrapper_bin_path(p: impl AsRef<Path>) {
 	WRAPPER_BIN_PATH
@@ -241,13 +412,33 @@ pub fn set_wrapper_exe_path(p: impl AsRef<Path>) {
 		.replace(p.as_ref().to_owned());
 }
 
-pub(crate) fn objcopy_path() -> PathBuf {
-	OBJCOPY_PATH
+/// Set a directory of wrappers for each system. It has a subdirectory for each system, such as `x86_64-linux` or `aarch64-darwin`, holding a `wrapper.exe`. A wrapper set with [`set_wrapper_exe_path`] is used instead when there is one.
+pub fn set_wrappers_path(p: impl AsRef<Path>) {
+	WRAPPERS_PATH.lock().unwrap().replace(p.as_ref().to_owned());
+}
+
+/// Get the paths of the wrapper executable and binary to use for a system.
+pub fn wrapper_paths(system: &str) -> Result<(PathBuf, PathBuf)> {
+	if let (Some(exe), Some(bin)) = (wrapper_exe_path(), wrapper_bin_path()) {
+		return Ok((exe, bin));
+	}
+	let path = WRAPPERS_PATH
 		.lock()
 		.unwrap()
 		.as_ref()
-		.cloned()
-		.unwrap_or_else(|| PathBuf::from("objcopy"))
+		.map(|path| path.join(system))
+		.ok_or_else(|| Error::MissingWrapper(system.to_owned()))?;
+	let exe = path.join("wrapper.exe");
+	if !exe.is_file() {
+		return Err(Error::MissingWrapper(system.to_owned()));
+	}
+	Ok((exe.clone(), exe))
+}
+
+/// Get the system of a binary, such as `x86_64-linux`, from its ELF `e_machine` or Mach-O `cputype`.
+pub fn system(path: impl AsRef<Path>, format: Option<Format>) -> Result<String> {
+	let file = File::open(path.as_ref())?;
+	create_format(&file, format)?.system(&file)
 }
 
 pub(crate) fn wrapper_bin_path() -> Option<PathBuf> {
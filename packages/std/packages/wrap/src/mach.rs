@@ -1,9 +1,11 @@
-use crate::{BinaryFormat, File, FileLocation, Footer, MAGIC};
+use crate::{BinaryFormat, Error, File, FileLocation, Footer, MAGIC, Result, Section, Segment};
 use num::ToPrimitive as _;
+use std::ops::Range;
 use sys::{
-	LC_CODE_SIGNATURE, LC_SEGMENT_64, linkedit_data_command, load_command, mach_header_64,
-	segment_command_64,
+	CPU_TYPE_ARM64, CPU_TYPE_X86_64, LC_CODE_SIGNATURE, LC_SEGMENT_64, fat_arch, fat_header,
+	linkedit_data_command, load_command, mach_header_64, segment_command_64,
 };
+use zerocopy::{FromBytes, IntoBytes as _};
 
 #[allow(warnings, clippy::pedantic, clippy::all)]
 pub(crate) mod sys;
@@ -22,8 +24,8 @@ const ALIGNMENT: usize = 16;
 
 impl BinaryFormat for Mach64 {
 	fn matches(&self, file: &crate::file::File) -> bool {
-		let magic = u32::from_le_bytes(file[0..4].try_into().unwrap());
-		magic == MAGIC_64
+		file.read_at_unaligned::<u32>(0)
+			.is_ok_and(|magic| u32::from_le(magic) == MAGIC_64)
 	}
 
 	#[allow(clippy::unused_self)]
@@ -31,161 +33,610 @@ impl BinaryFormat for Mach64 {
 		"mach"
 	}
 
-	fn write_manifest(&self, file: &mut File, data: &[u8]) {
-		// Get the data and pad to 4 byte boundaries.
-		let mut data = data.to_vec();
-
-		// Pad to 4 byte boundaries.
-		if !data.len().is_multiple_of(ALIGNMENT) {
-			let padding = ALIGNMENT - data.len() % ALIGNMENT;
-			for _ in 0..padding {
-				data.insert(0, 0);
-			}
+	fn system(&self, file: &File) -> Result<String> {
+		let header = *file.read_at::<mach_header_64>(0)?;
+		match header.cputype {
+			CPU_TYPE_X86_64 => Ok("x86_64-darwin".to_owned()),
+			CPU_TYPE_ARM64 => Ok("aarch64-darwin".to_owned()),
+			cputype => Err(Error::UnsupportedFormat(format!(
+				"unknown cputype {cputype:#x}"
+			))),
 		}
+	}
 
-		// Find the code signature and LINKEDIT sections.
-		let mut code_signature_command = None;
-		let mut linkedit_command = None;
-		let header = file.read_at::<mach_header_64>(0);
-		let mut offset = size_of_val(header);
+	fn write_manifest(&self, file: &mut File, data: &[u8]) -> Result<()> {
+		write_in_range(file, 0..file.len(), data)?;
+		Ok(())
+	}
+
+	fn read_manifest(&self, file: &File) -> Result<Option<crate::FileLocation>> {
+		manifest_location(file.as_slice()?)
+	}
+
+	fn manifest_ranges(&self, file: &File) -> Result<Vec<FileLocation>> {
+		manifest_ranges(file.as_slice()?)
+	}
+
+	fn sections(&self, file: &File) -> Result<Vec<Section>> {
+		// Find the LINKEDIT segment and the code signature.
+		let mut linkedit = None;
+		let mut sections = Vec::new();
+		let header = *file.read_at::<mach_header_64>(0)?;
+		let mut offset = size_of::<mach_header_64>();
 		for _ in 0..header.ncmds {
-			let load_command = file.read_at::<load_command>(offset);
-			if load_command.cmd == LC_CODE_SIGNATURE {
-				assert!(code_signature_command.is_none());
-				let command = *file.read_at::<linkedit_data_command>(offset);
-				code_signature_command.replace((offset, command));
-			}
+			let load_command = *file.read_at::<load_command>(offset)?;
 			if load_command.cmd == LC_SEGMENT_64 {
-				let command = file.read_at::<segment_command_64>(offset);
+				let command = file.read_at::<segment_command_64>(offset)?;
 				if command.segname == LINKEDIT {
-					assert!(linkedit_command.is_none());
-					linkedit_command.replace(offset);
+					linkedit.replace(Segment {
+						kind: "__LINKEDIT".to_owned(),
+						offset: command.fileoff.to_usize().unwrap(),
+						length: command.filesize.to_usize().unwrap(),
+						address: command.vmaddr,
+						memory_size: command.vmsize,
+					});
 				}
 			}
+			if load_command.cmd == LC_CODE_SIGNATURE {
+				let command = file.read_at::<linkedit_data_command>(offset)?;
+				sections.push(Section {
+					name: "LC_CODE_SIGNATURE".to_owned(),
+					offset: command.dataoff.to_usize().unwrap(),
+					length: command.datasize.to_usize().unwrap(),
+					address: None,
+					segment: None,
+				});
+			}
 			offset += load_command.cmdsize.to_usize().unwrap();
 		}
 
-		// Insert the data.
-		let position = code_signature_command.map_or_else(
-			|| file.file_size().unwrap(),
-			|(_, command)| command.dataoff.into(),
-		);
+		// The manifest is stored just before the code signature.
+		if let Some(location) = self.read_manifest(file)? {
+			sections.push(Section {
+				name: "manifest".to_owned(),
+				offset: location.offset,
+				length: location.length,
+				address: None,
+				segment: None,
+			});
+		}
 
-		// Patch LINKEDIT
-		if let Some(offset) = linkedit_command {
-			let command = file.read_at_mut::<segment_command_64>(offset);
-			command.filesize += data.len().to_u64().unwrap();
+		// Both regions live in the LINKEDIT segment.
+		for section in &mut sections {
+			section.segment = linkedit.clone().filter(|segment| {
+				segment.offset <= section.offset && section.end() <= segment.offset + segment.length
+			});
 		}
 
-		// Patch the code signature.
-		if let Some((offset, _)) = code_signature_command {
-			let command = file.read_at_mut::<linkedit_data_command>(offset);
-			command.dataoff += data.len().to_u32().unwrap();
+		Ok(sections)
+	}
+
+	fn overwrite_manifest(&self, file: &mut File, data: &[u8]) -> Result<()> {
+		overwrite_in_range(file, 0..file.len(), data)?;
+		Ok(())
+	}
+
+	fn unwrap(&self, _file: &mut File, _entrypoint: u64) -> Result<()> {
+		Err(Error::UnsupportedFormat(
+			"embedded Mach-O wrappers cannot be unwrapped".to_owned(),
+		))
+	}
+
+	fn embed(&self, file: &mut File, data: &[u8]) -> Result<()> {
+		self.write_manifest(file, data)
+	}
+}
+
+impl MachUniversal {
+	/// Find a manifest appended after the last slice by older versions of `wrap`.
+	fn appended_manifest(file: &File) -> Result<Option<FileLocation>> {
+		let file_size = file.file_size()?;
+		let Some(offset) = file_size.checked_sub(size_of::<Footer>().to_u64().unwrap()) else {
+			return Ok(None);
+		};
+		let footer = file.read_at_unaligned::<Footer>(offset)?;
+		if footer.magic != MAGIC {
+			return Ok(None);
 		}
-		file.insert(&data, position).expect("failed to insert data");
+		let start = offset
+			.checked_sub(footer.size)
+			.ok_or(Error::BadFooter("the manifest size exceeds the file size"))?;
+		Ok(Some(FileLocation {
+			offset: start.to_usize().unwrap(),
+			length: (file_size - start).to_usize().unwrap(),
+		}))
 	}
+}
 
-	fn read_manifest(&self, file: &File) -> Option<crate::FileLocation> {
-		// Find the code signature.
-		let mut code_signature_command = None;
-		let header = *file.read_at::<mach_header_64>(0);
-		let mut offset = size_of::<mach_header_64>();
-		for _ in 0..header.ncmds {
-			let load_command = file.read_at::<load_command>(offset);
-			if load_command.cmd == LC_CODE_SIGNATURE {
-				let command = file.read_at::<linkedit_data_command>(offset);
-				code_signature_command.replace(command.dataoff.to_usize().unwrap());
-				break;
+impl BinaryFormat for MachUniversal {
+	fn matches(&self, file: &File) -> bool {
+		file.read_at_unaligned::<u32>(0)
+			.is_ok_and(|magic| u32::from_be(magic) == MAGIC_UNIVERSAL)
+	}
+
+	fn name(&self) -> &'static str {
+		"universal mach-o"
+	}
+
+	fn system(&self, _file: &File) -> Result<String> {
+		Err(Error::UnsupportedFormat(
+			"a universal binary has a slice for each system".to_owned(),
+		))
+	}
+
+	fn read_manifest(&self, file: &File) -> Result<Option<FileLocation>> {
+		if let Some(location) = Self::appended_manifest(file)? {
+			return Ok(Some(location));
+		}
+
+		// Every slice holds a copy of the manifest, so use the first one.
+		let data = file.as_slice()?;
+		for arch in fat_arches(data)? {
+			let range = slice_range(&arch);
+			if !is_mach64(&data[range.clone()]) {
+				continue;
+			}
+			if let Some(location) = manifest_location(&data[range.clone()])? {
+				return Ok(Some(FileLocation {
+					offset: range.start + location.offset,
+					length: location.length,
+				}));
 			}
-			offset += load_command.cmdsize.to_usize().unwrap();
 		}
-		let offset = code_signature_command? - size_of::<Footer>();
+		Ok(None)
+	}
 
-		// Try and find the magic number within about
-		let footer = file.read_at_unaligned::<Footer>(offset);
-		if footer.magic != MAGIC {
-			return None;
+	fn manifest_ranges(&self, file: &File) -> Result<Vec<FileLocation>> {
+		let data = file.as_slice()?;
+		let header = read::<fat_header>(data, 0)?;
+		let mut ranges = vec![FileLocation {
+			offset: 0,
+			length: size_of::<fat_header>()
+				+ u32::from_be(header.nfat_arch).to_usize().unwrap() * size_of::<fat_arch>(),
+		}];
+		if let Some(offset) = data.len().checked_sub(size_of::<Footer>()) {
+			ranges.push(FileLocation {
+				offset,
+				length: size_of::<Footer>(),
+			});
 		}
-		let offset = offset
-			.checked_sub(footer.size.try_into().unwrap())
-			.expect("underflow");
-		let length = footer.size.to_usize().unwrap() + size_of::<Footer>();
-		Some(crate::FileLocation { offset, length })
+		for arch in fat_arches(data)? {
+			let range = slice_range(&arch);
+			ranges.push(FileLocation {
+				offset: range.start,
+				length: size_of::<u32>().min(range.len()),
+			});
+			if !is_mach64(&data[range.clone()]) {
+				continue;
+			}
+			ranges.extend(
+				manifest_ranges(&data[range.clone()])?
+					.into_iter()
+					.map(|location| FileLocation {
+						offset: range.start + location.offset,
+						length: location.length,
+					}),
+			);
+		}
+		Ok(ranges)
 	}
 
-	fn overwrite_manifest(&self, file: &mut File, data: &[u8]) {
-		let Some(old) = self.read_manifest(file) else {
-			self.write_manifest(file, data);
-			return;
-		};
+	fn sections(&self, file: &File) -> Result<Vec<Section>> {
+		let data = file.as_slice()?;
+		let mut sections = Vec::new();
+		for arch in fat_arches(data)? {
+			let range = slice_range(&arch);
+			let name = arch_name(arch.cputype);
+			let slice = Segment {
+				kind: format!("{name} slice"),
+				offset: range.start,
+				length: range.len(),
+				address: 0,
+				memory_size: 0,
+			};
+			sections.push(Section {
+				name: format!("{name} slice"),
+				offset: range.start,
+				length: range.len(),
+				address: None,
+				segment: None,
+			});
+			if !is_mach64(&data[range.clone()]) {
+				continue;
+			}
+			if let Some(location) = manifest_location(&data[range.clone()])? {
+				sections.push(Section {
+					name: format!("{name} manifest"),
+					offset: range.start + location.offset,
+					length: location.length,
+					address: None,
+					segment: Some(slice),
+				});
+			}
+		}
 
-		// Compute the new offset of the code signature.
-		let new_offset = old.offset.to_usize().unwrap() + data.len();
+		// Older versions of wrap appended the manifest after the last slice.
+		if let Some(location) = Self::appended_manifest(file)? {
+			sections.push(Section {
+				name: "manifest".to_owned(),
+				offset: location.offset,
+				length: location.length,
+				address: None,
+				segment: None,
+			});
+		}
 
-		// Find the code signature.
-		let header = *file.read_at::<mach_header_64>(0);
-		let mut offset = size_of_val(&header);
-		for _ in 0..header.ncmds {
-			let load_command = *file.read_at::<load_command>(offset);
-			if load_command.cmd == LC_CODE_SIGNATURE {
-				let code_signature = file.read_at_mut::<linkedit_data_command>(offset);
-				code_signature.dataoff = new_offset.try_into().unwrap();
-				break;
+		Ok(sections)
+	}
+
+	fn write_manifest(&self, file: &mut File, data: &[u8]) -> Result<()> {
+		// Remove a manifest appended by an older version of wrap.
+		if let Some(location) = Self::appended_manifest(file)? {
+			file.delete(location)?;
+		}
+
+		// Embed the manifest in every slice in place. The slices that follow move, so they are aligned again.
+		let file_size = file.len();
+		let arches = fat_arches(file.as_slice()?)?;
+		let mut end = size_of::<fat_header>() + arches.len() * size_of::<fat_arch>();
+		for (index, arch) in arches.iter().enumerate() {
+			// The slice has moved by as much as the file has grown or shrunk.
+			let range = slice_range(arch);
+			let start = (range.start + file.len())
+				.checked_sub(file_size)
+				.ok_or(Error::TruncatedHeader("fat_arch"))?;
+			let alignment = 1usize
+				.checked_shl(arch.align)
+				.ok_or_else(|| Error::UnsupportedFormat("invalid slice alignment".to_owned()))?;
+			let offset = end.next_multiple_of(alignment);
+			if offset > start {
+				file.insert(&vec![0; offset - start], start.to_u64().unwrap())?;
+			} else if offset < start {
+				file.delete(FileLocation {
+					offset,
+					length: start - offset,
+				})?;
 			}
-			offset += load_command.cmdsize.to_usize().unwrap();
+			let mut slice = offset..offset + range.len();
+			let magic = FileLocation {
+				offset,
+				length: size_of::<u32>().min(slice.len()),
+			};
+			if is_mach64(file.bytes(magic, "slice")?) {
+				slice = overwrite_in_range(file, slice, data)?;
+			}
+
+			// Update the `fat_arch`, which is stored big endian.
+			let arch = fat_arch {
+				cputype: arch.cputype.to_be(),
+				cpusubtype: arch.cpusubtype.to_be(),
+				offset: crate::convert::<_, u32>(slice.start)?.to_be(),
+				size: crate::convert::<_, u32>(slice.len())?.to_be(),
+				align: arch.align.to_be(),
+			};
+			let position = size_of::<fat_header>() + index * size_of::<fat_arch>();
+			file.patch(position, arch.as_bytes())?;
+			end = slice.end;
 		}
+		Ok(())
+	}
 
-		// Replace the manifest.
-		file.replace(old, data)
-			.expect("failed to overwrite manifest");
+	fn overwrite_manifest(&self, file: &mut File, data: &[u8]) -> Result<()> {
+		// Every slice's manifest is overwritten.
+		self.write_manifest(file, data)
 	}
 
-	fn embed(&self, path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
-		// let wrapper_exe = crate::wrapper_exe_path()
-		// 	.ok_or_else(|| std::io::Error::other("missing wrapper executable"))?;
-		// std::fs::copy(&wrapper_exe, path).expect("failed to copy file");
-		// std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).expect("failed to set permissions");
-		let mut file = File::open(path, false).expect("failed to open file");
-		self.write_manifest(&mut file, data);
-		Ok(())
+	fn unwrap(&self, _file: &mut File, _entrypoint: u64) -> Result<()> {
+		Err(Error::UnsupportedFormat(
+			"embedded Mach-O wrappers cannot be unwrapped".to_owned(),
+		))
+	}
+
+	fn embed(&self, file: &mut File, data: &[u8]) -> Result<()> {
+		self.write_manifest(file, data)
 	}
 }
 
-impl BinaryFormat for MachUniversal {
-	fn matches(&self, file: &File) -> bool {
-		let magic = u32::from_be_bytes(file[0..4].try_into().unwrap());
-		magic == MAGIC_UNIVERSAL
+/// Find the manifest of a single architecture binary, which is stored just before its code signature.
+fn manifest_location(data: &[u8]) -> Result<Option<FileLocation>> {
+	let Some(offset) =
+		code_signature_offset(data)?.and_then(|offset| offset.checked_sub(size_of::<Footer>()))
+	else {
+		return Ok(None);
+	};
+
+	// Try and find the magic number within about
+	let footer = read::<Footer>(data, offset)?;
+	if footer.magic != MAGIC {
+		return Ok(None);
 	}
+	let offset = footer
+		.size
+		.to_usize()
+		.and_then(|size| offset.checked_sub(size))
+		.ok_or(Error::BadFooter("the manifest size exceeds the file size"))?;
+	let length = footer.size.to_usize().unwrap() + size_of::<Footer>();
+	Ok(Some(crate::FileLocation { offset, length }))
+}
 
-	fn name(&self) -> &'static str {
-		"universal mach-o"
+/// Get the ranges of a single architecture binary that [`manifest_location`] reads before the manifest itself, given the bytes that are available.
+fn manifest_ranges(data: &[u8]) -> Result<Vec<FileLocation>> {
+	let header = read::<mach_header_64>(data, 0)?;
+	let mut ranges = vec![FileLocation {
+		offset: 0,
+		length: size_of::<mach_header_64>() + header.sizeofcmds.to_usize().unwrap(),
+	}];
+	if let Some(offset) =
+		code_signature_offset(data)?.and_then(|offset| offset.checked_sub(size_of::<Footer>()))
+	{
+		ranges.push(FileLocation {
+			offset,
+			length: size_of::<Footer>(),
+		});
 	}
+	Ok(ranges)
+}
 
-	fn read_manifest(&self, file: &File) -> Option<FileLocation> {
-		let file_size = file.file_size().expect("failed to get the file size");
-		let offset = file_size.checked_sub(size_of::<Footer>().to_u64().unwrap())?;
-		let footer = file.read_at::<Footer>(offset);
-		if footer.magic != MAGIC {
-			return None;
+/// Find the offset of the code signature of a single architecture binary.
+fn code_signature_offset(data: &[u8]) -> Result<Option<usize>> {
+	let header = read::<mach_header_64>(data, 0)?;
+	let mut offset = size_of::<mach_header_64>();
+	for _ in 0..header.ncmds {
+		let load_command = read::<load_command>(data, offset)?;
+		if load_command.cmd == LC_CODE_SIGNATURE {
+			let command = read::<linkedit_data_command>(data, offset)?;
+			return Ok(Some(command.dataoff.to_usize().unwrap()));
+		}
+		offset += load_command.cmdsize.to_usize().unwrap();
+	}
+	Ok(None)
+}
+
+/// Write a manifest into the single architecture binary at a range of the file. Returns the range of the binary once it is written.
+fn write_in_range(file: &mut File, range: Range<usize>, data: &[u8]) -> Result<Range<usize>> {
+	// Get the data and pad to 4 byte boundaries.
+	let mut data = data.to_vec();
+
+	// Pad to 4 byte boundaries.
+	if !data.len().is_multiple_of(ALIGNMENT) {
+		let padding = ALIGNMENT - data.len() % ALIGNMENT;
+		for _ in 0..padding {
+			data.insert(0, 0);
+		}
+	}
+
+	// Find the code signature and LINKEDIT sections.
+	let mut code_signature_command = None;
+	let mut linkedit_command = None;
+	let header = file.read_at::<mach_header_64>(range.start)?;
+	let mut offset = range.start + size_of_val(header);
+	for _ in 0..header.ncmds {
+		let load_command = file.read_at::<load_command>(offset)?;
+		if load_command.cmd == LC_CODE_SIGNATURE {
+			if code_signature_command.is_some() {
+				return Err(Error::UnsupportedFormat(
+					"multiple code signatures found".to_owned(),
+				));
+			}
+			let command = *file.read_at::<linkedit_data_command>(offset)?;
+			code_signature_command.replace((offset, command));
+		}
+		if load_command.cmd == LC_SEGMENT_64 {
+			let command = file.read_at::<segment_command_64>(offset)?;
+			if command.segname == LINKEDIT {
+				if linkedit_command.is_some() {
+					return Err(Error::UnsupportedFormat(
+						"multiple __LINKEDIT segments found".to_owned(),
+					));
+				}
+				linkedit_command.replace(offset);
+			}
+		}
+		offset += load_command.cmdsize.to_usize().unwrap();
+	}
+
+	// Insert the data.
+	let position = match code_signature_command {
+		Some((_, command)) => range.start + command.dataoff.to_usize().unwrap(),
+		None => range.end,
+	};
+
+	// Patch LINKEDIT
+	if let Some(offset) = linkedit_command {
+		file.update::<segment_command_64>(offset, |command| {
+			command.filesize += data.len().to_u64().unwrap();
+		})?;
+	}
+
+	// Patch the code signature.
+	if let Some((offset, _)) = code_signature_command {
+		let delta = crate::convert::<_, u32>(data.len())?;
+		file.update::<linkedit_data_command>(offset, |command| command.dataoff += delta)?;
+	}
+	file.insert(&data, position.to_u64().unwrap())?;
+	Ok(range.start..range.end + data.len())
+}
+
+/// Replace the manifest of the single architecture binary at a range of the file, or write one if it has none. Returns the range of the binary once it is written.
+fn overwrite_in_range(file: &mut File, range: Range<usize>, data: &[u8]) -> Result<Range<usize>> {
+	let slice = file.bytes(
+		FileLocation {
+			offset: range.start,
+			length: range.len(),
+		},
+		"slice",
+	)?;
+	let Some(old) = manifest_location(slice)? else {
+		return write_in_range(file, range, data);
+	};
+
+	// Remove the old manifest along with the padding inserted before it.
+	let length = old.length.next_multiple_of(ALIGNMENT);
+	let offset = old
+		.end()
+		.checked_sub(length)
+		.ok_or(Error::BadFooter("the manifest size exceeds the file size"))?;
+	let delta = crate::convert::<_, u32>(length)?;
+
+	// Patch the code signature and LINKEDIT.
+	let header = *file.read_at::<mach_header_64>(range.start)?;
+	let mut command_offset = range.start + size_of_val(&header);
+	for _ in 0..header.ncmds {
+		let load_command = *file.read_at::<load_command>(command_offset)?;
+		if load_command.cmd == LC_CODE_SIGNATURE {
+			file.update::<linkedit_data_command>(command_offset, |command| {
+				command.dataoff -= delta;
+			})?;
+		}
+		if load_command.cmd == LC_SEGMENT_64 {
+			file.update::<segment_command_64>(command_offset, |command| {
+				if command.segname == LINKEDIT {
+					command.filesize -= u64::from(delta);
+				}
+			})?;
 		}
-		Some(FileLocation {
-			offset: (offset - footer.size).to_usize().unwrap(),
-			length: (file_size - offset).to_usize().unwrap(),
+		command_offset += load_command.cmdsize.to_usize().unwrap();
+	}
+	file.delete(FileLocation {
+		offset: range.start + offset,
+		length,
+	})?;
+
+	// Write the new manifest.
+	write_in_range(file, range.start..range.end - length, data)
+}
+
+/// Read the `fat_arch` table of a universal binary, converting each entry to native byte order.
+fn fat_arches(data: &[u8]) -> Result<Vec<fat_arch>> {
+	let header = read::<fat_header>(data, 0)?;
+	let count = u32::from_be(header.nfat_arch).to_usize().unwrap();
+	(0..count)
+		.map(|index| {
+			let offset = size_of::<fat_header>() + index * size_of::<fat_arch>();
+			let arch = read::<fat_arch>(data, offset)?;
+			let arch = fat_arch {
+				cputype: i32::from_be(arch.cputype),
+				cpusubtype: i32::from_be(arch.cpusubtype),
+				offset: u32::from_be(arch.offset),
+				size: u32::from_be(arch.size),
+				align: u32::from_be(arch.align),
+			};
+			if slice_range(&arch).end > data.len() {
+				return Err(Error::TruncatedHeader("fat_arch"));
+			}
+			Ok(arch)
 		})
+		.collect()
+}
+
+fn slice_range(arch: &fat_arch) -> std::ops::Range<usize> {
+	let offset = arch.offset.to_usize().unwrap();
+	offset..offset + arch.size.to_usize().unwrap()
+}
+
+fn is_mach64(data: &[u8]) -> bool {
+	read::<u32>(data, 0).is_ok_and(|magic| u32::from_le(magic) == MAGIC_64)
+}
+
+fn arch_name(cputype: i32) -> String {
+	match cputype {
+		CPU_TYPE_X86_64 => "x86_64".to_owned(),
+		CPU_TYPE_ARM64 => "arm64".to_owned(),
+		cputype => format!("{cputype:#x}"),
+	}
+}
+
+fn read<T: FromBytes>(data: &[u8], offset: usize) -> Result<T> {
+	data.get(offset..)
+		.and_then(|data| T::read_from_prefix(data).ok())
+		.map(|(value, _)| value)
+		.ok_or(Error::TruncatedHeader(crate::file::type_name::<T>()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{fat_arches, manifest_location, read, slice_range};
+	use crate::{Format, Options, mach::sys::linkedit_data_command};
+
+	const UNIVERSAL: &[u8] = include_bytes!("../fixtures/mach_universal");
+
+	fn fixture() -> tempfile::NamedTempFile {
+		let file = tempfile::NamedTempFile::new().unwrap();
+		std::fs::write(file.path(), UNIVERSAL).unwrap();
+		file
 	}
 
-	fn write_manifest(&self, file: &mut File, data: &[u8]) {
-		file.append(data).expect("failed to append data");
+	fn manifest(arg: &str) -> serde_json::Value {
+		serde_json::json!({ "args": [arg] })
 	}
 
-	fn overwrite_manifest(&self, file: &mut File, data: &[u8]) {
-		if let Some(old) = self.read_manifest(file) {
-			file.delete(old).expect("failed to remove manifest");
+	/// Check that every slice is aligned and ends with its code signature.
+	fn check_slices(data: &[u8]) {
+		let arches = fat_arches(data).unwrap();
+		assert_eq!(arches.len(), 2);
+		for arch in &arches {
+			let range = slice_range(arch);
+			assert!(range.start.is_multiple_of(1 << arch.align));
+			let slice = &data[range.clone()];
+			let location = manifest_location(slice).unwrap().unwrap();
+			// The code signature is the third load command of the fixtures, after two segments.
+			let signature = read::<linkedit_data_command>(slice, 0x20 + 2 * 72).unwrap();
+			assert_eq!(location.end(), signature.dataoff as usize);
+			assert!((signature.dataoff as usize).is_multiple_of(16));
+			assert_eq!(
+				(signature.dataoff + signature.datasize) as usize,
+				range.len()
+			);
 		}
-		self.write_manifest(file, data);
 	}
 
-	fn embed(&self, _path: &std::path::Path, _data: &[u8]) -> std::io::Result<()> {
-		Err(std::io::Error::other("unsupported"))
+	#[test]
+	fn embed_universal() {
+		let file = fixture();
+		crate::embed(
+			file.path(),
+			&manifest("first"),
+			Some(Format::MachUniversal),
+			Options::default(),
+		)
+		.unwrap();
+		check_slices(&std::fs::read(file.path()).unwrap());
+		let output =
+			crate::read_manifest::<serde_json::Value>(file.path(), Some(Format::MachUniversal))
+				.unwrap();
+		assert_eq!(output.manifest, Some(manifest("first")));
+	}
+
+	#[test]
+	fn overwrite_universal() {
+		let file = fixture();
+		let options = Options::default();
+		crate::write_manifest(file.path(), &manifest("first"), None, options).unwrap();
+		let first = std::fs::read(file.path()).unwrap();
+		crate::write_manifest(file.path(), &manifest("a longer second"), None, options).unwrap();
+		check_slices(&std::fs::read(file.path()).unwrap());
+		let output = crate::read_manifest::<serde_json::Value>(file.path(), None).unwrap();
+		assert_eq!(output.manifest, Some(manifest("a longer second")));
+
+		// Writing the first manifest again restores the original file.
+		crate::write_manifest(file.path(), &manifest("first"), None, options).unwrap();
+		assert_eq!(std::fs::read(file.path()).unwrap(), first);
+	}
+
+	#[test]
+	fn migrate_appended_manifest() {
+		// Older versions of wrap appended the manifest after the last slice.
+		let file = fixture();
+		let mut data = UNIVERSAL.to_vec();
+		data.extend(crate::encode(&manifest("appended"), Options::default()).unwrap());
+		std::fs::write(file.path(), data).unwrap();
+		let output = crate::read_manifest::<serde_json::Value>(file.path(), None).unwrap();
+		assert_eq!(output.manifest, Some(manifest("appended")));
+
+		crate::write_manifest(file.path(), &manifest("embedded"), None, Options::default())
+			.unwrap();
+		check_slices(&std::fs::read(file.path()).unwrap());
+		let output = crate::read_manifest::<serde_json::Value>(file.path(), None).unwrap();
+		assert_eq!(output.manifest, Some(manifest("embedded")));
 	}
 }
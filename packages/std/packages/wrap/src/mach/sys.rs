@@ -156,6 +156,8 @@ pub const CPUFAMILY_INTEL_6_26: u32 = 1801080018;
 pub const MH_MAGIC: u32 = 4277009102;
 pub const MH_CIGAM: u32 = 3472551422;
 pub const MH_MAGIC_64: u32 = 4277009103;
+pub const CPU_TYPE_X86_64: i32 = 16777223;
+pub const CPU_TYPE_ARM64: i32 = 16777228;
 pub const MH_CIGAM_64: u32 = 3489328638;
 pub const MH_OBJECT: u32 = 1;
 pub const MH_EXECUTE: u32 = 2;
@@ -1400,3 +1402,34 @@ pub struct note_command {
 	pub offset: u64,
 	pub size: u64,
 }
+#[repr(C)]
+#[derive(
+	Debug,
+	Copy,
+	Clone,
+	zerocopy::FromBytes,
+	zerocopy::IntoBytes,
+	zerocopy::KnownLayout,
+	zerocopy::Immutable,
+)]
+pub struct fat_header {
+	pub magic: u32,
+	pub nfat_arch: u32,
+}
+#[repr(C)]
+#[derive(
+	Debug,
+	Copy,
+	Clone,
+	zerocopy::FromBytes,
+	zerocopy::IntoBytes,
+	zerocopy::KnownLayout,
+	zerocopy::Immutable,
+)]
+pub struct fat_arch {
+	pub cputype: cpu_type_t,
+	pub cpusubtype: cpu_subtype_t,
+	pub offset: u32,
+	pub size: u32,
+	pub align: u32,
+}
@@ -1,5 +1,10 @@
+use anstream::{eprintln, println};
 use clap::Parser;
-use std::{fs::Permissions, os::unix::fs::PermissionsExt as _, path::PathBuf};
+use crossterm::style::Stylize as _;
+use std::{
+	collections::BTreeMap, fmt::Write as _, fs::Permissions, os::unix::fs::PermissionsExt as _,
+	path::PathBuf, process::ExitCode,
+};
 
 #[derive(clap::Parser)]
 struct Args {
@@ -12,11 +17,43 @@ enum Command {
 	/// Write a new manifest.
 	Write(Write),
 
-	/// Read a manifest, if it exists.
+	/// Read a manifest, if it exists. A `tangram_serialize` manifest is written as is, since it cannot be converted to JSON without its type.
 	Read(Read),
 
 	/// Embed a manifest and wrapper.
 	Embed(Embed),
+
+	/// Inspect a wrapped binary.
+	Inspect(Inspect),
+
+	/// Upgrade the manifests of existing wrappers in place.
+	Migrate(Migrate),
+
+	/// Check that the manifests and wrapper sections of binaries are consistent.
+	Verify(Verify),
+
+	/// Restore the executable that a wrapper runs.
+	Unwrap(Unwrap),
+
+	/// Change the environment and default arguments of a wrapper in place.
+	Edit(Edit),
+
+	/// Print the argv and env that a wrapper would exec, without running it.
+	Explain(Explain),
+
+	/// Copy a wrapper and everything it refers to into a directory, and make it relocatable.
+	Bundle(Bundle),
+}
+
+#[derive(clap::Args)]
+struct EncodingArgs {
+	/// The encoding of the manifest.
+	#[arg(long, value_enum, default_value = "json")]
+	encoding: wrap::Encoding,
+
+	/// The compression of the encoded manifest.
+	#[arg(long, value_enum, default_value = "none")]
+	compression: wrap::Compression,
 }
 
 #[derive(clap::Parser)]
@@ -25,7 +62,7 @@ struct Write {
 	#[arg(long)]
 	format: Option<wrap::Format>,
 
-	/// The file to use as the manifest input.
+	/// The file to use as the manifest input, in the chosen encoding.
 	#[arg(long)]
 	manifest: PathBuf,
 
@@ -33,6 +70,9 @@ struct Write {
 	#[arg(long, short)]
 	output: PathBuf,
 
+	#[command(flatten)]
+	encoding: EncodingArgs,
+
 	/// The binary file to modify.
 	input: PathBuf,
 }
@@ -61,64 +101,666 @@ struct Embed {
 	#[arg(long, short)]
 	output: PathBuf,
 
-	/// The file to use as the manifest input.
+	/// The file to use as the manifest input, in the chosen encoding.
 	#[arg(long)]
 	manifest: PathBuf,
 
 	/// The wrapper executable.
-	#[arg(long)]
-	wrapper_exe: PathBuf,
+	#[arg(long, required_unless_present = "wrappers")]
+	wrapper_exe: Option<PathBuf>,
 
 	/// The wrapper binary.
 	#[arg(long)]
 	wrapper_bin: Option<PathBuf>,
 
-	/// The path of the objcopy binary to use.
-	#[arg(long)]
-	objcopy_path: Option<PathBuf>,
+	/// A directory with a wrapper for each system, such as `x86_64-linux/wrapper.exe`. The wrapper matching the input is used.
+	#[arg(long, conflicts_with = "wrapper_exe")]
+	wrappers: Option<PathBuf>,
+
+	#[command(flatten)]
+	encoding: EncodingArgs,
 
 	/// The input file to wrap.
 	input: PathBuf,
 }
 
-fn main() {
+#[derive(clap::Parser)]
+struct Inspect {
+	/// Specify the binary format to use.
+	#[arg(long)]
+	format: Option<wrap::Format>,
+
+	/// Print the inspection as JSON.
+	#[arg(long)]
+	json: bool,
+
+	/// The binary file to inspect.
+	input: PathBuf,
+}
+
+#[derive(clap::Parser)]
+struct Migrate {
+	/// Specify the binary format to use.
+	#[arg(long)]
+	format: Option<wrap::Format>,
+
+	#[command(flatten)]
+	encoding: EncodingArgs,
+
+	/// The wrappers to migrate.
+	#[arg(required = true)]
+	inputs: Vec<PathBuf>,
+}
+
+#[derive(clap::Parser)]
+struct Verify {
+	/// Specify the binary format to use.
+	#[arg(long)]
+	format: Option<wrap::Format>,
+
+	/// The binary files to verify.
+	#[arg(required = true)]
+	inputs: Vec<PathBuf>,
+}
+
+#[derive(clap::Parser)]
+struct Unwrap {
+	/// Specify the binary format to use.
+	#[arg(long)]
+	format: Option<wrap::Format>,
+
+	/// The directory to resolve artifacts in. Defaults to the directory the wrapper would use.
+	#[arg(long)]
+	artifacts_path: Option<PathBuf>,
+
+	/// The output to write. `wrap` will not modify files in place.
+	#[arg(long, short)]
+	output: PathBuf,
+
+	/// The wrapper to unwrap.
+	input: PathBuf,
+}
+
+#[derive(clap::Parser)]
+struct Edit {
+	/// Specify the binary format to use.
+	#[arg(long)]
+	format: Option<wrap::Format>,
+
+	/// The directory whose paths are stored as artifacts. Defaults to the same artifact roots as the proxies use.
+	#[arg(long)]
+	artifacts_path: Option<PathBuf>,
+
+	/// Set an environment variable.
+	#[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+	set: Vec<(String, String)>,
+
+	/// Unset an environment variable.
+	#[arg(long, value_name = "KEY")]
+	unset: Vec<String>,
+
+	/// Prefix an environment variable with a value.
+	#[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+	prepend: Vec<(String, String)>,
+
+	/// Suffix an environment variable with a value.
+	#[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+	append: Vec<(String, String)>,
+
+	/// The separator for `--prepend` and `--append`.
+	#[arg(long, default_value = ":")]
+	separator: String,
+
+	/// Add a default argument.
+	#[arg(long, allow_hyphen_values = true)]
+	arg: Vec<String>,
+
+	/// Remove the existing default arguments before adding any with `--arg`.
+	#[arg(long)]
+	clear_args: bool,
+
+	/// The wrapper to edit.
+	input: PathBuf,
+}
+
+#[derive(clap::Parser)]
+struct Explain {
+	/// Specify the binary format to use.
+	#[arg(long)]
+	format: Option<wrap::Format>,
+
+	/// The directory to resolve artifacts in. Defaults to the directory the wrapper would use.
+	#[arg(long)]
+	artifacts_path: Option<PathBuf>,
+
+	/// Start from an empty environment instead of the current one.
+	#[arg(long)]
+	clear_env: bool,
+
+	/// Set a variable in the environment the wrapper is run in.
+	#[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+	env: Vec<(String, String)>,
+
+	/// Print the explanation as JSON.
+	#[arg(long)]
+	json: bool,
+
+	/// The wrapper to explain.
+	input: PathBuf,
+
+	/// The arguments the wrapper is run with.
+	#[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+	args: Vec<String>,
+}
+
+#[derive(clap::Parser)]
+struct Bundle {
+	/// Specify the binary format to use.
+	#[arg(long)]
+	format: Option<wrap::Format>,
+
+	/// The directory to resolve artifacts in. Defaults to the directory the wrapper would use.
+	#[arg(long)]
+	artifacts_path: Option<PathBuf>,
+
+	/// The directory to create.
+	#[arg(long, short)]
+	output: PathBuf,
+
+	/// The wrapper to bundle.
+	input: PathBuf,
+}
+
+fn main() -> ExitCode {
 	let args = Args::parse();
+	match run(args) {
+		Ok(code) => code,
+		Err(error) => {
+			if std::env::var("TANGRAM_ERROR_FORMAT").as_deref() == Ok("json") {
+				eprintln!("{}", error_to_json(&error));
+			} else {
+				eprintln!("error: {error}");
+				let mut source = std::error::Error::source(&error);
+				while let Some(error) = source {
+					eprintln!("  caused by: {error}");
+					source = error.source();
+				}
+			}
+			ExitCode::FAILURE
+		},
+	}
+}
+
+/// Serialize an error and its source chain with the same `message` and `source` keys as `common::error`, with the source of each error nested in it.
+fn error_to_json(error: &dyn std::error::Error) -> serde_json::Value {
+	let mut json = serde_json::json!({ "message": error.to_string() });
+	if let Some(source) = error.source() {
+		json["source"] = error_to_json(source);
+	}
+	json
+}
+
+#[allow(clippy::too_many_lines)]
+fn run(args: Args) -> wrap::Result<ExitCode> {
 	match args.command {
 		Command::Write(args) => {
-			let manifest: serde_json::Value = serde_json::from_reader(
-				&mut std::fs::File::open(args.manifest).expect("failed to open manifest file"),
-			)
-			.expect("failed to deserialize manifest");
+			let manifest = wrap::Payload {
+				encoding: args.encoding.encoding,
+				data: std::fs::read(args.manifest)?,
+			};
+			if manifest.encoding == wrap::Encoding::Json {
+				let value: serde_json::Value = serde_json::from_slice(&manifest.data)?;
+				wrap::validate(&value, false)?;
+			}
 			if args.output.exists() {
-				std::fs::remove_file(&args.output).expect("failed to delete output");
+				std::fs::remove_file(&args.output)?;
 			}
-			std::fs::copy(&args.input, &args.output).expect("failed to copy file");
-			std::fs::set_permissions(&args.output, Permissions::from_mode(0o755))
-				.expect("failed to set output as writeable");
-			wrap::write_manifest(&args.output, &manifest, args.format);
+			std::fs::copy(&args.input, &args.output)?;
+			std::fs::set_permissions(&args.output, Permissions::from_mode(0o755))?;
+			wrap::write_manifest(
+				&args.output,
+				&manifest,
+				args.format,
+				args.encoding.options(),
+			)?;
 		},
 		Command::Read(args) => {
-			let output = wrap::read_manifest::<serde_json::Value>(args.input, args.format);
-			serde_json::to_writer(
-				std::fs::File::create(args.output).expect("failed to open output for writing"),
-				&output,
-			)
-			.expect("failed to write manifest");
+			let output = wrap::read_manifest::<wrap::Payload>(args.input, args.format)?;
+			match output.manifest {
+				Some(payload) if payload.encoding == wrap::Encoding::Serialize => {
+					std::fs::write(args.output, payload.data)?;
+				},
+				manifest => {
+					let output = wrap::Output {
+						manifest: manifest
+							.map(|payload| {
+								serde_json::from_slice::<serde_json::Value>(&payload.data)
+							})
+							.transpose()?,
+						location: output.location,
+					};
+					serde_json::to_writer(std::fs::File::create(args.output)?, &output)?;
+				},
+			}
 		},
 		Command::Embed(args) => {
-			let manifest: serde_json::Value = serde_json::from_reader(
-				&mut std::fs::File::open(args.manifest).expect("failed to open manifest file"),
-			)
-			.expect("failed to deserialize manifest");
-			if let Some(path) = args.objcopy_path {
-				wrap::set_objcopy_path(path);
+			let manifest = wrap::Payload {
+				encoding: args.encoding.encoding,
+				data: std::fs::read(args.manifest)?,
+			};
+			if manifest.encoding == wrap::Encoding::Json {
+				let value: serde_json::Value = serde_json::from_slice(&manifest.data)?;
+				wrap::validate(&value, true)?;
 			}
 			if let Some(path) = args.wrapper_bin {
 				wrap::set_wrapper_bin_path(path);
 			}
-			wrap::set_wrapper_exe_path(args.wrapper_exe);
-			std::fs::copy(args.input, &args.output).expect("failed to copy input file");
-			wrap::embed(&args.output, &manifest, args.format).expect("failed to embed the wrapper");
+			if let Some(path) = args.wrapper_exe {
+				wrap::set_wrapper_exe_path(path);
+			}
+			if let Some(path) = args.wrappers {
+				wrap::set_wrappers_path(path);
+			}
+			std::fs::copy(args.input, &args.output)?;
+			wrap::embed(
+				&args.output,
+				&manifest,
+				args.format,
+				args.encoding.options(),
+			)?;
+		},
+		Command::Inspect(args) => {
+			let inspection = wrap::inspect(&args.input, args.format)?;
+			if args.json {
+				serde_json::to_writer_pretty(std::io::stdout(), &inspection)?;
+				println!();
+			} else {
+				let title = args.input.display().to_string().bold().to_string();
+				print_tree(&inspection_tree(title, &inspection), "");
+			}
+		},
+		Command::Migrate(args) => {
+			let options = args.encoding.options();
+			for input in &args.inputs {
+				let migrated = wrap::migrate::<wrap::Payload>(input, args.format, options)?;
+				let status = if migrated { "migrated" } else { "unchanged" };
+				println!("{status} {}", input.display());
+			}
+		},
+		Command::Verify(args) => {
+			let mut failed = false;
+			for input in &args.inputs {
+				let verification = wrap::verify(input, args.format)?;
+				if verification.is_ok() {
+					let status = if !verification.is_wrapper() {
+						"ok (not a wrapper)".to_owned()
+					} else if verification.checksum.is_none() {
+						"ok (no checksum)".to_owned()
+					} else {
+						"ok".to_owned()
+					};
+					println!("{} {}", status.green(), input.display());
+				} else {
+					failed = true;
+					println!("{} {}", "error".red(), input.display());
+					for problem in &verification.problems {
+						println!("  {problem}");
+					}
+				}
+			}
+			if failed {
+				return Ok(ExitCode::FAILURE);
+			}
+		},
+		Command::Unwrap(args) => {
+			if args.output.exists() {
+				std::fs::remove_file(&args.output)?;
+			}
+
+			// An embedded wrapper is restored in a copy. Any other wrapper is resolved where it is, since `$ORIGIN` refers to its directory.
+			let embedded = wrap::read_manifest::<serde_json::Value>(&args.input, args.format)?
+				.manifest
+				.is_some_and(|manifest| manifest["executable"]["kind"] == "address");
+			let target = if embedded {
+				std::fs::copy(&args.input, &args.output)?;
+				&args.output
+			} else {
+				&args.input
+			};
+			let unwrapped = wrap::unwrap(target, args.format, args.artifacts_path.as_deref());
+			match unwrapped {
+				Ok(wrap::Unwrapped::Restored) => (),
+				Ok(wrap::Unwrapped::Path(path)) => {
+					std::fs::copy(path, &args.output)?;
+					std::fs::set_permissions(&args.output, Permissions::from_mode(0o755))?;
+				},
+				Err(error) => {
+					if embedded {
+						std::fs::remove_file(&args.output).ok();
+					}
+					return Err(error);
+				},
+			}
+		},
+		Command::Edit(args) => {
+			let roots = args
+				.artifacts_path
+				.clone()
+				.map_or_else(|| wrap::artifact_roots(&args.input), |path| vec![path]);
+			wrap::edit(&args.input, args.format, &args.edits(), &roots)?;
+		},
+		Command::Explain(args) => {
+			let mut env: BTreeMap<_, _> = if args.clear_env {
+				BTreeMap::new()
+			} else {
+				std::env::vars().collect()
+			};
+			env.extend(args.env);
+			let explanation = wrap::explain(
+				&args.input,
+				args.format,
+				&args.args,
+				env,
+				args.artifacts_path.as_deref(),
+			)?;
+			if args.json {
+				serde_json::to_writer_pretty(std::io::stdout(), &explanation)?;
+				println!();
+			} else {
+				print_explanation(&explanation);
+			}
 		},
+		Command::Bundle(args) => {
+			let wrapper = wrap::bundle(
+				&args.input,
+				args.format,
+				&args.output,
+				args.artifacts_path.as_deref(),
+			)?;
+			println!("{}", wrapper.display());
+		},
+	}
+	Ok(ExitCode::SUCCESS)
+}
+
+impl EncodingArgs {
+	fn options(&self) -> wrap::Options {
+		wrap::Options {
+			encoding: self.encoding,
+			compression: self.compression,
+		}
+	}
+}
+
+impl Edit {
+	/// Get the edits in the order they are applied: unsets, sets, prefixes and suffixes, then arguments.
+	fn edits(&self) -> Vec<wrap::Edit> {
+		let mut edits = Vec::new();
+		edits.extend(
+			self.unset
+				.iter()
+				.map(|key| wrap::Edit::Unset { key: key.clone() }),
+		);
+		edits.extend(self.set.iter().map(|(key, value)| wrap::Edit::Set {
+			key: key.clone(),
+			value: value.clone(),
+		}));
+		edits.extend(self.prepend.iter().map(|(key, value)| wrap::Edit::Prepend {
+			key: key.clone(),
+			value: value.clone(),
+			separator: self.separator.clone(),
+		}));
+		edits.extend(self.append.iter().map(|(key, value)| wrap::Edit::Append {
+			key: key.clone(),
+			value: value.clone(),
+			separator: self.separator.clone(),
+		}));
+		if self.clear_args {
+			edits.push(wrap::Edit::ClearArgs);
+		}
+		edits.extend(self.arg.iter().cloned().map(wrap::Edit::Arg));
+		edits
+	}
+}
+
+fn parse_key_value(string: &str) -> Result<(String, String), String> {
+	let (key, value) = string
+		.split_once('=')
+		.ok_or_else(|| format!("expected KEY=VALUE, got {string}"))?;
+	Ok((key.to_owned(), value.to_owned()))
+}
+
+fn print_explanation(explanation: &wrap::Explanation) {
+	match &explanation.path {
+		Some(path) => println!("{}: {path}", "path".blue()),
+		None => println!("{}: the executable embedded in the wrapper", "path".blue()),
+	}
+	if let Some(content) = &explanation.content {
+		println!("{}:", "content".blue());
+		for line in content.lines() {
+			println!("  {line}");
+		}
+	}
+	println!("{}:", "argv".blue());
+	for (index, arg) in explanation.argv.iter().enumerate() {
+		println!("  [{index}] {arg}");
+	}
+	println!("{}:", "env".blue());
+	for (key, value) in &explanation.env {
+		println!("  {}={value}", key.as_str().green());
+	}
+}
+
+/// A node of the tree printed by `wrap inspect`.
+struct Node {
+	label: String,
+	children: Vec<Node>,
+}
+
+impl Node {
+	fn leaf(label: impl Into<String>) -> Self {
+		Self {
+			label: label.into(),
+			children: Vec::new(),
+		}
+	}
+
+	fn branch(label: impl Into<String>, children: Vec<Node>) -> Self {
+		Self {
+			label: label.into(),
+			children,
+		}
+	}
+
+	fn field(key: &str, value: impl std::fmt::Display) -> Self {
+		Self::leaf(format!("{}: {value}", key.blue()))
+	}
+
+	fn list(key: &str, values: &[String]) -> Self {
+		Self::branch(
+			key.blue().to_string(),
+			values.iter().map(Node::leaf).collect(),
+		)
+	}
+}
+
+fn inspection_tree(title: String, inspection: &wrap::Inspection) -> Node {
+	let mut children = vec![
+		Node::field("format", format!("{:?}", inspection.format).to_lowercase()),
+		Node::field("size", inspection.file_size),
+	];
+
+	// Add the sections.
+	let sections = inspection
+		.sections
+		.iter()
+		.map(|section| {
+			let mut label = format!(
+				"{} offset={:#x} length={:#x}",
+				section.name.as_str().green(),
+				section.offset,
+				section.length
+			);
+			if let Some(address) = section.address {
+				write!(label, " address={address:#x}").unwrap();
+			}
+			let children = section
+				.segment
+				.iter()
+				.map(|segment| {
+					Node::leaf(format!(
+						"{} offset={:#x} length={:#x} address={:#x} memory_size={:#x}",
+						segment.kind.as_str().cyan(),
+						segment.offset,
+						segment.length,
+						segment.address,
+						segment.memory_size
+					))
+				})
+				.collect();
+			Node::branch(label, children)
+		})
+		.collect();
+	children.push(Node::branch("sections".blue().to_string(), sections));
+
+	// Add the footer.
+	match &inspection.footer {
+		Some(footer) => {
+			let valid = if footer.valid {
+				"valid".green()
+			} else {
+				"invalid".red()
+			};
+			let mut fields = vec![
+				Node::field("size", footer.size),
+				Node::field("version", footer.version),
+				Node::field("magic", format!("\"{}\"", footer.magic)),
+			];
+			if let Some(encoding) = footer.encoding {
+				fields.push(Node::field(
+					"encoding",
+					format!("{encoding:?}").to_lowercase(),
+				));
+			}
+			if let Some(compression) = footer.compression {
+				fields.push(Node::field(
+					"compression",
+					format!("{compression:?}").to_lowercase(),
+				));
+			}
+			if let Some(checksum) = &footer.checksum {
+				fields.push(Node::field("checksum", checksum));
+			}
+			children.push(Node::branch(
+				format!("{} ({valid})", "footer".blue()),
+				fields,
+			));
+		},
+		None => children.push(Node::leaf("no manifest".yellow().to_string())),
+	}
+
+	// Add the manifest.
+	if let Some(manifest) = &inspection.manifest {
+		children.push(manifest_tree(manifest));
+	}
+
+	Node::branch(title, children)
+}
+
+fn manifest_tree(manifest: &wrap::ManifestInfo) -> Node {
+	let mut fields = Vec::new();
+	let executable = &manifest.executable;
+	let value = match (&executable.path, &executable.content, executable.address) {
+		(Some(path), _, _) => path.clone(),
+		(_, Some(content), _) => content.clone(),
+		(_, _, Some(address)) => format!("{address:#x}"),
+		_ => String::new(),
+	};
+	fields.push(Node::field(
+		"executable",
+		format!("{} {value}", executable.kind.as_str().green()),
+	));
+	if let Some(interpreter) = &manifest.interpreter {
+		let mut children = Vec::new();
+		if let Some(path) = &interpreter.path {
+			children.push(Node::field("path", path));
+		}
+		children.push(Node::list("library paths", &interpreter.library_paths));
+		children.push(Node::list("preloads", &interpreter.preloads));
+		children.push(Node::list("args", &interpreter.args));
+		fields.push(Node::branch(
+			format!(
+				"{}: {}",
+				"interpreter".blue(),
+				interpreter.kind.as_str().green()
+			),
+			children,
+		));
+	}
+	if let Some(env) = &manifest.env {
+		fields.push(mutation_tree("env", env));
+	}
+	fields.push(Node::list("args", &manifest.args));
+	fields.push(Node::list("artifacts", &manifest.artifacts));
+	Node::branch("manifest".blue().to_string(), fields)
+}
+
+/// Describe an env mutation, expanding `merge` mutations into one node per variable.
+fn mutation_tree(key: &str, mutation: &serde_json::Value) -> Node {
+	let kind = mutation
+		.get("kind")
+		.and_then(serde_json::Value::as_str)
+		.unwrap_or("unknown");
+	let key = key.blue();
+	if let ("merge", Some(serde_json::Value::Object(values))) = (kind, mutation.get("value")) {
+		return Node::branch(
+			format!("{key}: {}", kind.green()),
+			values
+				.iter()
+				.map(|(name, value)| mutation_tree(name, value))
+				.collect(),
+		);
+	}
+	let mut label = format!("{key}: {}", kind.green());
+	if let Some(object) = mutation.as_object() {
+		for (name, value) in object.iter().filter(|(name, _)| *name != "kind") {
+			write!(label, " {name}={:?}", wrap::render(value)).unwrap();
+		}
+	}
+	Node::leaf(label)
+}
+
+fn print_tree(node: &Node, prefix: &str) {
+	println!("{}", node.label);
+	print_children(&node.children, prefix);
+}
+
+fn print_children(children: &[Node], prefix: &str) {
+	for (index, child) in children.iter().enumerate() {
+		let last = index == children.len() - 1;
+		let (branch, indent) = if last {
+			("└── ", "    ")
+		} else {
+			("├── ", "│   ")
+		};
+		println!("{prefix}{branch}{}", child.label);
+		print_children(&child.children, &format!("{prefix}{indent}"));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::error_to_json;
+
+	#[test]
+	fn json() {
+		let error = wrap::Error::Io(std::io::Error::other("the disk is full"));
+		assert_eq!(
+			error_to_json(&error),
+			serde_json::json!({
+				"message": "an I/O error occurred",
+				"source": { "message": "the disk is full" },
+			})
+		);
 	}
 }
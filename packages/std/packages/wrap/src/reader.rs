@@ -0,0 +1,187 @@
+use crate::{BinaryFormat, Codec, FileLocation, Format, Output, Result, file::File};
+use num::ToPrimitive as _;
+use std::ops::Range;
+
+/// The size of the ranges that are requested.
+const PAGE_SIZE: usize = 4096;
+
+/// Read a manifest without access to the whole file, for example from a blob that supports ranged reads. The reader does no I/O itself: call [`ManifestReader::missing`] to get the ranges it needs, provide them with [`ManifestReader::supply`], and repeat until no ranges are missing. Then call [`ManifestReader::finish`].
+///
+/// Only the headers and the manifest are read, so this is much cheaper than reading the file for large binaries.
+pub struct ManifestReader {
+	file: File<'static>,
+	pages: Vec<bool>,
+	hint: Option<Format>,
+	format: Option<Box<dyn BinaryFormat>>,
+}
+
+impl ManifestReader {
+	/// Create a reader for a file of the given size.
+	#[must_use]
+	pub fn new(size: u64, format: Option<Format>) -> Self {
+		// The buffer is zeroed lazily by the allocator, so only the pages that are supplied use memory.
+		let size = size.to_usize().unwrap();
+		Self {
+			file: File::from_vec(vec![0; size]),
+			pages: vec![false; size.div_ceil(PAGE_SIZE)],
+			hint: format,
+			format: None,
+		}
+	}
+
+	/// Get the ranges that must be supplied before the manifest can be read. Returns an empty list when the reader is ready to finish.
+	pub fn missing(&mut self) -> Result<Vec<Range<u64>>> {
+		// Read the first page to detect the format.
+		let header = FileLocation {
+			offset: 0,
+			length: PAGE_SIZE.min(self.file.len()),
+		};
+		if !self.is_supplied(header) {
+			return Ok(self.missing_pages(&[header]));
+		}
+		if self.format.is_none() {
+			self.format = Some(crate::create_format(&self.file, self.hint)?);
+		}
+		let format = self.format.as_ref().unwrap();
+
+		// Read the headers, and then the manifest they point to.
+		let mut ranges = format.manifest_ranges(&self.file)?;
+		if ranges.iter().all(|range| self.is_supplied(*range))
+			&& let Some(location) = format.read_manifest(&self.file)?
+		{
+			ranges.push(location);
+		}
+		Ok(self.missing_pages(&ranges))
+	}
+
+	/// Supply the bytes at an offset of the file.
+	pub fn supply(&mut self, offset: u64, bytes: &[u8]) -> Result<()> {
+		let start = offset.to_usize().unwrap();
+		let end = start
+			.checked_add(bytes.len())
+			.filter(|end| *end <= self.file.len())
+			.ok_or_else(|| std::io::Error::other("the bytes extend past the end of the file"))?;
+		self.file.patch(start, bytes)?;
+		let first = start.div_ceil(PAGE_SIZE);
+		let last = if end == self.file.len() {
+			self.pages.len()
+		} else {
+			end / PAGE_SIZE
+		};
+		for page in first..last {
+			self.pages[page] = true;
+		}
+		Ok(())
+	}
+
+	/// Read the manifest from the supplied bytes.
+	pub fn finish<T: Codec>(self) -> Result<Output<T>> {
+		crate::read_manifest_from_file(&self.file, self.hint)
+	}
+
+	fn is_supplied(&self, location: FileLocation) -> bool {
+		self.pages(location).all(|page| self.pages[page])
+	}
+
+	/// Get the missing pages that cover the locations, merged into ranges.
+	fn missing_pages(&self, locations: &[FileLocation]) -> Vec<Range<u64>> {
+		let mut pages = locations
+			.iter()
+			.flat_map(|location| self.pages(*location))
+			.filter(|page| !self.pages[*page])
+			.collect::<Vec<_>>();
+		pages.sort_unstable();
+		pages.dedup();
+		let mut ranges: Vec<Range<u64>> = Vec::new();
+		for page in pages {
+			let start = (page * PAGE_SIZE).to_u64().unwrap();
+			let end = ((page + 1) * PAGE_SIZE)
+				.min(self.file.len())
+				.to_u64()
+				.unwrap();
+			match ranges.last_mut() {
+				Some(range) if range.end == start => range.end = end,
+				_ => ranges.push(start..end),
+			}
+		}
+		ranges
+	}
+
+	/// Get the pages of a location that lies within the file.
+	fn pages(&self, location: FileLocation) -> Range<usize> {
+		let end = location.end().min(self.file.len());
+		let start = location.offset.min(end);
+		if start == end {
+			return 0..0;
+		}
+		start / PAGE_SIZE..end.div_ceil(PAGE_SIZE)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ManifestReader;
+	use crate::{Options, Output};
+	use num::ToPrimitive as _;
+	use std::path::Path;
+
+	fn read(bytes: &[u8]) -> (Output<serde_json::Value>, usize) {
+		let mut reader = ManifestReader::new(bytes.len().to_u64().unwrap(), None);
+		let mut read = 0;
+		loop {
+			let ranges = reader.missing().unwrap();
+			if ranges.is_empty() {
+				break;
+			}
+			for range in ranges {
+				let range = range.start.to_usize().unwrap()..range.end.to_usize().unwrap();
+				read += range.len();
+				reader
+					.supply(range.start.to_u64().unwrap(), &bytes[range])
+					.unwrap();
+			}
+		}
+		(reader.finish().unwrap(), read)
+	}
+
+	#[test]
+	fn reads_only_headers_and_manifest() {
+		let manifest = serde_json::json!({ "args": ["ranged"] });
+		for name in ["elf32_lsb", "elf64_msb", "mach_universal"] {
+			// Pad the binary so the manifest is far from its headers.
+			let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+				.join("fixtures")
+				.join(name);
+			let file = tempfile::NamedTempFile::new().unwrap();
+			let mut bytes = std::fs::read(path).unwrap();
+			if name.starts_with("elf") {
+				bytes.resize(bytes.len() + (1 << 20), 0);
+			}
+			std::fs::write(file.path(), bytes).unwrap();
+			crate::write_manifest(file.path(), &manifest, None, Options::default()).unwrap();
+
+			let bytes = std::fs::read(file.path()).unwrap();
+			let (output, read) = read(&bytes);
+			let expected = crate::read_manifest::<serde_json::Value>(file.path(), None).unwrap();
+			assert_eq!(output.manifest, Some(manifest.clone()), "{name}");
+			assert_eq!(
+				output.location.map(|location| location.range()),
+				expected.location.map(|location| location.range()),
+				"{name}"
+			);
+			if name.starts_with("elf") {
+				assert!(read < bytes.len() / 16, "{name}: read {read} bytes");
+			}
+		}
+	}
+
+	#[test]
+	fn not_a_binary() {
+		let bytes = [0xcc; 100];
+		let mut reader = ManifestReader::new(100, None);
+		let range = reader.missing().unwrap().pop().unwrap();
+		assert_eq!(range, 0..100);
+		reader.supply(0, &bytes).unwrap();
+		assert!(reader.missing().unwrap_err().is_malformed());
+	}
+}
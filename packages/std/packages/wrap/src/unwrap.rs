@@ -0,0 +1,161 @@
+use crate::{Error, Format, Result};
+use serde_json::Value;
+use std::{
+	collections::BTreeSet,
+	path::{Path, PathBuf},
+};
+
+/// The result of unwrapping a wrapper.
+#[derive(Clone, Debug)]
+pub enum Unwrapped {
+	/// The wrapper was embedded in the executable, which was restored in place.
+	Restored,
+
+	/// The wrapper runs the executable at this path.
+	Path(PathBuf),
+}
+
+/// Unwrap a wrapper. Embedded wrappers are removed from the file in place. For wrappers that run an executable by path, the path is returned with artifacts resolved in `artifacts_dir`, or in the directory the wrapper runtime would use.
+pub fn unwrap(
+	path: impl AsRef<Path>,
+	format: Option<Format>,
+	artifacts_dir: Option<&Path>,
+) -> Result<Unwrapped> {
+	let path = path.as_ref();
+	let manifest = crate::read_manifest::<Value>(path, format)?
+		.manifest
+		.ok_or_else(|| Error::UnsupportedFormat("the file is not a wrapper".to_owned()))?;
+	let executable = manifest.get("executable").unwrap_or(&Value::Null);
+	let value = executable.get("value").unwrap_or(&Value::Null);
+	match executable.get("kind").and_then(Value::as_str) {
+		Some("address") => {
+			let entrypoint = value
+				.as_u64()
+				.ok_or_else(|| Error::UnsupportedFormat("invalid entrypoint".to_owned()))?;
+			crate::modify(path, |file| {
+				let binary_format = crate::create_format(file, format)?;
+				binary_format.unwrap(file, entrypoint)
+			})?;
+			Ok(Unwrapped::Restored)
+		},
+		Some("path") => {
+			let artifacts_dir = match artifacts_dir {
+				Some(artifacts_dir) => artifacts_dir.to_owned(),
+				None => manifest_artifacts_dir(&manifest, path)?,
+			};
+			let mut value = value.clone();
+			if is_relocatable(&manifest) {
+				expand_origin(&mut value, &origin(path)?);
+			}
+			Ok(Unwrapped::Path(resolve(&value, &artifacts_dir)?.into()))
+		},
+		Some(kind) => Err(Error::UnsupportedFormat(format!(
+			"cannot unwrap an executable of kind {kind}"
+		))),
+		None => Err(Error::UnsupportedFormat(
+			"the manifest has no executable".to_owned(),
+		)),
+	}
+}
+
+/// Find the artifacts directory the same way the wrapper runtime does: `/.tangram/artifacts`, then `/opt/tangram/artifacts`, then `.tangram/artifacts` in the nearest ancestor of the wrapper.
+#[must_use]
+pub fn find_artifacts_dir(wrapper: &Path) -> Option<PathBuf> {
+	let candidates = [
+		Path::new("/.tangram/artifacts"),
+		Path::new("/opt/tangram/artifacts"),
+	];
+	if let Some(path) = candidates.into_iter().find(|path| path.exists()) {
+		return Some(path.to_owned());
+	}
+	let wrapper = std::path::absolute(wrapper).ok()?;
+	wrapper
+		.ancestors()
+		.skip(1)
+		.map(|ancestor| ancestor.join(".tangram/artifacts"))
+		.find(|path| path.exists())
+}
+
+/// Whether a manifest is relocatable, which means its artifacts are in a directory relative to the wrapper and `$ORIGIN` in its templates is the directory of the wrapper.
+pub(crate) fn is_relocatable(manifest: &Value) -> bool {
+	manifest["artifactsPath"].is_string()
+}
+
+/// Get the directory that `$ORIGIN` refers to, which is the parent of the canonical path of the wrapper, as the runtime gets it from `/proc/self/exe`.
+pub(crate) fn origin(wrapper: &Path) -> Result<PathBuf> {
+	let wrapper = wrapper.canonicalize()?;
+	Ok(wrapper.parent().unwrap_or(Path::new("/")).to_owned())
+}
+
+/// Get the artifacts directory of a wrapper, which is in its manifest if it is relocatable, or wherever the runtime would find it.
+pub(crate) fn manifest_artifacts_dir(manifest: &Value, wrapper: &Path) -> Result<PathBuf> {
+	if let Some(artifacts_path) = manifest["artifactsPath"].as_str() {
+		return Ok(origin(wrapper)?.join(artifacts_path));
+	}
+	let artifacts_dir = find_artifacts_dir(wrapper).ok_or_else(|| {
+		std::io::Error::new(
+			std::io::ErrorKind::NotFound,
+			"failed to find the artifacts directory",
+		)
+	})?;
+	Ok(artifacts_dir)
+}
+
+/// Replace a leading `$ORIGIN` in the string components of every template with `origin`.
+pub(crate) fn expand_origin(value: &mut Value, origin: &Path) {
+	match value {
+		Value::Object(object) => {
+			if let Some(Value::Array(components)) = object.get_mut("components") {
+				for component in components {
+					if component["kind"] != "string" {
+						continue;
+					}
+					if let Some(rest) = component["value"]
+						.as_str()
+						.and_then(|string| string.strip_prefix("$ORIGIN"))
+					{
+						component["value"] = format!("{}{rest}", origin.display()).into();
+					}
+				}
+			}
+			for value in object.values_mut() {
+				expand_origin(value, origin);
+			}
+		},
+		Value::Array(values) => {
+			for value in values {
+				expand_origin(value, origin);
+			}
+		},
+		_ => (),
+	}
+}
+
+/// Render a template, resolving artifacts in the artifacts directory.
+pub(crate) fn resolve(template: &Value, artifacts_dir: &Path) -> Result<String> {
+	let components = template
+		.get("components")
+		.and_then(Value::as_array)
+		.ok_or_else(|| Error::UnsupportedFormat("invalid executable path".to_owned()))?;
+	let mut path = String::new();
+	for component in components {
+		let value = component.get("value").unwrap_or(&Value::Null);
+		match component.get("kind").and_then(Value::as_str) {
+			Some("string") => path.push_str(value.as_str().unwrap_or_default()),
+			Some("artifact") => {
+				let mut ids = BTreeSet::new();
+				crate::inspect::collect_artifacts(value, &mut ids);
+				let id = ids
+					.pop_first()
+					.ok_or_else(|| Error::UnsupportedFormat("invalid artifact".to_owned()))?;
+				path.push_str(&artifacts_dir.join(id).to_string_lossy());
+			},
+			_ => {
+				return Err(Error::UnsupportedFormat(format!(
+					"cannot resolve the template component {component}"
+				)));
+			},
+		}
+	}
+	Ok(path)
+}
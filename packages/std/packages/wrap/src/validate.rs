@@ -0,0 +1,272 @@
+use crate::{Error, Result};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// A manifest that the wrapper runtime would reject or misinterpret.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+	/// An address executable is only meaningful in a wrapper embedded in the executable.
+	AddressNotEmbedded,
+
+	/// The interpreter ignores a field, such as the library paths of a normal interpreter.
+	Ignored {
+		interpreter: &'static str,
+		field: &'static str,
+	},
+
+	/// A path is an empty template.
+	EmptyPath(&'static str),
+
+	/// A library path appears more than once.
+	DuplicateLibraryPath(String),
+
+	/// The environment is not unset or set to a map.
+	UnsupportedEnv,
+
+	/// A part of the manifest does not have the expected shape.
+	Malformed(String),
+}
+
+impl std::fmt::Display for ValidationError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::AddressNotEmbedded => write!(
+				f,
+				"the executable is an address, but the wrapper is not embedded"
+			),
+			Self::Ignored { interpreter, field } => {
+				write!(f, "the {interpreter} interpreter ignores {field}")
+			},
+			Self::EmptyPath(what) => write!(f, "the {what} path is empty"),
+			Self::DuplicateLibraryPath(path) => write!(f, "duplicate library path {path}"),
+			Self::UnsupportedEnv => write!(f, "the environment must be unset or set to a map"),
+			Self::Malformed(message) => write!(f, "{message}"),
+		}
+	}
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Check that a manifest is one the wrapper runtime accepts. An address executable is only allowed if `embedded` is set.
+pub fn validate(manifest: &Value, embedded: bool) -> Result<()> {
+	validate_manifest(manifest, embedded).map_err(Error::Invalid)
+}
+
+fn validate_manifest(manifest: &Value, embedded: bool) -> Result<(), ValidationError> {
+	if !manifest.is_object() {
+		return Err(malformed("the manifest is not an object"));
+	}
+
+	// Validate the executable.
+	let executable = &manifest["executable"];
+	match executable["kind"].as_str() {
+		Some("path") => path(&executable["value"], "executable")?,
+		Some("content") => template(&executable["value"])?,
+		Some("address") if executable["value"].is_u64() => {
+			if !embedded {
+				return Err(ValidationError::AddressNotEmbedded);
+			}
+		},
+		_ => return Err(malformed("invalid executable")),
+	}
+
+	// Validate the interpreter.
+	let interpreter = &manifest["interpreter"];
+	match interpreter["kind"].as_str() {
+		_ if interpreter.is_null() => (),
+		Some("normal") => {
+			path(&interpreter["path"], "interpreter")?;
+			templates(&interpreter["args"])?;
+			ignored(interpreter, "normal", &["libraryPaths", "preloads"])?;
+		},
+		Some("ld-linux" | "ld-musl") => {
+			path(&interpreter["path"], "interpreter")?;
+			library_paths(&interpreter["libraryPaths"])?;
+			preloads(&interpreter["preloads"])?;
+			templates(&interpreter["args"])?;
+		},
+		Some("dyld") => {
+			library_paths(&interpreter["libraryPaths"])?;
+			preloads(&interpreter["preloads"])?;
+			ignored(interpreter, "dyld", &["path", "args"])?;
+		},
+		_ => return Err(malformed("invalid interpreter")),
+	}
+
+	// Validate the environment.
+	let env = &manifest["env"];
+	match env["kind"].as_str() {
+		_ if env.is_null() => (),
+		Some("unset") => (),
+		Some("set") if env["value"]["kind"] == "map" && env["value"]["value"].is_object() => (),
+		_ => return Err(ValidationError::UnsupportedEnv),
+	}
+
+	// Validate the args.
+	templates(&manifest["args"])?;
+
+	// Validate the artifacts path of a relocatable manifest.
+	match &manifest["artifactsPath"] {
+		Value::Null => (),
+		Value::String(path) if !path.is_empty() && !path.starts_with('/') => (),
+		_ => return Err(malformed("the artifacts path must be a relative path")),
+	}
+
+	Ok(())
+}
+
+fn library_paths(value: &Value) -> Result<(), ValidationError> {
+	let mut seen = BTreeSet::new();
+	for value in array(value)? {
+		path(value, "library")?;
+		let rendered = crate::inspect::render(value);
+		if !seen.insert(rendered.trim_end_matches('/').to_owned()) {
+			return Err(ValidationError::DuplicateLibraryPath(rendered));
+		}
+	}
+	Ok(())
+}
+
+fn preloads(value: &Value) -> Result<(), ValidationError> {
+	for value in array(value)? {
+		path(value, "preload")?;
+	}
+	Ok(())
+}
+
+fn templates(value: &Value) -> Result<(), ValidationError> {
+	for value in array(value)? {
+		template(value)?;
+	}
+	Ok(())
+}
+
+fn path(value: &Value, what: &'static str) -> Result<(), ValidationError> {
+	template(value)?;
+	if crate::inspect::render(value).is_empty() {
+		return Err(ValidationError::EmptyPath(what));
+	}
+	Ok(())
+}
+
+fn template(value: &Value) -> Result<(), ValidationError> {
+	let components = value["components"]
+		.as_array()
+		.ok_or_else(|| malformed("expected a template"))?;
+	for component in components {
+		let valid = match component["kind"].as_str() {
+			Some("string") => component["value"].is_string(),
+			Some("artifact" | "placeholder") => !component["value"].is_null(),
+			_ => false,
+		};
+		if !valid {
+			return Err(malformed(&format!(
+				"invalid template component {component}"
+			)));
+		}
+	}
+	Ok(())
+}
+
+fn array(value: &Value) -> Result<&[Value], ValidationError> {
+	match value {
+		Value::Null => Ok(&[]),
+		Value::Array(values) => Ok(values),
+		_ => Err(malformed("expected an array")),
+	}
+}
+
+/// Check that the interpreter does not set fields that the runtime parses but does not use.
+fn ignored(
+	interpreter: &Value,
+	kind: &'static str,
+	fields: &[&'static str],
+) -> Result<(), ValidationError> {
+	for field in fields {
+		let value = &interpreter[*field];
+		if !value.is_null() && value.as_array().is_none_or(|values| !values.is_empty()) {
+			return Err(ValidationError::Ignored {
+				interpreter: kind,
+				field,
+			});
+		}
+	}
+	Ok(())
+}
+
+fn malformed(message: &str) -> ValidationError {
+	ValidationError::Malformed(message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ValidationError, validate_manifest};
+	use serde_json::json;
+
+	#[test]
+	fn validate() {
+		let string = |value: &str| json!({ "components": [{ "kind": "string", "value": value }] });
+		let manifest = json!({
+			"interpreter": {
+				"kind": "ld-linux",
+				"path": string("/lib/ld-linux-x86-64.so.2"),
+				"libraryPaths": [string("/lib"), string("/usr/lib")],
+			},
+			"executable": { "kind": "path", "value": string("/bin/sh") },
+			"env": { "kind": "set", "value": { "kind": "map", "value": { "FOO": "bar" } } },
+			"args": [string("-c")],
+		});
+		assert_eq!(validate_manifest(&manifest, false), Ok(()));
+
+		let mut duplicate = manifest.clone();
+		duplicate["interpreter"]["libraryPaths"] = json!([string("/lib"), string("/lib/")]);
+		assert_eq!(
+			validate_manifest(&duplicate, false),
+			Err(ValidationError::DuplicateLibraryPath("/lib/".to_owned()))
+		);
+
+		let mut musl = manifest.clone();
+		musl["interpreter"]["kind"] = json!("ld-musl");
+		musl["interpreter"]["preloads"] = json!([string("/lib/libinject.so")]);
+		assert_eq!(validate_manifest(&musl, false), Ok(()));
+
+		let mut normal = musl.clone();
+		normal["interpreter"]["kind"] = json!("normal");
+		normal["interpreter"]["libraryPaths"] = json!([]);
+		assert_eq!(
+			validate_manifest(&normal, false),
+			Err(ValidationError::Ignored {
+				interpreter: "normal",
+				field: "preloads"
+			})
+		);
+
+		let mut empty = manifest.clone();
+		empty["executable"]["value"] = json!({ "components": [] });
+		assert_eq!(
+			validate_manifest(&empty, false),
+			Err(ValidationError::EmptyPath("executable"))
+		);
+
+		let mut address = manifest.clone();
+		address["executable"] = json!({ "kind": "address", "value": 4096 });
+		assert_eq!(
+			validate_manifest(&address, false),
+			Err(ValidationError::AddressNotEmbedded)
+		);
+		assert_eq!(validate_manifest(&address, true), Ok(()));
+
+		let mut relocatable = manifest.clone();
+		relocatable["artifactsPath"] = json!("/artifacts");
+		assert!(validate_manifest(&relocatable, false).is_err());
+		relocatable["artifactsPath"] = json!(".tangram/artifacts");
+		assert_eq!(validate_manifest(&relocatable, false), Ok(()));
+
+		let mut env = manifest;
+		env["env"] = json!({ "kind": "prepend", "values": [] });
+		assert_eq!(
+			validate_manifest(&env, false),
+			Err(ValidationError::UnsupportedEnv)
+		);
+	}
+}
@@ -0,0 +1,177 @@
+use crate::{Encoding, Error, Format, Result, file::File};
+use num::ToPrimitive as _;
+use std::path::Path;
+
+/// The name of the ELF section that holds the wrapper runtime.
+const WRAPPER_SECTION_NAME: &str = ".text.tg-wrapper";
+
+/// The result of checking a binary for a consistent wrapper.
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct Verification {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub format: Option<Format>,
+
+	/// The version of the manifest, if the file has one.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub version: Option<u64>,
+
+	/// The BLAKE3 checksum of the manifest, if it has one.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub checksum: Option<String>,
+
+	/// Everything that is inconsistent about the file.
+	pub problems: Vec<String>,
+}
+
+impl Verification {
+	/// Whether the file has a manifest.
+	#[must_use]
+	pub fn is_wrapper(&self) -> bool {
+		self.version.is_some()
+	}
+
+	#[must_use]
+	pub fn is_ok(&self) -> bool {
+		self.problems.is_empty()
+	}
+}
+
+/// Check that the manifest, section table and wrapper section of a binary are consistent. Files that are not wrappers verify successfully. Errors are only returned if the file cannot be read.
+pub fn verify(path: impl AsRef<Path>, format: Option<Format>) -> Result<Verification> {
+	let path = path.as_ref();
+	let mut verification = Verification::default();
+	let format = match format {
+		Some(format) => format,
+		None => match crate::detect_format(path) {
+			Ok(Some(format)) => format,
+			Ok(None) => return Ok(verification),
+			Err(error) if error.is_malformed() => {
+				verification.problems.push(error.to_string());
+				return Ok(verification);
+			},
+			Err(error) => return Err(error),
+		},
+	};
+	verification.format = Some(format);
+	let file = File::open(path)?;
+	if let Err(error) = check(&file, format, &mut verification) {
+		if !error.is_malformed() {
+			return Err(error);
+		}
+		verification.problems.push(error.to_string());
+	}
+	Ok(verification)
+}
+
+fn check(file: &File, format: Format, verification: &mut Verification) -> Result<()> {
+	let binary_format = crate::create_format(file, Some(format))?;
+	let file_size = file.file_size()?.to_usize().unwrap();
+
+	// Check the section table.
+	let sections = binary_format.sections(file)?;
+	for section in &sections {
+		if section.end() > file_size {
+			verification.problems.push(format!(
+				"section {} extends past the end of the file ({:#x} > {file_size:#x})",
+				section.name,
+				section.end()
+			));
+		}
+	}
+
+	// Check the wrapper section.
+	let wrapper = sections
+		.iter()
+		.find(|section| section.name == WRAPPER_SECTION_NAME);
+	if let Some(wrapper) = wrapper {
+		if wrapper.length == 0 {
+			verification
+				.problems
+				.push(format!("section {WRAPPER_SECTION_NAME} is empty"));
+		}
+		if wrapper
+			.segment
+			.as_ref()
+			.is_none_or(|segment| segment.kind != "PT_LOAD")
+		{
+			verification.problems.push(format!(
+				"section {WRAPPER_SECTION_NAME} is not mapped by a PT_LOAD segment"
+			));
+		}
+	}
+
+	// Check the manifest.
+	let Some(location) = binary_format.read_manifest(file)? else {
+		if wrapper.is_some() {
+			verification
+				.problems
+				.push("the file has a wrapper section but no manifest".to_owned());
+		}
+		return Ok(());
+	};
+	if location.end() > file_size {
+		verification
+			.problems
+			.push("the manifest extends past the end of the file".to_owned());
+		return Ok(());
+	}
+	let data = file.bytes(location, "manifest")?;
+	let footer = crate::Footer::read(data)?;
+	verification.version = Some(footer.version);
+	let layout = crate::encoding::layout(data)?;
+	verification.checksum = layout
+		.checksum
+		.map(|checksum| checksum.to_hex().to_string());
+	let payload = match crate::encoding::payload(data) {
+		Ok((_, payload)) => payload,
+		Err(Error::ChecksumMismatch { expected, found }) => {
+			verification.problems.push(format!(
+				"the manifest does not match its checksum (expected {expected}, found {found})"
+			));
+			return Ok(());
+		},
+		Err(error) => return Err(error),
+	};
+	if layout.options.encoding == Encoding::Json {
+		serde_json::from_slice::<serde_json::Value>(&payload)?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{Error, Options};
+	use std::path::Path;
+
+	#[test]
+	fn checksum_mismatch() {
+		let file = tempfile::NamedTempFile::new().unwrap();
+		let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/elf64_lsb");
+		std::fs::copy(fixture, file.path()).unwrap();
+		let verification = crate::verify(file.path(), None).unwrap();
+		assert!(verification.is_ok() && !verification.is_wrapper());
+
+		let manifest = serde_json::json!({ "args": ["checked"] });
+		crate::write_manifest(file.path(), &manifest, None, Options::default()).unwrap();
+		let verification = crate::verify(file.path(), None).unwrap();
+		assert!(verification.is_ok(), "{:?}", verification.problems);
+		let checksum = verification.checksum.unwrap();
+
+		// Flip a byte of the payload.
+		let output = crate::read_manifest::<serde_json::Value>(file.path(), None).unwrap();
+		let offset = output.location.unwrap().offset;
+		let mut bytes = std::fs::read(file.path()).unwrap();
+		bytes[offset] ^= 0xff;
+		std::fs::write(file.path(), &bytes).unwrap();
+
+		assert!(matches!(
+			crate::read_manifest::<serde_json::Value>(file.path(), None),
+			Err(Error::ChecksumMismatch { expected, .. }) if expected == checksum
+		));
+		let verification = crate::verify(file.path(), None).unwrap();
+		assert_eq!(verification.checksum, Some(checksum));
+		assert_eq!(verification.problems.len(), 1);
+		assert!(verification.problems[0].contains("checksum"));
+	}
+}
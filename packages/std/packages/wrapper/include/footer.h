@@ -5,3 +5,20 @@ typedef struct {
 	uint64_t version;
 	char	 magic[8];
 } Footer;
+
+// On ELF targets, the manifest is stored in a note with this name and type.
+#define TANGRAM_NOTE_NAME		"tangram"
+#define NT_TANGRAM_MANIFEST		0x7467
+
+// Version 1 manifests are followed by a descriptor, between the manifest and the footer.
+#define MANIFEST_ENCODING_JSON		0
+#define MANIFEST_COMPRESSION_NONE	0
+typedef struct {
+	uint8_t		encoding;
+	uint8_t		compression;
+	uint8_t		reserved[6];
+	uint64_t	size;
+} Descriptor;
+
+// Version 2 manifests store a BLAKE3 checksum of the manifest data before the descriptor.
+#define MANIFEST_CHECKSUM_SIZE		32
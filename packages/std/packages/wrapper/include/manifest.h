@@ -39,6 +39,7 @@ typedef struct {
 	Arena*		arena;
 	Manifest*	manifest;
 	String		artifacts_dir;
+	String		origin;
 	bool		enable_tracing;
 } Cx;
 
@@ -135,20 +136,10 @@ TG_VISIBILITY void parse_manifest (
 	// Sanity check.
 	ABORT_IF(len == 0, "expected a non-zero length");
 
-	// Find the artifacts directory.
-	String artifacts_dir;
-	find_artifacts_dir(arena, &artifacts_dir);
-	if (enable_tracing) {
-		trace("artifacts directory:");
-		print_json_string(&artifacts_dir);
-		trace("\n");
-	}
-
 	// Create the context.
 	Cx cx = {
 		.arena = arena,
 		.manifest = manifest,
-		.artifacts_dir = artifacts_dir,
 		.enable_tracing = enable_tracing
 	};
 
@@ -178,6 +169,23 @@ TG_VISIBILITY void parse_manifest (
 	if (cx.enable_tracing) {
 		trace("parsed manifest json\n");
 	}
+
+	// Find the artifacts directory. A relocatable manifest has one relative to the directory of the wrapper.
+	ABORT_IF(value.kind != JSON_OBJECT, "expected an object");
+	JsonValue* artifacts_path = json_get(&value.value._object, "artifactsPath");
+	if (artifacts_path && artifacts_path->kind != JSON_NULL) {
+		ABORT_IF(artifacts_path->kind != JSON_STRING, "expected a string");
+		cx.origin = parent_dir(executable_path(arena));
+		String ss[2] = { cx.origin, artifacts_path->value._string };
+		cx.artifacts_dir = join(arena, STRING_LITERAL("/"), ss, 2);
+	} else {
+		find_artifacts_dir(arena, &cx.artifacts_dir);
+	}
+	if (cx.enable_tracing) {
+		trace("artifacts directory:");
+		print_json_string(&cx.artifacts_dir);
+		trace("\n");
+	}
 	parse_manifest_from_json(&cx, &value);
 	if (cx.enable_tracing) {
 		trace("parsed manifest\n");
@@ -511,7 +519,16 @@ TG_VISIBILITY void apply_env (Cx* cx, JsonObject* env) {
 				while(array) {
 					if (array->value) {
 						ABORT_IF(array->value->kind != JSON_OBJECT, "expected an object");
-						apply_mutation_to_key(cx, key, &array->value->value._object);
+						JsonObject* mutation = &array->value->value._object;
+
+						// Unwrap mutations that are stored as values.
+						JsonValue* kind = json_get(mutation, "kind");
+						if (kind && kind->kind == JSON_STRING && cstreq(kind->value._string, "mutation")) {
+							JsonValue* value = json_get(mutation, "value");
+							ABORT_IF(!value || value->kind != JSON_OBJECT, "expected an object");
+							mutation = &value->value._object;
+						}
+						apply_mutation_to_key(cx, key, mutation);
 					}
 					array = array->next;
 				}
@@ -687,7 +704,14 @@ TG_VISIBILITY void render_template (Cx* cx, JsonValue* template, String* rendere
 			ABORT_IF(kind->kind != JSON_STRING, "expected a string");
 			ABORT_IF(value->kind != JSON_STRING, "expected a string");
 			if (cstreq(kind->value._string, "string")) {
-				append_to_string(rendered, &value->value._string, capacity);
+				// In a relocatable manifest, a leading $ORIGIN is the directory of the wrapper.
+				String string = value->value._string;
+				if (cx->origin.ptr && starts_with(string, STRING_LITERAL("$ORIGIN"))) {
+					append_to_string(rendered, &cx->origin, capacity);
+					string.ptr += 7;
+					string.len -= 7;
+				}
+				append_to_string(rendered, &string, capacity);
 			} else if (cstreq(kind->value._string, "artifact")) {
 				append_to_string(rendered, &cx->artifacts_dir, capacity);
 				append_ch_to_string(rendered, '/', capacity);
@@ -86,6 +86,9 @@ TG_VISIBILITY ProgramHeaders create_program_headers (
 
 // Allocate and setup the new executable stack before jumping to the new entrypoint.
 TG_VISIBILITY void* prepare_executable_stack (Arena* arena, Stack* stack, Manifest* manifest, Options* options);
+
+// Find the manifest note among ELF notes, returning its data and writing its size.
+TG_VISIBILITY char* find_manifest_note (char* notes, size_t size, size_t* manifest_size);
 #endif
 
 #ifdef __APPLE__
@@ -429,10 +432,10 @@ TG_VISIBILITY Executable create_executable (Arena* arena, Stack* stack, Options*
 		trace("opened %s (%d)\n", path.ptr, fd);
 	}
 #ifdef __linux__
-	// On ELF targets, the manifest is embedded in an ELF section. To read it we read the ELF
-	// file, walk the file contents, and read the manifest directly from the section. In the
-	// future we may choose to make the section loadable and read only to avoid needing to open
-	// the file at all.
+	// On ELF targets, the manifest is stored in an ELF note named "tangram". Wrappers have a
+	// PT_NOTE program header that covers the note, so it can be found even if the section headers
+	// were stripped. Older wrappers only have the manifest section, so we then read the section
+	// headers and look for it.
 	executable.elf_header = ALLOC(arena, Elf64_Ehdr);
 
 	// Read the elf header. We don't need to do any validation here, we assume the kernel didn't lie.
@@ -444,55 +447,70 @@ TG_VISIBILITY Executable create_executable (Arena* arena, Stack* stack, Options*
 	executable.program_headers = ALLOC_N(arena, executable.elf_header->e_phnum, Elf64_Phdr);
 	read_all(options->enable_tracing, fd, (char*)executable.program_headers, size, offset);
 
-	// Read the section header table.
-	offset = executable.elf_header->e_shoff;
-	size = executable.elf_header->e_shnum * sizeof(Elf64_Shdr);
-	executable.section_headers = ALLOC_N(arena, executable.elf_header->e_shnum, Elf64_Shdr);
-	read_all(options->enable_tracing, fd, (char*)executable.section_headers, size, offset);
-
-	// Read the section header string table.
-	Elf64_Shdr* section = executable.section_headers + executable.elf_header->e_shstrndx;
-	offset = section->sh_offset;
-	size = section->sh_size;
-	executable.section_string_table = ALLOC_N(arena, size, char);
-	read_all(options->enable_tracing, fd, (char*)executable.section_string_table, size, offset);
-
-	// Get the file size.
-	offset = lseek(fd, 0, SEEK_END);
-	if (offset < 0) {
-		ABORT("failed to seek");
+	// Look for the manifest note in the PT_NOTE segments.
+	size = 0;
+	for (int i = 0; !data && i < executable.elf_header->e_phnum; i++) {
+		Elf64_Phdr* phdr = executable.program_headers + i;
+		if (phdr->p_type != PT_NOTE) {
+			continue;
+		}
+		char* notes = alloc(arena, phdr->p_filesz, 1);
+		read_all(options->enable_tracing, fd, notes, phdr->p_filesz, phdr->p_offset);
+		data = find_manifest_note(notes, phdr->p_filesz, &size);
 	}
-	if (options->enable_tracing) {
-		trace("file size: %d\n", offset);
+	if (data && options->enable_tracing) {
+		trace("found the manifest note in a PT_NOTE segment\n");
 	}
 
-	Elf64_Shdr* section_itr = executable.section_headers;
-	Elf64_Shdr* section_end = section_itr + executable.elf_header->e_shnum;
-	String TANGRAM_MANIFEST_SECTION_NAME = STRING_LITERAL(".note.tg-manifest");
-	for (; section_itr != section_end; section_itr++) {
-		String name = {0};
-		name.ptr = (uint8_t*)&executable.section_string_table[section_itr->sh_name];
-		name.len = tg_strlen((char*)name.ptr);
-		if (options->enable_tracing) {
-			trace("found section ");
-			print_json_string(&name);
-			trace("\n");
-		}
-		if (streq(name, TANGRAM_MANIFEST_SECTION_NAME)) {
-			data	= alloc(arena, section_itr->sh_size, 1);
-			size	= section_itr->sh_size;
-			offset	= section_itr->sh_offset;
-			ABORT_IF(size < sizeof(Footer), "manifest section too small");
+	// Otherwise, look for the manifest section.
+	if (!data && executable.elf_header->e_shnum) {
+		// Read the section header table.
+		offset = executable.elf_header->e_shoff;
+		size = executable.elf_header->e_shnum * sizeof(Elf64_Shdr);
+		executable.section_headers = ALLOC_N(arena, executable.elf_header->e_shnum, Elf64_Shdr);
+		read_all(options->enable_tracing, fd, (char*)executable.section_headers, size, offset);
+
+		// Read the section header string table.
+		Elf64_Shdr* section = executable.section_headers + executable.elf_header->e_shstrndx;
+		offset = section->sh_offset;
+		size = section->sh_size;
+		executable.section_string_table = ALLOC_N(arena, size, char);
+		read_all(options->enable_tracing, fd, (char*)executable.section_string_table, size, offset);
+
+		Elf64_Shdr* section_itr = executable.section_headers;
+		Elf64_Shdr* section_end = section_itr + executable.elf_header->e_shnum;
+		String TANGRAM_MANIFEST_SECTION_NAME = STRING_LITERAL(".note.tg-manifest");
+		for (; section_itr != section_end; section_itr++) {
+			String name = {0};
+			name.ptr = (uint8_t*)&executable.section_string_table[section_itr->sh_name];
+			name.len = tg_strlen((char*)name.ptr);
 			if (options->enable_tracing) {
-				trace("reading manifest at offset: %ld, size: %ld\n", offset, size);
+				trace("found section ");
+				print_json_string(&name);
+				trace("\n");
+			}
+			if (streq(name, TANGRAM_MANIFEST_SECTION_NAME)) {
+				char* section_data = alloc(arena, section_itr->sh_size, 1);
+				offset = section_itr->sh_offset;
+				if (options->enable_tracing) {
+					trace("reading manifest section at offset: %ld, size: %ld\n", offset, section_itr->sh_size);
+				}
+				read_all(options->enable_tracing, fd, section_data, section_itr->sh_size, offset);
+
+				// Manifests written before the section became a note fill the whole section.
+				data = find_manifest_note(section_data, section_itr->sh_size, &size);
+				if (!data) {
+					data = section_data;
+					size = section_itr->sh_size;
+				}
+				break;
 			}
-			read_all(options->enable_tracing, fd, data, size, offset);
-			memcpy((void*)&executable.footer, (void*)(data + (size - sizeof(Footer))), sizeof(Footer));
-			ABORT_IF(executable.footer.size > size - sizeof(Footer), "invalid footer");
-			break;
 		}
 	}
-	ABORT_IF(!data, "failed to find manifest section");
+	ABORT_IF(!data, "failed to find the manifest");
+	ABORT_IF(size < sizeof(Footer), "manifest too small");
+	memcpy((void*)&executable.footer, (void*)(data + (size - sizeof(Footer))), sizeof(Footer));
+	ABORT_IF(executable.footer.size > size - sizeof(Footer), "invalid footer");
 
 #elif defined(__APPLE__)
 	// On Mach platforms, the manifest is embedded into the binary just before the code signature
@@ -534,13 +552,32 @@ TG_VISIBILITY Executable create_executable (Arena* arena, Stack* stack, Options*
 	// Close the file.
 	close(fd);
 
+	// Version 1 manifests are followed by a descriptor, and version 2 manifests by a checksum and a descriptor. Only uncompressed JSON can be parsed here. The checksum is checked by `wrap verify`, not at runtime.
+	size_t manifest_size = executable.footer.size;
+	if (executable.footer.version == 1 || executable.footer.version == 2) {
+		ABORT_IF(manifest_size < sizeof(Descriptor), "invalid manifest descriptor");
+		Descriptor descriptor;
+		memcpy((void*)&descriptor, (void*)(data + (manifest_size - sizeof(Descriptor))), sizeof(Descriptor));
+		ABORT_IF(
+			descriptor.encoding != MANIFEST_ENCODING_JSON || descriptor.compression != MANIFEST_COMPRESSION_NONE,
+			"unsupported manifest encoding, run `wrap migrate --encoding json --compression none`"
+		);
+		manifest_size -= sizeof(Descriptor);
+		if (executable.footer.version == 2) {
+			ABORT_IF(manifest_size < MANIFEST_CHECKSUM_SIZE, "invalid manifest checksum");
+			manifest_size -= MANIFEST_CHECKSUM_SIZE;
+		}
+	} else {
+		ABORT_IF(executable.footer.version != 0, "unsupported manifest version %ld", executable.footer.version);
+	}
+
 	// Parse the manifest.
 	if (options->enable_tracing) {
-		char* manifest_string = ALLOC_N(arena, executable.footer.size + 1, char);
-		memcpy(manifest_string, data, executable.footer.size);
+		char* manifest_string = ALLOC_N(arena, manifest_size + 1, char);
+		memcpy(manifest_string, data, manifest_size);
 		trace("%s\n", manifest_string);
 	}
-	parse_manifest(arena, executable.manifest, options->enable_tracing, (uint8_t*)data, executable.footer.size);
+	parse_manifest(arena, executable.manifest, options->enable_tracing, (uint8_t*)data, manifest_size);
 	
 	// Combine manifest paths with existing LD_LIBRARY_PATH/LD_PRELOAD.
 	String original_ld_library_path = lookup(&executable.manifest->env, STRING_LITERAL(LD_LIBRARY_PATH));
@@ -827,6 +864,26 @@ TG_VISIBILITY Interpreter create_interpreter(
 	return interpreter;
 }
 
+TG_VISIBILITY char* find_manifest_note (char* notes, size_t size, size_t* manifest_size) {
+	size_t offset = 0;
+	while (offset + sizeof(Elf64_Nhdr) <= size) {
+		Elf64_Nhdr* header = (Elf64_Nhdr*)(notes + offset);
+		size_t name = offset + sizeof(Elf64_Nhdr);
+		size_t desc = name + ALIGN(header->n_namesz, 4);
+		if (desc > size || header->n_descsz > size - desc) {
+			break;
+		}
+		String expected = { .ptr = (uint8_t*)TANGRAM_NOTE_NAME, .len = sizeof(TANGRAM_NOTE_NAME) };
+		String found = { .ptr = (uint8_t*)(notes + name), .len = header->n_namesz };
+		if (header->n_type == NT_TANGRAM_MANIFEST && streq(found, expected)) {
+			*manifest_size = header->n_descsz;
+			return notes + desc;
+		}
+		offset = desc + ALIGN(header->n_descsz, 4);
+	}
+	return NULL;
+}
+
 TG_VISIBILITY ProgramHeaders create_program_headers(
 	Arena* arena,
 	Manifest* manifest,
@@ -277,6 +277,15 @@ async function ccProxy(arg: CcProxyArg) {
 	});
 }
 
+/** Create a directory to use as `TANGRAM_WRAPPERS_PATH`, with a subdirectory for the system of each host holding its `wrapper.exe`. */
+async function wrappersDirectory(wrappers: Record<string, tg.File>) {
+	const entries: Record<string, tg.File> = {};
+	for (const [host, wrapper] of Object.entries(wrappers)) {
+		entries[`${std.triple.archAndOs(host)}/wrapper.exe`] = wrapper;
+	}
+	return tg.directory(entries);
+}
+
 type LdProxyArg = {
 	buildToolchain: tg.Directory;
 	build?: string;
@@ -299,23 +308,7 @@ async function ldProxy(arg: LdProxyArg) {
 	// Get the embedded wrapper artifacts.
 	const wrapperBin = await workspace.wrapperBinary({ host, build });
 	const wrapperExe = await workspace.wrapper({ host, build });
-	let objcopy = undefined;
-
-	// Find objcopy. Resolve symlinks to get the actual file.
-	const binDir = await buildToolchain.get("bin").then(tg.Directory.expect);
-	for (let [name, artifact] of Object.entries(await binDir.entries)) {
-		if (name.endsWith("objcopy")) {
-			// If it's a symlink, resolve it to get the actual file.
-			if (artifact instanceof tg.Symlink) {
-				const resolved = await binDir.get(name);
-				if (resolved instanceof tg.File) {
-					objcopy = resolved;
-				}
-			} else if (artifact instanceof tg.File) {
-				objcopy = artifact;
-			}
-		}
-	}
+	const wrappers = await wrappersDirectory({ [host]: wrapperExe });
 
 	// The linker proxy is built for the build machine.
 	const buildLinkerProxy = await workspace.ldProxy({
@@ -363,9 +356,7 @@ async function ldProxy(arg: LdProxyArg) {
 			: (tg.Mutation.unset() as tg.Mutation<tg.File>),
 		TANGRAM_WRAPPER_BIN_PATH: tg.Mutation.set(wrapperBin),
 		TANGRAM_WRAPPER_EXE_PATH: tg.Mutation.set(wrapperExe),
-		TANGRAM_OBJCOPY_PATH: objcopy
-			? tg.Mutation.set(objcopy)
-			: (tg.Mutation.unset() as tg.Mutation<tg.File>),
+		TANGRAM_WRAPPERS_PATH: tg.Mutation.set(wrappers),
 		TGLD_EMBED_WRAPPER: embedWrapper
 			? tg.Mutation.set("true")
 			: (tg.Mutation.unset() as tg.Mutation<string>),
@@ -430,7 +421,9 @@ export async function stripProxy(arg: tg.Unresolved<StripProxyArg>) {
 			TGSTRIP_COMMAND_PATH: tg.Mutation.setIfUnset<
 				tg.File | tg.Symlink | tg.Template
 			>(stripCommand),
-			TANGRAM_WRAPPER_EXE_PATH: tg.Mutation.setIfUnset(hostWrapper),
+			TANGRAM_WRAPPERS_PATH: tg.Mutation.setIfUnset(
+				await wrappersDirectory({ [host]: hostWrapper }),
+			),
 			...(codesign !== undefined
 				? { TANGRAM_CODESIGN_PATH: tg.Mutation.setIfUnset(codesign) }
 				: {}),
@@ -470,6 +463,7 @@ const ldProxyArtifactEnvVars = [
 	"TANGRAM_CODESIGN_PATH",
 	"TANGRAM_WRAPPER_BIN_PATH",
 	"TANGRAM_WRAPPER_EXE_PATH",
+	"TANGRAM_WRAPPERS_PATH",
 	"TGLD_INJECTION_PATH",
 ];
 
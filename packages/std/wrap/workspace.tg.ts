@@ -6,6 +6,7 @@ import cargoToml from "../Cargo.toml" with { type: "file" };
 import cargoLock from "../Cargo.lock" with { type: "file" };
 
 import * as wrapperSrc from "./wrapper.tg.ts";
+import artifactRoots from "../packages/artifact_roots" with { type: "directory" };
 import common from "../packages/common" with { type: "directory" };
 import tgcc from "../packages/tgcc" with { type: "directory" };
 import tgld from "../packages/tgld" with { type: "directory" };
@@ -37,6 +38,7 @@ export async function workspace(...args: tg.Args<Arg>): Promise<tg.Directory> {
 
 	// Get the source.
 	const defaultSource = tg.directory({
+		artifact_roots: artifactRoots,
 		common,
 		tgcc,
 		tgld,